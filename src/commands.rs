@@ -11,8 +11,22 @@ use clap::*;
 use clap::{Subcommand, ValueEnum};
 use strum::{Display, EnumString};
 
+use crate::commands::create_issue_from_run::DEFAULT_REGRESSION_WINDOW_DAYS;
+use crate::gh::ProjectRef;
+use crate::notify::{WebhookFormat, DEFAULT_WEBHOOK_TIMEOUT};
+use crate::project::ProjectField;
+use crate::report::ReportFormat;
+
+pub mod analyze_log;
+pub mod analyze_run;
 pub mod create_issue_from_run;
+pub mod history;
 pub mod locate_failure_log;
+pub mod report;
+pub mod serve;
+pub mod sync_labels;
+pub mod triage_runs;
+pub mod watch;
 
 #[derive(Debug, Subcommand)]
 pub enum Command {
@@ -33,6 +47,263 @@ pub enum Command {
         /// Don't create the issue if a similar issue already exists
         #[arg(short, long, default_value_t = true)]
         no_duplicate: bool,
+        /// Template file for the issue title (minijinja syntax)
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        title_template: Option<PathBuf>,
+        /// Template file for the issue body (minijinja syntax)
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        body_template: Option<PathBuf>,
+        /// Write a job summary to `$GITHUB_STEP_SUMMARY`, print `::error` annotations and set step
+        /// outputs in `$GITHUB_OUTPUT` (for use in a GitHub Actions workflow)
+        #[arg(long, default_value_t = false)]
+        github_actions_output: bool,
+        /// Webhook URL to notify about new (non-duplicate) failures, can be given multiple times
+        #[arg(long, value_hint = ValueHint::Url)]
+        webhook_url: Vec<String>,
+        /// The payload format of the webhook(s)
+        #[arg(long, default_value_t = WebhookFormat::default())]
+        webhook_format: WebhookFormat,
+        /// Seconds to wait for a webhook to connect and to respond
+        #[arg(long, default_value_t = DEFAULT_WEBHOOK_TIMEOUT.as_secs(), value_name = "SECONDS")]
+        webhook_timeout: u64,
+        /// Only send webhook notifications, don't create an issue
+        #[arg(long, default_value_t = false, requires = "webhook_url")]
+        no_issue: bool,
+        /// What to do if the failure matches a recently closed issue
+        #[arg(long, default_value_t = RegressionAction::default())]
+        on_regression: RegressionAction,
+        /// How many days back to look for closed issues matching the failure
+        #[arg(long, default_value_t = DEFAULT_REGRESSION_WINDOW_DAYS)]
+        regression_window_days: u64,
+        /// Create one issue for the whole run or one issue per failed job
+        #[arg(long, default_value_t = IssuePer::default())]
+        issue_per: IssuePer,
+        /// Also create a tracking issue with a task list of the per-job issues (with `--issue-per job`)
+        #[arg(long, default_value_t = false)]
+        tracking_issue: bool,
+        /// Title of the milestone to add created issues to
+        #[arg(long)]
+        milestone: Option<String>,
+        /// GitHub Project (v2) to add created issues to as `<owner>/<number>` (requires the
+        /// `project` scope of the GitHub token)
+        #[arg(long)]
+        project: Option<ProjectRef>,
+        /// Field of the project item to set as `<name>=<value>`, can be given multiple times.
+        /// The value is a template like `--title-template` e.g. `Component={{ failed_jobs[0].recipe }}`
+        #[arg(long, requires = "project")]
+        project_field: Vec<ProjectField>,
+        /// Detect flaky jobs (failed and passed for the same commit) in the recent runs of the
+        /// workflow, issues where every failed job is flaky are labeled `flaky`
+        #[arg(long, default_value_t = false)]
+        detect_flaky: bool,
+        /// How many recent runs of the workflow to look at for flaky jobs
+        #[arg(long, default_value_t = 20)]
+        flaky_history: usize,
+        /// Don't create an issue if every failed job is flaky with a flake rate below this (0 to 1)
+        #[arg(long, requires = "detect_flaky", value_parser = parse_rate)]
+        suppress_flaky_below: Option<f64>,
+    },
+
+    /// Analyze a failed workflow run and print the parsed result as JSON without creating an issue
+    AnalyzeRun {
+        /// The GitHub repository to parse
+        #[arg(long, value_hint = ValueHint::Url)]
+        repo: String,
+        /// The GitHub workflow run ID
+        #[arg(short = 'r', long)]
+        run_id: String,
+        /// The GitHub issue label (used for labeling and the duplicate check)
+        #[arg(short, long)]
+        label: String,
+        /// The kind of workflow (e.g. Yocto)
+        #[arg(short, long)]
+        kind: WorkflowKind,
+        /// Don't compare the result to existing issues
+        #[arg(long, default_value_t = false)]
+        skip_duplicate_check: bool,
+        /// Template file for the issue title (minijinja syntax)
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        title_template: Option<PathBuf>,
+        /// Template file for the issue body (minijinja syntax)
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        body_template: Option<PathBuf>,
+    },
+
+    /// Parse local job log files into an issue without accessing GitHub
+    ///
+    /// Each log file is a failed job of a run. The logs may have the `job<TAB>step<TAB>timestamp`
+    /// prefix of the logs from GitHub, otherwise the job is named after the file.
+    AnalyzeLog {
+        /// The log files
+        #[arg(required = true, value_hint = ValueHint::FilePath)]
+        logs: Vec<PathBuf>,
+        /// The GitHub issue label
+        #[arg(short, long)]
+        label: String,
+        /// The kind of workflow (e.g. Yocto)
+        #[arg(short, long)]
+        kind: WorkflowKind,
+        /// The GitHub repository the logs are from, for the links in the issue
+        #[arg(long, value_hint = ValueHint::Url)]
+        repo: Option<String>,
+        /// The workflow run ID the logs are from
+        #[arg(short = 'r', long, default_value = "local")]
+        run_id: String,
+        /// The name of the workflow
+        #[arg(long, default_value = "")]
+        workflow: String,
+        /// The branch of the run
+        #[arg(long, default_value = "")]
+        branch: String,
+        /// Print the rendered issue or the parsed model as JSON
+        #[arg(long, default_value_t = AnalysisFormat::default())]
+        format: AnalysisFormat,
+        /// Template file for the issue title (minijinja syntax)
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        title_template: Option<PathBuf>,
+        /// Template file for the issue body (minijinja syntax)
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        body_template: Option<PathBuf>,
+    },
+
+    /// Triage the failed runs of a workflow, creating or updating one issue per distinct failure
+    TriageRuns {
+        /// The GitHub repository to parse
+        #[arg(long, value_hint = ValueHint::Url)]
+        repo: String,
+        /// The GitHub issue label
+        #[arg(short, long)]
+        label: String,
+        /// The kind of workflow (e.g. Yocto)
+        #[arg(short, long)]
+        kind: WorkflowKind,
+        /// Only runs of this workflow (name or file name e.g. `build.yml`)
+        #[arg(long)]
+        workflow: Option<String>,
+        /// Only runs on this branch
+        #[arg(long)]
+        branch: Option<String>,
+        /// Only runs created on or after this date (YYYY-MM-DD)
+        #[arg(long)]
+        since: Option<String>,
+        /// Only runs created on or before this date (YYYY-MM-DD)
+        #[arg(long)]
+        until: Option<String>,
+        /// Maximum number of runs to triage
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+        /// Template file for the issue title (minijinja syntax)
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        title_template: Option<PathBuf>,
+        /// Template file for the issue body (minijinja syntax)
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        body_template: Option<PathBuf>,
+    },
+
+    /// Create or update all labels of the label policy in the repository
+    SyncLabels {
+        /// The GitHub repository
+        #[arg(long, value_hint = ValueHint::Url)]
+        repo: String,
+        /// The GitHub issue label that created issues are labeled with
+        #[arg(short, long)]
+        label: String,
+    },
+
+    /// Receive GitHub `workflow_run` webhooks and create issues for failed runs
+    ///
+    /// The webhook secret is read from the `GITHUB_WEBHOOK_SECRET` environment variable, the
+    /// repositories, workflows and branches to handle from the `[serve]` section of `--config`.
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        bind: String,
+        /// The GitHub issue label
+        #[arg(short, long)]
+        label: String,
+        /// The kind of workflow (e.g. Yocto)
+        #[arg(short, long)]
+        kind: WorkflowKind,
+        /// Create one issue for the whole run or one issue per failed job
+        #[arg(long, default_value_t = IssuePer::default())]
+        issue_per: IssuePer,
+        /// Template file for the issue title (minijinja syntax)
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        title_template: Option<PathBuf>,
+        /// Template file for the issue body (minijinja syntax)
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        body_template: Option<PathBuf>,
+    },
+
+    /// Poll repositories for newly failed runs and create issues for them
+    ///
+    /// The repositories, workflows and branches to handle are also read from the `[watch]` section
+    /// of `--config`. Runs that completed before a repository was first watched are skipped.
+    Watch {
+        /// The GitHub repositories to watch, in addition to those of the config file
+        #[arg(long, value_hint = ValueHint::Url)]
+        repo: Vec<String>,
+        /// The GitHub issue label
+        #[arg(short, long)]
+        label: String,
+        /// The kind of workflow (e.g. Yocto)
+        #[arg(short, long)]
+        kind: WorkflowKind,
+        /// Create one issue for the whole run or one issue per failed job
+        #[arg(long, default_value_t = IssuePer::default())]
+        issue_per: IssuePer,
+        /// Seconds between polls, doubled after every consecutive error up to an hour
+        #[arg(long, default_value_t = 300, value_parser = clap::value_parser!(u64).range(1..))]
+        interval: u64,
+        /// File with the newest seen run and the pending runs of each repository
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        state_file: PathBuf,
+        /// Poll once and exit
+        #[arg(long)]
+        once: bool,
+        /// Template file for the issue title (minijinja syntax)
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        title_template: Option<PathBuf>,
+        /// Template file for the issue body (minijinja syntax)
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        body_template: Option<PathBuf>,
+    },
+
+    /// Report failure statistics of the runs over a period compared to the previous period
+    Report {
+        /// The GitHub repository
+        #[arg(long, value_hint = ValueHint::Url)]
+        repo: String,
+        /// The kind of workflow (e.g. Yocto)
+        #[arg(short, long)]
+        kind: WorkflowKind,
+        /// Only runs of this workflow (name or file name e.g. `build.yml`)
+        #[arg(long)]
+        workflow: Option<String>,
+        /// Only runs on this branch
+        #[arg(long)]
+        branch: Option<String>,
+        /// Last day of the period (YYYY-MM-DD), defaults to today
+        #[arg(long)]
+        until: Option<String>,
+        /// Length of the period in days
+        #[arg(long, default_value_t = 7, value_parser = clap::value_parser!(u64).range(1..))]
+        days: u64,
+        /// Maximum number of runs per period
+        #[arg(long, default_value_t = 200)]
+        limit: usize,
+        /// How many of the most common failures, failed steps, failure kinds and recipes to list
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+        /// The output format
+        #[arg(long, default_value_t = ReportFormat::default())]
+        format: ReportFormat,
+    },
+
+    /// Query the failure history database given with `--history-db`
+    History {
+        #[command(subcommand)]
+        command: HistoryCommand,
     },
 
     /// Locate the specific failure log in a failed build/test/other
@@ -47,6 +318,111 @@ pub enum Command {
     },
 }
 
+/// Subcommands of `history`
+#[derive(Debug, Subcommand)]
+pub enum HistoryCommand {
+    /// Record the recent runs of a repository (including green runs, needed for `mttr`)
+    Sync {
+        /// The GitHub repository
+        #[arg(long, value_hint = ValueHint::Url)]
+        repo: String,
+        /// Only runs of this workflow (name or file name e.g. `build.yml`)
+        #[arg(long)]
+        workflow: Option<String>,
+        /// Only runs on this branch
+        #[arg(long)]
+        branch: Option<String>,
+        /// Maximum number of runs to record
+        #[arg(long, default_value_t = 100)]
+        limit: usize,
+    },
+    /// The jobs that failed most often
+    TopJobs {
+        #[command(flatten)]
+        query: HistoryQueryArgs,
+    },
+    /// The failures (by fingerprint) that occurred most often
+    TopFingerprints {
+        #[command(flatten)]
+        query: HistoryQueryArgs,
+    },
+    /// The failures of a job, oldest first
+    Timeline {
+        /// The name of the job
+        #[arg(long)]
+        job: String,
+        #[command(flatten)]
+        query: HistoryQueryArgs,
+    },
+    /// Mean time to recovery per job, from the first failure to the next green run of the workflow
+    Mttr {
+        /// Only this job
+        #[arg(long)]
+        job: Option<String>,
+        #[command(flatten)]
+        query: HistoryQueryArgs,
+    },
+}
+
+/// Options shared by the `history` queries
+#[derive(Debug, Args)]
+pub struct HistoryQueryArgs {
+    /// Only failures in this GitHub repository
+    #[arg(long, value_hint = ValueHint::Url)]
+    pub repo: Option<String>,
+    /// Only failures on or after this date (YYYY-MM-DD)
+    #[arg(long)]
+    pub since: Option<String>,
+    /// Maximum number of results
+    #[arg(long, default_value_t = 10)]
+    pub limit: usize,
+}
+
+/// Parse a rate between 0 and 1
+fn parse_rate(s: &str) -> Result<f64, String> {
+    let rate: f64 = s.parse().map_err(|e| format!("{e}"))?;
+    if (0.0..=1.0).contains(&rate) {
+        Ok(rate)
+    } else {
+        Err(format!("{rate} is not between 0 and 1"))
+    }
+}
+
+/// How failed jobs are grouped into issues
+#[derive(ValueEnum, Display, Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[strum(serialize_all = "lowercase")]
+pub enum IssuePer {
+    /// One issue for all failed jobs of a run
+    #[default]
+    Run,
+    /// One issue per failed job, each with its own duplicate check
+    Job,
+}
+
+/// The output format of `analyze-log`
+#[derive(ValueEnum, Display, Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[strum(serialize_all = "lowercase")]
+pub enum AnalysisFormat {
+    /// The issue title and body
+    #[default]
+    Markdown,
+    /// The parsed model like `analyze-run`
+    Json,
+}
+
+/// What to do when a failure matches a recently closed issue
+#[derive(ValueEnum, Display, Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[strum(serialize_all = "lowercase")]
+pub enum RegressionAction {
+    /// Reopen the closed issue with a comment about the regression
+    #[default]
+    Reopen,
+    /// Create a new issue without looking at closed issues
+    New,
+    /// Create a new issue that links to the closed issue
+    Link,
+}
+
 /// The kind of workflow (e.g. Yocto)
 #[derive(ValueEnum, Display, Copy, Clone, Debug, PartialEq, Eq)]
 pub enum WorkflowKind {
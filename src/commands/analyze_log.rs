@@ -0,0 +1,107 @@
+use super::{
+    analyze_run::RunAnalysis, create_issue_from_run::parse_to_gh_issue, AnalysisFormat,
+    WorkflowKind,
+};
+use crate::{
+    errlog::ErrorLog,
+    issue::{template::IssueTemplates, Issue},
+    util::RunHeader,
+};
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufReader, Write},
+    path::PathBuf,
+};
+
+/// The run that the analyzed logs are from, the links in the issue are built from it
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LocalRun {
+    /// The repository URL, empty if unknown
+    pub repo: String,
+    pub run_id: String,
+    pub header: RunHeader,
+}
+
+/// Parse local log files into an issue, each log file is a failed job
+///
+/// The job ID is the file name without extension (like the job logs of a fixture directory), and
+/// it's also the name of the job if the log doesn't have the GitHub prefix.
+pub fn issue_from_logs(
+    logs: &[PathBuf],
+    run: LocalRun,
+    label: &str,
+    kind: WorkflowKind,
+) -> Result<Issue, Box<dyn Error>> {
+    let errlogs = logs
+        .iter()
+        .map(|path| {
+            let raw_log = File::open(path)
+                .map(BufReader::new)
+                .map_err(|e| format!("Failed to read log file {path:?}: {e}"))?;
+            let job_id = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            ErrorLog::from_local_log(job_id.clone(), &job_id, "unknown", raw_log)
+                .map_err(|e| format!("Invalid log file {path:?}: {e}").into())
+        })
+        .collect::<Result<Vec<ErrorLog>, Box<dyn Error>>>()?;
+    parse_to_gh_issue(
+        errlogs,
+        &run.repo,
+        run.run_id,
+        run.header,
+        label.to_string(),
+        kind,
+    )
+}
+
+/// Analyze local log files and print the rendered issue or the parsed model as JSON to stdout
+///
+/// GitHub is not accessed, so there's no duplicate check.
+pub fn analyze_log(
+    logs: &[PathBuf],
+    run: LocalRun,
+    label: &str,
+    kind: WorkflowKind,
+    templates: IssueTemplates,
+    format: AnalysisFormat,
+) -> Result<(), Box<dyn Error>> {
+    let issue = issue_from_logs(logs, run, label, kind)?.with_templates(templates)?;
+    match format {
+        AnalysisFormat::Markdown => {
+            crate::macros::pipe_println!("# {}\n\n{}", issue.title(), issue.body()?)?
+        },
+        AnalysisFormat::Json => crate::macros::pipe_println!(
+            "{}",
+            serde_json::to_string_pretty(&RunAnalysis::new(&issue, None)?)?
+        )?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_issue_from_logs() {
+        let logs = [PathBuf::from(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/github.com/luftkode/distro-template/jobs/21442749267.log"
+        ))];
+        let run = LocalRun {
+            repo: "https://github.com/luftkode/distro-template".to_string(),
+            run_id: "7858139663".to_string(),
+            header: RunHeader::default(),
+        };
+        let issue = issue_from_logs(&logs, run, "bug", WorkflowKind::Yocto).unwrap();
+
+        let job = &issue.failed_jobs()[0];
+        assert_eq!(job.name(), "Test template xilinx");
+        assert_eq!(job.failed_step(), "📦 Build yocto image");
+        assert_eq!(job.id(), "21442749267");
+    }
+}
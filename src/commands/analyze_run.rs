@@ -0,0 +1,99 @@
+use super::{
+    create_issue_from_run::{find_duplicate, issue_from_run, DuplicateMatch},
+    WorkflowKind,
+};
+use crate::{
+    gh,
+    issue::{template::IssueTemplates, Issue},
+};
+use serde::Serialize;
+use std::{error::Error, io::Write};
+
+/// The parsed model of a failed run as emitted by `analyze-run`
+#[derive(Debug, Serialize)]
+pub struct RunAnalysis<'a> {
+    #[serde(flatten)]
+    pub issue: &'a Issue,
+    /// The rendered issue body
+    pub body: String,
+    /// `None` if the duplicate check was skipped
+    pub duplicate: Option<DuplicateMatch>,
+}
+
+impl<'a> RunAnalysis<'a> {
+    /// Render the issue body for the analysis
+    ///
+    /// # Errors
+    /// Returns an error if the body template fails to render
+    pub fn new(
+        issue: &'a Issue,
+        duplicate: Option<DuplicateMatch>,
+    ) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            issue,
+            body: issue.body()?,
+            duplicate,
+        })
+    }
+}
+
+/// Analyze a failed run and print the parsed model as JSON to stdout
+///
+/// Nothing is created or modified in the repository.
+pub fn analyze_run(
+    github_cli: Box<dyn gh::GitHub>,
+    run_id: &str,
+    label: &str,
+    kind: WorkflowKind,
+    log_parallelism: usize,
+    templates: IssueTemplates,
+    skip_duplicate_check: bool,
+) -> Result<(), Box<dyn Error>> {
+    let issue = issue_from_run(github_cli.as_ref(), run_id, label, kind, log_parallelism)?
+        .with_templates(templates)?;
+    let duplicate = if skip_duplicate_check {
+        None
+    } else {
+        Some(find_duplicate(github_cli.as_ref(), &issue, label)?)
+    };
+    let analysis = RunAnalysis::new(&issue, duplicate)?;
+    crate::macros::pipe_println!("{}", serde_json::to_string_pretty(&analysis)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gh::gh_cli_fake::GitHubCliFake;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_run_analysis_json() {
+        let github_cli = GitHubCliFake::new("https://github.com/luftkode/distro-template".into());
+        let issue =
+            issue_from_run(&github_cli, "7858139663", "bug", WorkflowKind::Yocto, 1).unwrap();
+        let duplicate = find_duplicate(&github_cli, &issue, "bug").unwrap();
+        let json =
+            serde_json::to_value(RunAnalysis::new(&issue, Some(duplicate)).unwrap()).unwrap();
+
+        assert_eq!(json["run_id"], "7858139663");
+        assert_eq!(json["branch"], "master");
+        assert_eq!(json["workflow"], "Use template and build image");
+        assert_eq!(json["labels"], serde_json::json!(["bug", "misc"]));
+        assert_eq!(json["fingerprint"], issue.fingerprint());
+        assert_eq!(json["duplicate"]["status"], "new");
+        let job = &json["failed_jobs"][0];
+        assert_eq!(job["name"], "Test template xilinx");
+        assert_eq!(job["failed_step"], "📦 Build yocto image");
+        assert_eq!(job["error_message"]["parser"], "yocto");
+        assert_eq!(job["error_message"]["error"]["kind"], "misc");
+        assert_eq!(
+            job["error_message"]["error"]["logfile"],
+            serde_json::Value::Null
+        );
+        assert!(json["body"]
+            .as_str()
+            .unwrap()
+            .starts_with("**Run ID**: 7858139663"));
+    }
+}
@@ -1,130 +1,762 @@
-use super::{WorkflowKind, LEVENSHTEIN_THRESHOLD};
+use super::{IssuePer, RegressionAction, WorkflowKind, LEVENSHTEIN_THRESHOLD};
 use crate::{
     err_msg_parse,
     errlog::ErrorLog,
-    gh,
-    issue::{FailedJob, Issue},
+    flaky::{FlakinessDetector, RunAttempt},
+    gh::{self, GhIssue},
+    history::{FailureRecord, HistoryDb},
+    issue::{metadata, template::IssueTemplates, truncate_title, FailedJob, Issue, IssueOutcome},
+    labels::LabelPolicy,
+    notify::{self, Notifier},
+    owners::OwnershipResolver,
+    project::ProjectOptions,
+    settings::OwnersSettings,
     util,
 };
-use std::error::Error;
+use serde::Serialize;
+use std::{
+    error::Error,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+/// Default number of job logs that are fetched at the same time
+pub const DEFAULT_LOG_PARALLELISM: usize = 4;
+/// Default number of days to look back for closed issues matching a failure
+pub const DEFAULT_REGRESSION_WINDOW_DAYS: u64 = 30;
+
+/// Options for creating an issue from a run
+#[derive(Debug, Clone)]
+pub struct CreateIssueOptions {
+    /// The label of created issues, also used to find duplicates
+    pub label: String,
+    pub kind: WorkflowKind,
+    pub templates: IssueTemplates,
+    pub dry_run: bool,
+    /// Don't create the issue if a similar issue already exists
+    pub no_duplicate: bool,
+    /// Write a job summary, annotations and step outputs for GitHub Actions
+    pub github_actions_output: bool,
+    /// Webhooks to notify about new failures
+    pub notifiers: Vec<Notifier>,
+    /// Only notify the webhooks, don't create an issue
+    pub skip_issue_creation: bool,
+    /// What to do if the failure matches a recently closed issue
+    pub on_regression: RegressionAction,
+    /// How many days back to look for closed issues matching the failure
+    pub regression_window_days: u64,
+    /// Create one issue for the whole run or one issue per failed job
+    pub issue_per: IssuePer,
+    /// How many job logs are fetched at the same time
+    pub log_parallelism: usize,
+    /// Create a tracking issue listing the per-job issues
+    pub tracking_issue: bool,
+    /// Resolves the owners that issues are assigned to
+    pub owners: OwnershipResolver,
+    /// Names, colors and descriptions of the labels of created issues
+    pub labels: LabelPolicy,
+    /// The milestone and project board created issues are added to
+    pub project: ProjectOptions,
+    /// Detect flaky jobs and suppress issues for them
+    pub flaky: Option<FlakinessDetector>,
+    /// Record the failures in this history database
+    pub history_db: Option<PathBuf>,
+}
+
+impl CreateIssueOptions {
+    /// Options for creating issues with the label for a workflow of the kind, everything else is
+    /// the default of the `create-issue-from-run` subcommand
+    pub fn new(label: String, kind: WorkflowKind) -> Self {
+        Self {
+            label,
+            kind,
+            templates: IssueTemplates::default(),
+            dry_run: false,
+            no_duplicate: false,
+            github_actions_output: false,
+            notifiers: vec![],
+            skip_issue_creation: false,
+            on_regression: RegressionAction::default(),
+            regression_window_days: DEFAULT_REGRESSION_WINDOW_DAYS,
+            issue_per: IssuePer::default(),
+            log_parallelism: DEFAULT_LOG_PARALLELISM,
+            tracking_issue: false,
+            owners: OwnershipResolver::default(),
+            labels: LabelPolicy::default(),
+            project: ProjectOptions::default(),
+            flaky: None,
+            history_db: None,
+        }
+    }
+}
+
+/// Options for creating issues for the runs of repositories that are only known at runtime
+#[derive(Debug, Clone)]
+pub struct RepoIssueOptions {
+    pub github_cli: gh::GitHubCliSettings,
+    /// Owners are resolved per repository
+    pub owners: OwnersSettings,
+    pub create_issue: CreateIssueOptions,
+}
+
+/// Create an issue for a failed run of a repository, resolving the owners for the repository
+pub fn create_issue_from_repo_run(
+    repo: &str,
+    run_id: u64,
+    options: &RepoIssueOptions,
+) -> Result<(), Box<dyn Error>> {
+    log::info!("Creating an issue for run {run_id} of {repo}");
+    let github_cli = gh::init_github_cli_with(repo.to_string(), &options.github_cli)?;
+    let create_issue = CreateIssueOptions {
+        owners: OwnershipResolver::from_settings(options.owners.clone(), github_cli.as_ref())?,
+        ..options.create_issue.clone()
+    };
+    create_issue_from_run(github_cli, &run_id.to_string(), &create_issue)
+}
 
 pub fn create_issue_from_run(
     github_cli: Box<dyn gh::GitHub>,
     run_id: &str,
-    labels: &str,
-    kind: WorkflowKind,
-    dry_run: bool,
-    no_duplicate: bool,
+    options: &CreateIssueOptions,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    // Run the GitHub CLI to get the workflow run
-    let run_summary = github_cli.run_summary(None, run_id)?;
-    log::info!("Run summary: {run_summary}");
+    let gh_issue = issue_from_run(
+        github_cli.as_ref(),
+        run_id,
+        &options.label,
+        options.kind,
+        options.log_parallelism,
+    )?
+    .with_templates(options.templates.clone())?;
+    let recent_attempts = match &options.flaky {
+        Some(detector) => Some(detector.recent_attempts(github_cli.as_ref(), &gh_issue)?),
+        None => None,
+    };
+    match options.issue_per {
+        IssuePer::Run => {
+            if options.tracking_issue {
+                log::warn!("A tracking issue is only created with `--issue-per job`");
+            }
+            let (gh_issue, outcome) = handle_issue(
+                github_cli.as_ref(),
+                gh_issue,
+                recent_attempts.as_deref(),
+                options,
+            )?;
+            record_history(github_cli.as_ref(), &gh_issue, &outcome, options)?;
+            if options.github_actions_output {
+                gh::actions::report_outputs(&[(gh_issue, outcome)])?;
+            }
+        },
+        IssuePer::Job => {
+            let run_title = gh_issue.title().to_string();
+            let run_id = gh_issue.run_id().to_string();
+            let run_link = gh_issue.run_link().to_string();
+            let job_issues = gh_issue
+                .into_per_job_issues()?
+                .into_iter()
+                .map(|job_issue| {
+                    handle_issue(
+                        github_cli.as_ref(),
+                        job_issue,
+                        recent_attempts.as_deref(),
+                        options,
+                    )
+                })
+                .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+            for (job_issue, outcome) in &job_issues {
+                record_history(github_cli.as_ref(), job_issue, outcome, options)?;
+            }
+            if options.github_actions_output {
+                gh::actions::report_outputs(&job_issues)?;
+            }
+            if options.tracking_issue {
+                let body = tracking_issue_body(&run_id, &run_link, &job_issues);
+                create_tracking_issue(
+                    github_cli.as_ref(),
+                    &run_id,
+                    &run_title,
+                    &body,
+                    &job_issues,
+                    options,
+                )?;
+            }
+        },
+    }
+    Ok(())
+}
 
-    let failed_jobs = util::take_lines_with_failed_jobs(run_summary);
-    if failed_jobs.is_empty() {
-        log::error!("No failed jobs found! Exiting...");
-        std::process::exit(1);
+/// Create the issue unless it's a duplicate, then report and notify about the outcome
+///
+/// Flaky jobs are detected in `recent_attempts` if the attempts of the recent runs are already
+/// known, see [FlakinessDetector::recent_attempts].
+pub(crate) fn handle_issue(
+    github_cli: &dyn gh::GitHub,
+    gh_issue: Issue,
+    recent_attempts: Option<&[RunAttempt]>,
+    options: &CreateIssueOptions,
+) -> Result<(Issue, IssueOutcome), Box<dyn Error>> {
+    let mut gh_issue = options.owners.assign(options.labels.apply(gh_issue));
+    if let Some(detector) = &options.flaky {
+        gh_issue = match recent_attempts {
+            Some(attempts) => FlakinessDetector::mark(attempts, gh_issue),
+            None => {
+                let attempts = detector.recent_attempts(github_cli, &gh_issue)?;
+                FlakinessDetector::mark(&attempts, gh_issue)
+            },
+        };
+    }
+    // Render the title again, so it sees the same labels as the body
+    gh_issue = gh_issue.with_templates(options.templates.clone())?;
+    let outcome = if options
+        .flaky
+        .as_ref()
+        .is_some_and(|detector| detector.suppresses(&gh_issue))
+    {
+        log::info!(
+            "Not creating an issue for the flaky failure, the flake rate is below the threshold"
+        );
+        IssueOutcome::Suppressed
+    } else {
+        create_issue_unless_duplicate(github_cli, &mut gh_issue, options)?
+    };
+    if options.github_actions_output {
+        gh::actions::report(&gh_issue, &outcome)?;
     }
+    notify::notify_all(&options.notifiers, &gh_issue, &outcome, options.dry_run);
+    Ok((gh_issue, outcome))
+}
 
-    log::info!("Failed jobs: {:?}", failed_jobs);
-    let failed_job_ids = util::id_from_job_lines(&failed_jobs);
-    let failed_job_logs: Vec<String> = failed_job_ids
-        .iter()
-        .map(|job_id| github_cli.failed_job_log(None, job_id))
-        .collect::<Result<Vec<String>, Box<dyn Error>>>()?;
+/// Record the failed jobs of the issue in the history database, if one is set
+///
+/// The run of the issue is recorded as failing now.
+fn record_history(
+    github_cli: &dyn gh::GitHub,
+    gh_issue: &Issue,
+    outcome: &IssueOutcome,
+    options: &CreateIssueOptions,
+) -> Result<(), Box<dyn Error>> {
+    let Some(path) = &options.history_db else {
+        return Ok(());
+    };
+    if options.dry_run {
+        log::info!(
+            "DRY RUN MODE! Not recording the failure in {}",
+            path.display()
+        );
+        return Ok(());
+    }
+    let records = FailureRecord::from_issue(
+        github_cli.default_repo(),
+        gh_issue,
+        &util::iso_timestamp_now(),
+        outcome.issue_url(),
+    );
+    HistoryDb::open(path)?.record_failures(&records)?;
+    log::debug!(
+        "Recorded {} failed job(s) in {}",
+        records.len(),
+        path.display()
+    );
+    Ok(())
+}
 
-    log::info!("Got {} failed job log(s)", failed_job_logs.len());
+/// Body of a tracking issue with a task list item for each per-job issue
+fn tracking_issue_body(
+    run_id: &str,
+    run_link: &str,
+    job_issues: &[(Issue, IssueOutcome)],
+) -> String {
+    let mut body = format!(
+        "**Run ID**: {run_id} [LINK TO RUN]({run_link})\n\n**{} failed {}:**\n",
+        job_issues.len(),
+        if job_issues.len() == 1 { "job" } else { "jobs" }
+    );
+    for (job_issue, outcome) in job_issues {
+        let item = match outcome {
+            IssueOutcome::Created(url) => url.to_string(),
+            IssueOutcome::DuplicateOf(url) => format!("{url} (known failure)"),
+            IssueOutcome::Reopened(url) => format!("{url} (regression)"),
+            IssueOutcome::NotCreated => format!("`{}`", job_issue.title()),
+            IssueOutcome::Suppressed => format!("`{}` (flaky)", job_issue.title()),
+        };
+        body.push_str(&format!("- [ ] {item}\n"));
+    }
+    body
+}
 
-    let failed_logs = failed_job_logs
-        .iter()
-        .zip(failed_job_ids.iter())
-        .map(|(log, id)| ErrorLog::new(id.to_string(), log.to_string()))
-        .collect::<Result<Vec<ErrorLog>, Box<dyn Error>>>()?;
+/// Title prefix of tracking issues
+const TRACKING_ISSUE_PREFIX: &str = "Tracking: ";
 
-    let gh_issue = parse_to_gh_issue(
-        failed_logs,
-        github_cli.default_repo(),
-        run_id.to_owned(),
-        labels.to_string(),
-        kind,
+/// Create a tracking issue for the run, unless no per-job issue was created or reopened or the run
+/// already has a tracking issue
+fn create_tracking_issue(
+    github_cli: &dyn gh::GitHub,
+    run_id: &str,
+    run_title: &str,
+    body: &str,
+    job_issues: &[(Issue, IssueOutcome)],
+    options: &CreateIssueOptions,
+) -> Result<(), Box<dyn Error>> {
+    let title = truncate_title(&format!("{TRACKING_ISSUE_PREFIX}{run_title}"));
+    if options.dry_run {
+        println!("####################################");
+        println!("DRY RUN MODE! The following tracking issue would be created:");
+        println!("==== ISSUE TITLE ==== \n{title}");
+        println!("==== START OF ISSUE BODY ==== \n{body}");
+        println!("==== END OF ISSUE BODY ====");
+        return Ok(());
+    }
+    if !job_issues.iter().any(|(_, outcome)| {
+        matches!(
+            outcome,
+            IssueOutcome::Created(_) | IssueOutcome::Reopened(_)
+        )
+    }) {
+        log::info!(
+            "No issue was created or reopened for the failed jobs, not creating a tracking issue"
+        );
+        return Ok(());
+    }
+    let run_id_line = format!("**Run ID**: {run_id} ");
+    if let Some(existing) = github_cli
+        .issues_open_with_label(None, &options.label)?
+        .into_iter()
+        .find(|issue| {
+            issue.title.starts_with(TRACKING_ISSUE_PREFIX) && issue.body.starts_with(&run_id_line)
+        })
+    {
+        log::info!(
+            "Run {run_id} already has a tracking issue: {}",
+            existing.url
+        );
+        return Ok(());
+    }
+    let labels = options.labels.ensure_labels(
+        github_cli,
+        &options.label,
+        std::slice::from_ref(&options.label),
     )?;
-    if no_duplicate {
-        let similar_issues = github_cli.issue_bodies_open_with_label(None, labels)?;
-        // Check how similar the issues are
-        let smallest_distance = issue_text_similarity(&gh_issue.body(), &similar_issues);
-        log::info!("Smallest levenshtein distance to similar issue: {smallest_distance} (Similarity threshold={LEVENSHTEIN_THRESHOLD})");
-        match smallest_distance {
-            0 => {
-                log::warn!("An issue with the exact same body already exists. Exiting...");
-                std::process::exit(0);
+    let url = github_cli.create_issue(None, &title, body, &labels, &[])?;
+    log::info!("Created tracking issue: {url}");
+    Ok(())
+}
+
+/// Create the issue in the repository unless it's a duplicate (and duplicates are not allowed)
+///
+/// If the failure matches a recently closed issue, the closed issue is reopened or linked from the
+/// new issue depending on [CreateIssueOptions::on_regression].
+fn create_issue_unless_duplicate(
+    github_cli: &dyn gh::GitHub,
+    gh_issue: &mut Issue,
+    options: &CreateIssueOptions,
+) -> Result<IssueOutcome, Box<dyn Error>> {
+    if options.no_duplicate {
+        match find_duplicate(github_cli, gh_issue, &options.label)? {
+            DuplicateMatch::Exact { url } => {
+                log::warn!("An issue for the exact same failure already exists: {url}");
+                return Ok(IssueOutcome::DuplicateOf(url));
             },
-            _ if smallest_distance < LEVENSHTEIN_THRESHOLD => {
-                log::warn!("An issue with a similar body already exists. Exiting...");
-                std::process::exit(0);
+            DuplicateMatch::Similar { url, .. } => {
+                log::warn!("An issue with a similar body already exists: {url}");
+                return Ok(IssueOutcome::DuplicateOf(url));
             },
-            _ => log::info!("No similar issue found. Continuing..."),
+            DuplicateMatch::New { .. } => log::info!("No similar issue found. Continuing..."),
+        }
+    }
+    if options.skip_issue_creation {
+        log::info!("Skipping issue creation");
+        return Ok(IssueOutcome::NotCreated);
+    }
+    if options.on_regression != RegressionAction::New {
+        if let Some(closed_issue) = find_regression(
+            github_cli,
+            gh_issue,
+            &options.label,
+            options.regression_window_days,
+        )? {
+            log::warn!(
+                "The failure is a regression of the closed issue {}",
+                closed_issue.url
+            );
+            match options.on_regression {
+                RegressionAction::Reopen => {
+                    return reopen_regressed_issue(github_cli, gh_issue, &closed_issue, options)
+                },
+                RegressionAction::Link => gh_issue.set_regression_of(closed_issue.url),
+                RegressionAction::New => unreachable!("Regressions are not searched for"),
+            }
         }
     }
-    if dry_run {
+    if options.dry_run {
         println!("####################################");
         println!("DRY RUN MODE! The following issue would be created:");
         println!("==== ISSUE TITLE ==== \n{}", gh_issue.title());
         println!("==== ISSUE LABEL(S) ==== \n{}", gh_issue.labels().join(","));
-        println!("==== START OF ISSUE BODY ==== \n{}", gh_issue.body());
+        println!(
+            "==== ISSUE ASSIGNEE(S) ==== \n{}",
+            gh_issue.assignees().join(",")
+        );
+        if let Some(milestone) = &options.project.milestone {
+            println!("==== ISSUE MILESTONE ==== \n{milestone}");
+        }
+        if let Some(project) = &options.project.project {
+            println!("==== ISSUE PROJECT ==== \n{project}");
+            for (field, value) in options.project.render_fields(gh_issue)? {
+                println!("{field}={value}");
+            }
+        }
+        println!("==== START OF ISSUE BODY ==== \n{}", gh_issue.body()?);
         println!("==== END OF ISSUE BODY ====");
+        Ok(IssueOutcome::NotCreated)
     } else {
-        log::debug!("Creating an issue in the remote repository with the following characteristics:\n==== ISSUE TITLE ==== \n{title}\n==== ISSUE LABEL(S) ==== \n{labels}\n==== START OF ISSUE BODY ==== \n{body}\n==== END OF ISSUE BODY ====", title = gh_issue.title(), labels = gh_issue.labels().join(","), body = gh_issue.body());
-        github_cli.create_issue(None, gh_issue.title(), &gh_issue.body(), gh_issue.labels())?;
+        let body = gh_issue.body()?;
+        log::debug!("Creating an issue in the remote repository with the following characteristics:\n==== ISSUE TITLE ==== \n{title}\n==== ISSUE LABEL(S) ==== \n{labels}\n==== START OF ISSUE BODY ==== \n{body}\n==== END OF ISSUE BODY ====", title = gh_issue.title(), labels = gh_issue.labels().join(","));
+        let labels = options
+            .labels
+            .ensure_labels(github_cli, &options.label, gh_issue.labels())?;
+        let url = github_cli.create_issue(
+            None,
+            gh_issue.title(),
+            &body,
+            &labels,
+            &gh_issue.assignees(),
+        )?;
+        log::info!("Created issue: {url}");
+        options.project.apply(github_cli, gh_issue, &url)?;
+        Ok(IssueOutcome::Created(url))
     }
-    Ok(())
 }
 
-/// Calculate the smallest levenshtein distance between the issue body and the other issues with the same label
-fn issue_text_similarity(issue_body: &str, other_issues: &[String]) -> usize {
-    let issue_body_without_timestamps = util::remove_timestamps(issue_body);
+/// Reopen the closed issue with a comment linking to the run it regressed in
+fn reopen_regressed_issue(
+    github_cli: &dyn gh::GitHub,
+    gh_issue: &Issue,
+    closed_issue: &GhIssue,
+    options: &CreateIssueOptions,
+) -> Result<IssueOutcome, Box<dyn Error>> {
+    let comment = format!(
+        "Regressed in run [{run_id}]({run_link})",
+        run_id = gh_issue.run_id(),
+        run_link = gh_issue.run_link()
+    );
+    if options.dry_run {
+        println!("####################################");
+        println!(
+            "DRY RUN MODE! The following issue would be reopened: {}",
+            closed_issue.url
+        );
+        println!("==== COMMENT ==== \n{comment}");
+        return Ok(IssueOutcome::NotCreated);
+    }
+    github_cli.reopen_issue(None, closed_issue.number)?;
+    github_cli.add_issue_comment(None, closed_issue.number, &comment)?;
+    log::info!("Reopened issue: {}", closed_issue.url);
+    Ok(IssueOutcome::Reopened(closed_issue.url.clone()))
+}
+
+/// Fetch the summary and failed job logs of a run and parse them into an [Issue]
+///
+/// Up to `log_parallelism` logs are fetched at the same time. Jobs whose log can't be fetched or
+/// parsed are left out of the issue.
+///
+/// # Errors
+/// Returns an error if the run has no failed jobs or if fetching/parsing the logs of all of them
+/// fails
+pub fn issue_from_run(
+    github_cli: &dyn gh::GitHub,
+    run_id: &str,
+    label: &str,
+    kind: WorkflowKind,
+    log_parallelism: usize,
+) -> Result<Issue, Box<dyn Error>> {
+    // Run the GitHub CLI to get the workflow run
+    let run_summary = github_cli.run_summary(None, run_id)?;
+    log::info!("Run summary: {run_summary}");
+    let run_header = util::run_header_from_summary(&run_summary).unwrap_or_else(|| {
+        log::warn!("Could not parse branch and workflow name from the run summary");
+        util::RunHeader::default()
+    });
+
+    let failed_jobs = util::take_lines_with_failed_jobs(run_summary);
+    if failed_jobs.is_empty() {
+        return Err(format!("No failed jobs found in run {run_id}").into());
+    }
+
+    log::info!("Failed jobs: {:?}", failed_jobs);
+    let failed_job_ids = util::id_from_job_lines(&failed_jobs);
+    let results = fetch_failed_jobs(github_cli, run_id, &failed_job_ids, kind, log_parallelism);
+    let mut failed_jobs = Vec::with_capacity(results.len());
+    let mut errors = vec![];
+    for (job_id, result) in failed_job_ids.iter().zip(results) {
+        match result {
+            Ok(failed_job) => failed_jobs.push(failed_job),
+            Err(e) => {
+                log::error!("Failed to analyze the log of job {job_id}: {e}");
+                errors.push(format!("job {job_id}: {e}"));
+            },
+        }
+    }
+    if failed_jobs.is_empty() {
+        return Err(format!(
+            "Failed to analyze the failed jobs of run {run_id}: {}",
+            errors.join(", ")
+        )
+        .into());
+    }
+    if !errors.is_empty() {
+        log::warn!(
+            "Continuing without {} of {} failed job(s)",
+            errors.len(),
+            failed_job_ids.len()
+        );
+    }
+    log::info!("Got {} failed job log(s)", failed_jobs.len());
+
+    Ok(issue_from_failed_jobs(
+        failed_jobs,
+        github_cli.default_repo(),
+        run_id.to_owned(),
+        run_header,
+        label.to_string(),
+    ))
+}
+
+/// Fetch and parse the logs of failed jobs of a run, up to `parallelism` at the same time
+///
+/// Each log is parsed while it's downloaded. The results are in the order of `job_ids`, and a job
+/// whose log can't be fetched or parsed doesn't affect the others.
+pub fn fetch_failed_jobs(
+    github_cli: &dyn gh::GitHub,
+    run_id: &str,
+    job_ids: &[String],
+    kind: WorkflowKind,
+    parallelism: usize,
+) -> Vec<Result<FailedJob, Box<dyn Error>>> {
+    let next = AtomicUsize::new(0);
+    let fetch_next = || {
+        let mut results = vec![];
+        loop {
+            let index = next.fetch_add(1, Ordering::Relaxed);
+            let Some(job_id) = job_ids.get(index) else {
+                break;
+            };
+            let result = github_cli
+                .failed_job_log(None, job_id)
+                .and_then(|log| ErrorLog::new(job_id.to_string(), log))
+                .and_then(|errlog| {
+                    failed_job_from_log(&errlog, github_cli.default_repo(), run_id, kind)
+                })
+                // Errors can't be sent between threads
+                .map_err(|e| e.to_string());
+            results.push((index, result));
+        }
+        results
+    };
+    let workers = parallelism.clamp(1, job_ids.len().max(1));
+    let mut results: Vec<_> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..workers).map(|_| scope.spawn(fetch_next)).collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("Fetching a job log panicked"))
+            .collect()
+    });
+    results.sort_by_key(|(index, _)| *index);
+    results
+        .into_iter()
+        .map(|(_, result)| result.map_err(Into::into))
+        .collect()
+}
+
+/// The result of comparing an issue to the open issues with the same label
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum DuplicateMatch {
+    /// An issue with the same failure fingerprint in its metadata, or with the exact same body
+    /// (ignoring timestamps and IDs) exists at `url`
+    Exact { url: String },
+    /// An issue with a body within [LEVENSHTEIN_THRESHOLD] exists at `url`
+    Similar { distance: usize, url: String },
+    /// No similar issue exists, `smallest_distance` is `None` if there are no issues to compare to
+    New { smallest_distance: Option<usize> },
+}
 
-    let smallest_distance = other_issues
+impl DuplicateMatch {
+    /// Classify the most similar existing issue and its levenshtein distance to the new issue
+    pub fn from_most_similar(most_similar: Option<(usize, &GhIssue)>) -> Self {
+        match most_similar {
+            Some((0, issue)) => DuplicateMatch::Exact {
+                url: issue.url.clone(),
+            },
+            Some((distance, issue)) if distance < LEVENSHTEIN_THRESHOLD => {
+                DuplicateMatch::Similar {
+                    distance,
+                    url: issue.url.clone(),
+                }
+            },
+            Some((distance, _)) => DuplicateMatch::New {
+                smallest_distance: Some(distance),
+            },
+            None => DuplicateMatch::New {
+                smallest_distance: None,
+            },
+        }
+    }
+
+    /// Returns true if the issue is an exact or similar duplicate of an existing issue
+    pub fn is_duplicate(&self) -> bool {
+        !matches!(self, DuplicateMatch::New { .. })
+    }
+
+    /// The URL of the existing issue if the issue is a duplicate
+    pub fn url(&self) -> Option<&str> {
+        match self {
+            DuplicateMatch::Exact { url } | DuplicateMatch::Similar { url, .. } => Some(url),
+            DuplicateMatch::New { .. } => None,
+        }
+    }
+}
+
+/// Compare the issue to the open issues with the given label
+///
+/// Issues created by this tool are matched by the fingerprint in their metadata, other issues are
+/// compared by the levenshtein distance of their bodies.
+pub fn find_duplicate(
+    github_cli: &dyn gh::GitHub,
+    issue: &Issue,
+    label: &str,
+) -> Result<DuplicateMatch, Box<dyn Error>> {
+    let similar_issues = github_cli.issues_open_with_label(None, label)?;
+    let (duplicate_match, _) = match_issue(issue, &similar_issues)?;
+    Ok(duplicate_match)
+}
+
+/// Find an issue with the given label that was closed within the last `window_days` days and
+/// matches the issue like a duplicate would
+pub fn find_regression(
+    github_cli: &dyn gh::GitHub,
+    issue: &Issue,
+    label: &str,
+    window_days: u64,
+) -> Result<Option<GhIssue>, Box<dyn Error>> {
+    let closed_since = util::iso_date_days_ago(window_days);
+    let closed_issues = github_cli.issues_closed_with_label(None, label, &closed_since)?;
+    log::debug!(
+        "Comparing to {} issue(s) closed since {closed_since}",
+        closed_issues.len()
+    );
+    match match_issue(issue, &closed_issues)? {
+        (duplicate_match, Some(closed_issue)) if duplicate_match.is_duplicate() => {
+            Ok(Some(closed_issue.clone()))
+        },
+        _ => Ok(None),
+    }
+}
+
+/// Compare the issue to other issues and return the result along with the most similar issue
+fn match_issue<'i>(
+    issue: &Issue,
+    other_issues: &'i [GhIssue],
+) -> Result<(DuplicateMatch, Option<&'i GhIssue>), Box<dyn Error>> {
+    if let Some(same_fingerprint) = other_issues.iter().find(|other| {
+        metadata::extract_metadata(&other.body)
+            .is_some_and(|metadata| metadata.fingerprint == issue.fingerprint())
+    }) {
+        log::info!(
+            "Issue {} has the same failure fingerprint {}",
+            same_fingerprint.url,
+            issue.fingerprint()
+        );
+        let duplicate_match = DuplicateMatch::Exact {
+            url: same_fingerprint.url.clone(),
+        };
+        return Ok((duplicate_match, Some(same_fingerprint)));
+    }
+    let most_similar = most_similar_issue(&issue.body()?, other_issues);
+    if let Some((smallest_distance, _)) = most_similar {
+        log::info!("Smallest levenshtein distance to similar issue: {smallest_distance} (Similarity threshold={LEVENSHTEIN_THRESHOLD})");
+    }
+    Ok((
+        DuplicateMatch::from_most_similar(most_similar),
+        most_similar.map(|(_, other)| other),
+    ))
+}
+
+/// Find the issue with the smallest levenshtein distance to the issue body and return the distance
+/// and the issue
+fn most_similar_issue<'i>(
+    issue_body: &str,
+    other_issues: &'i [GhIssue],
+) -> Option<(usize, &'i GhIssue)> {
+    let issue_body_without_timestamps =
+        util::remove_timestamps(metadata::strip_metadata(issue_body));
+
+    other_issues
         .iter()
-        .map(|other_issue_body| {
-            distance::levenshtein(
+        .map(|other_issue| {
+            let distance = distance::levenshtein(
                 &issue_body_without_timestamps,
-                &util::remove_timestamps(other_issue_body),
-            )
+                &util::remove_timestamps(metadata::strip_metadata(&other_issue.body)),
+            );
+            (distance, other_issue)
         })
-        .min()
-        .unwrap_or(usize::MAX);
+        .min_by_key(|(distance, _)| *distance)
+}
 
-    smallest_distance
+/// Parse the error of a failed job log
+fn failed_job_from_log(
+    errlog: &ErrorLog,
+    repo: &str,
+    run_id: &str,
+    kind: WorkflowKind,
+) -> Result<FailedJob, Box<dyn Error>> {
+    let err_summary = err_msg_parse::parse_error_log(errlog, kind)?;
+    Ok(FailedJob::new(
+        errlog.failed_job().to_owned(),
+        errlog.job_id().to_owned(),
+        gh::util::repo_url_to_job_url(repo, run_id, errlog.job_id()),
+        errlog.failed_step().to_owned(),
+        err_summary,
+    ))
 }
 
-fn parse_to_gh_issue(
+/// Parse the failed job logs of a run into an issue
+pub(crate) fn parse_to_gh_issue(
     errlogs: Vec<ErrorLog>,
     repo: &str,
     run_id: String,
+    run_header: util::RunHeader,
     label: String,
     kind: WorkflowKind,
 ) -> Result<Issue, Box<dyn Error>> {
     let failed_jobs: Vec<FailedJob> = errlogs
         .iter()
-        .map(|errlog| {
-            let err_summary = err_msg_parse::parse_error_message(errlog.no_prefix_log(), kind)?;
-            Ok(FailedJob::new(
-                errlog.failed_job().to_owned(),
-                errlog.job_id().to_owned(),
-                gh::util::repo_url_to_job_url(repo, &run_id, errlog.job_id()),
-                errlog.failed_step().to_owned(),
-                err_summary,
-            ))
-        })
+        .map(|errlog| failed_job_from_log(errlog, repo, &run_id, kind))
         .collect::<Result<Vec<FailedJob>, Box<dyn Error>>>()?;
+    Ok(issue_from_failed_jobs(
+        failed_jobs,
+        repo,
+        run_id,
+        run_header,
+        label,
+    ))
+}
 
-    let issue = Issue::new(
+fn issue_from_failed_jobs(
+    failed_jobs: Vec<FailedJob>,
+    repo: &str,
+    run_id: String,
+    run_header: util::RunHeader,
+    label: String,
+) -> Issue {
+    Issue::new(
         run_id.to_string(),
         gh::util::repo_url_to_run_url(repo, &run_id),
+        run_header.workflow,
+        run_header.branch,
         failed_jobs,
         label,
-    );
-    Ok(issue)
+    )
 }
 
 #[cfg(test)]
@@ -132,6 +764,18 @@ mod tests {
     use super::*;
     use pretty_assertions::assert_eq;
 
+    /// Calculate the smallest levenshtein distance between the issue body and the other issues
+    fn issue_text_similarity(issue_body: &str, other_issues: &[String]) -> usize {
+        let other_issues: Vec<GhIssue> = other_issues
+            .iter()
+            .map(|body| GhIssue {
+                body: body.to_string(),
+                ..Default::default()
+            })
+            .collect();
+        most_similar_issue(issue_body, &other_issues).map_or(usize::MAX, |(distance, _)| distance)
+    }
+
     const EXAMPLE_ISSUE_BODY_0: &str = r#"**Run ID**: 7858139663 [LINK TO RUN]( https://github.com/luftkode/distro-template/actions/runs/7850874958)
 
 **2 jobs failed:**
@@ -190,6 +834,50 @@ Yocto error: ERROR: No recipes available for: ...
         assert_eq!(distance, 0);
     }
 
+    #[test]
+    fn test_duplicate_match_from_most_similar() {
+        let issues = vec![
+            GhIssue {
+                number: 1,
+                url: "https://github.com/foo/bar/issues/1".to_string(),
+                body: EXAMPLE_ISSUE_BODY_0.to_string(),
+                ..Default::default()
+            },
+            GhIssue {
+                number: 2,
+                url: "https://github.com/foo/bar/issues/2".to_string(),
+                body: "Something else entirely".to_string(),
+                ..Default::default()
+            },
+        ];
+        assert_eq!(
+            DuplicateMatch::from_most_similar(most_similar_issue(EXAMPLE_ISSUE_BODY_1, &issues)),
+            DuplicateMatch::Exact {
+                url: "https://github.com/foo/bar/issues/1".to_string()
+            }
+        );
+        assert_eq!(
+            DuplicateMatch::from_most_similar(Some((LEVENSHTEIN_THRESHOLD - 1, &issues[1]))),
+            DuplicateMatch::Similar {
+                distance: LEVENSHTEIN_THRESHOLD - 1,
+                url: "https://github.com/foo/bar/issues/2".to_string()
+            }
+        );
+        assert_eq!(
+            DuplicateMatch::from_most_similar(Some((LEVENSHTEIN_THRESHOLD, &issues[1]))),
+            DuplicateMatch::New {
+                smallest_distance: Some(LEVENSHTEIN_THRESHOLD)
+            }
+        );
+        // No issues to compare to
+        assert_eq!(
+            DuplicateMatch::from_most_similar(most_similar_issue("foo", &[])),
+            DuplicateMatch::New {
+                smallest_distance: None
+            }
+        );
+    }
+
     /// Identical except for very similar run and job IDs
     #[test]
     fn test_issue_body_distance_edit_minimal_diff() {
@@ -245,6 +933,138 @@ Yocto error: ERROR: No recipes available for: ...
         assert_eq!(distance, 142);
     }
 
+    #[test]
+    fn test_fetch_failed_jobs_collects_errors() {
+        let github_cli = gh::gh_cli_fake::GitHubCliFake::with_fixtures(
+            "https://github.com/luftkode/distro-template".to_string(),
+            gh::fixture::Fixtures::new(
+                concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures").into(),
+            ),
+        );
+        let job_ids = ["21442749267", "404", "21460523393", "21460523544"].map(String::from);
+        for parallelism in [1, 2, 8] {
+            let results =
+                fetch_failed_jobs(&github_cli, "1", &job_ids, WorkflowKind::Yocto, parallelism);
+            let ids: Vec<_> = results
+                .iter()
+                .map(|result| {
+                    result
+                        .as_ref()
+                        .map(|job| job.id())
+                        .map_err(|e| e.to_string())
+                })
+                .collect();
+            assert_eq!(ids[0], Ok("21442749267"));
+            assert!(ids[1].as_ref().unwrap_err().contains("404.log"), "{ids:?}");
+            assert_eq!(ids[2..], [Ok("21460523393"), Ok("21460523544")]);
+        }
+    }
+
+    #[test]
+    fn test_tracking_issue_once_per_run() {
+        use gh::GitHub;
+        let github_cli = gh::gh_cli_fake::GitHubCliFake::new(
+            "https://github.com/luftkode/distro-template".into(),
+        );
+        let options = CreateIssueOptions::new("bug".to_string(), WorkflowKind::Yocto);
+        let run_link = "https://github.com/luftkode/distro-template/actions/runs/7858139663";
+        let tracking_issues = |outcome: IssueOutcome| {
+            let job_issue = Issue::new(
+                "7858139663".to_string(),
+                run_link.to_string(),
+                "Use template and build image".to_string(),
+                "master".to_string(),
+                vec![],
+                "bug".to_string(),
+            );
+            let job_issues = [(job_issue, outcome)];
+            let body = tracking_issue_body("7858139663", run_link, &job_issues);
+            create_tracking_issue(
+                &github_cli,
+                "7858139663",
+                "1 job failed",
+                &body,
+                &job_issues,
+                &options,
+            )
+            .unwrap();
+            github_cli
+                .issues_open_with_label(None, "bug")
+                .unwrap()
+                .iter()
+                .filter(|issue| issue.title.starts_with(TRACKING_ISSUE_PREFIX))
+                .count()
+        };
+
+        // Neither known nor suppressed failures get a tracking issue
+        let url = "https://github.com/luftkode/distro-template/issues/1".to_string();
+        assert_eq!(tracking_issues(IssueOutcome::DuplicateOf(url.clone())), 0);
+        assert_eq!(tracking_issues(IssueOutcome::Suppressed), 0);
+        assert_eq!(tracking_issues(IssueOutcome::Created(url.clone())), 1);
+        // Another issue created for the same run doesn't create another tracking issue
+        assert_eq!(tracking_issues(IssueOutcome::Created(url.clone())), 1);
+        // Not even when the tracking issue isn't among the 30 newest issues with the label
+        for i in 0..40 {
+            github_cli
+                .create_issue(
+                    None,
+                    &format!("Other {i}"),
+                    "Other failure",
+                    &["bug".to_string()],
+                    &[],
+                )
+                .unwrap();
+        }
+        assert_eq!(tracking_issues(IssueOutcome::Created(url)), 1);
+    }
+
+    #[test]
+    fn test_duplicate_of_issue_beyond_first_30() {
+        use gh::GitHub;
+        let github_cli = gh::gh_cli_fake::GitHubCliFake::with_fixtures(
+            "https://github.com/luftkode/distro-template".to_string(),
+            gh::fixture::Fixtures::new(
+                concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures").into(),
+            ),
+        );
+        github_cli
+            .create_label(None, "bug", "FF0000", "", false)
+            .unwrap();
+        let issue =
+            issue_from_run(&github_cli, "7858139663", "bug", WorkflowKind::Yocto, 1).unwrap();
+        let url = github_cli
+            .create_issue(
+                None,
+                issue.title(),
+                &issue.body().unwrap(),
+                &["bug".to_string()],
+                &[],
+            )
+            .unwrap();
+        for i in 0..40 {
+            github_cli
+                .create_issue(
+                    None,
+                    &format!("Other {i}"),
+                    "Other failure",
+                    &["bug".to_string()],
+                    &[],
+                )
+                .unwrap();
+        }
+        assert!(
+            github_cli
+                .issues_open_with_label(None, "bug")
+                .unwrap()
+                .len()
+                > 30
+        );
+        assert_eq!(
+            find_duplicate(&github_cli, &issue, "bug").unwrap(),
+            DuplicateMatch::Exact { url }
+        );
+    }
+
     // Regression test for https://github.com/luftkode/gh-workflow-parser/issues/9
     /// Large issue text with many timestamps doesn't make the issues dissimilar
     #[test]
@@ -257,7 +1077,7 @@ Yocto error: ERROR: No recipes available for: ...
         assert!(distance < LEVENSHTEIN_THRESHOLD, "Distance: {distance}");
     }
 
-    const ISSUE_FREQUENT_TIMESTAMPS_TEXT1: &'static str = r#"**Run ID**: 8072883145 [LINK TO RUN](https://github.com/luftkode/distro-template/actions/runs/8072883145)
+    const ISSUE_FREQUENT_TIMESTAMPS_TEXT1: &str = r#"**Run ID**: 8072883145 [LINK TO RUN](https://github.com/luftkode/distro-template/actions/runs/8072883145)
 
 **1 job failed:**
 - **`Test template xilinx`**
@@ -408,7 +1228,7 @@ env:
 ##[error]Input required and not supplied: path
 ```"#;
 
-    const ISSUE_FREQUENT_TIMESTAMPS_TEXT2: &'static str = r#"**Run ID**: 8057183947 [LINK TO RUN](https://github.com/luftkode/distro-template/actions/runs/8057183947)
+    const ISSUE_FREQUENT_TIMESTAMPS_TEXT2: &str = r#"**Run ID**: 8057183947 [LINK TO RUN](https://github.com/luftkode/distro-template/actions/runs/8057183947)
 
 **1 job failed:**
 - **`Test template xilinx`**
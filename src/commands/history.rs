@@ -0,0 +1,92 @@
+use crate::{
+    gh::{self, RunFilter},
+    history::{HistoryDb, HistoryQuery},
+    util::human_duration,
+};
+use std::{error::Error, io::Write};
+
+/// Record the runs matching the filter, so the time to recovery can be computed from green runs
+pub fn sync(
+    github_cli: Box<dyn gh::GitHub>,
+    db: &mut HistoryDb,
+    filter: &RunFilter,
+    dry_run: bool,
+) -> Result<(), Box<dyn Error>> {
+    let runs = github_cli.list_runs(None, filter)?;
+    if dry_run {
+        println!("DRY RUN MODE! {} run(s) would be recorded", runs.len());
+        return Ok(());
+    }
+    db.record_runs(github_cli.default_repo(), &runs)?;
+    crate::macros::pipe_println!("Recorded {} run(s)", runs.len())?;
+    Ok(())
+}
+
+/// Print the jobs that failed most often
+pub fn top_jobs(db: &HistoryDb, query: &HistoryQuery) -> Result<(), Box<dyn Error>> {
+    for job in db.top_jobs(query)? {
+        crate::macros::pipe_println!(
+            "{}\t{}\t{}\tlast failed {}",
+            job.failures,
+            job.job_name,
+            job.workflow,
+            job.last_failed_at
+        )?;
+    }
+    Ok(())
+}
+
+/// Print the failures that occurred most often
+pub fn top_fingerprints(db: &HistoryDb, query: &HistoryQuery) -> Result<(), Box<dyn Error>> {
+    for failure in db.top_fingerprints(query)? {
+        crate::macros::pipe_println!(
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            failure.failures,
+            failure.fingerprint,
+            failure.job_name,
+            failure.failure_label.as_deref().unwrap_or("-"),
+            failure.recipe.as_deref().unwrap_or("-"),
+            failure.issue_url.as_deref().unwrap_or("-")
+        )?;
+    }
+    Ok(())
+}
+
+/// Print the failures of a job, oldest first
+pub fn timeline(db: &HistoryDb, job: &str, query: &HistoryQuery) -> Result<(), Box<dyn Error>> {
+    for record in db.timeline(job, query)? {
+        crate::macros::pipe_println!(
+            "{}\t{}\t{}\t{}",
+            record.failed_at,
+            record.run_link,
+            record.failure_label.as_deref().unwrap_or("-"),
+            record.issue_url.as_deref().unwrap_or("-")
+        )?;
+    }
+    Ok(())
+}
+
+/// Print the mean time to recovery per job
+pub fn mttr(db: &HistoryDb, job: Option<&str>, query: &HistoryQuery) -> Result<(), Box<dyn Error>> {
+    for recovery in db.mttr(job, query)? {
+        let mttr = match recovery.mean_seconds {
+            Some(seconds) => format!(
+                "mttr {} ({} recoveries)",
+                human_duration(seconds),
+                recovery.recoveries
+            ),
+            None => "mttr -".to_string(),
+        };
+        let status = match &recovery.failing_since {
+            Some(since) => format!("failing since {since}"),
+            None => "recovered".to_string(),
+        };
+        crate::macros::pipe_println!(
+            "{}\t{}\t{}\t{mttr}\t{status}",
+            recovery.job_name,
+            recovery.workflow,
+            recovery.branch
+        )?;
+    }
+    Ok(())
+}
@@ -1,5 +1,9 @@
-use crate::util::first_path_from_str;
-use std::{io, path::PathBuf};
+use crate::{errlog::ErrorLog, util::first_path_from_str};
+use std::{
+    fs::File,
+    io::{self, BufReader},
+    path::PathBuf,
+};
 
 use super::BuildKind;
 
@@ -17,26 +21,26 @@ pub fn locate_failure_log(
     kind: BuildKind,
     log_file: Option<&PathBuf>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let logfile_content: String = match log_file {
+    // The log is streamed, only the error summary or the end of it is kept
+    let log = match log_file {
         Some(file) => {
             log::info!("Reading log file: {file:?}");
             if !file.exists() {
                 return Err(format!("File: {file:?} does not exist",).into());
             }
-            std::fs::read_to_string(file)?
+            ErrorLog::from_local_log(String::new(), "", "", BufReader::new(File::open(file)?))?
         },
         None => {
             log::info!("Reading log from stdin");
-            let stdin = io::stdin();
-            let mut handle = stdin.lock();
-            let mut buf = String::new();
-            io::Read::read_to_string(&mut handle, &mut buf)?;
-            buf
+            ErrorLog::from_local_log(String::new(), "", "", io::stdin().lock())?
         },
     };
 
     match kind {
-        BuildKind::Yocto => locate_yocto_failure_log(&logfile_content)?,
+        BuildKind::Yocto => match log.yocto_error_summary() {
+            Some(error_summary) => locate_yocto_failure_log(error_summary)?,
+            None => locate_yocto_failure_log(&log.context())?,
+        },
         BuildKind::Other => todo!("This feature is not implemented yet!"),
     }
 
@@ -0,0 +1,32 @@
+use super::WorkflowKind;
+use crate::{
+    gh::{self, RunFilter},
+    report::{Period, Report, ReportFormat},
+};
+use std::{error::Error, io::Write};
+
+/// Print the failure statistics of the runs matching the filter over the period
+pub fn report(
+    github_cli: Box<dyn gh::GitHub>,
+    filter: &RunFilter,
+    period: Period,
+    kind: WorkflowKind,
+    log_parallelism: usize,
+    top: usize,
+    format: ReportFormat,
+) -> Result<(), Box<dyn Error>> {
+    let report = Report::collect(
+        github_cli.as_ref(),
+        filter,
+        period,
+        kind,
+        log_parallelism,
+        top,
+    )?;
+    let output = match format {
+        ReportFormat::Markdown => report.to_markdown(),
+        ReportFormat::Json => serde_json::to_string_pretty(&report)?,
+    };
+    crate::macros::pipe_println!("{output}")?;
+    Ok(())
+}
@@ -0,0 +1,122 @@
+use super::create_issue_from_run::{create_issue_from_repo_run, RepoIssueOptions};
+use crate::{
+    serve::{handle_webhook, FailedRun, EVENT_HEADER, SIGNATURE_HEADER},
+    settings::ServeSettings,
+};
+use std::{
+    error::Error,
+    io::{Read, Write},
+    sync::{mpsc, Arc},
+    thread,
+};
+
+/// Maximum size of a webhook payload accepted by GitHub
+const MAX_PAYLOAD_LEN: u64 = 25 * 1024 * 1024;
+
+/// Receive `workflow_run` webhooks on the address and create issues for failed runs
+///
+/// Requests are answered right away, the issues are created one at a time in the background.
+pub fn serve(
+    bind: &str,
+    secret: Vec<u8>,
+    settings: ServeSettings,
+    options: RepoIssueOptions,
+) -> Result<(), Box<dyn Error>> {
+    let server =
+        tiny_http::Server::http(bind).map_err(|e| format!("Could not listen on {bind}: {e}"))?;
+    crate::macros::pipe_println!("Listening on http://{}", server.server_addr())?;
+
+    let options = Arc::new(options);
+    let mut worker = Worker::spawn(Arc::clone(&options));
+
+    for mut request in server.incoming_requests() {
+        if request.method() != &tiny_http::Method::Post {
+            respond(request, 405, "Method not allowed".to_string());
+            continue;
+        }
+        let event = header(&request, EVENT_HEADER);
+        let signature = header(&request, SIGNATURE_HEADER);
+        let mut payload = vec![];
+        if let Err(e) = request
+            .as_reader()
+            .take(MAX_PAYLOAD_LEN)
+            .read_to_end(&mut payload)
+        {
+            log::warn!("Failed to read the request body: {e}");
+            respond(
+                request,
+                400,
+                format!("Failed to read the request body: {e}"),
+            );
+            continue;
+        }
+        let response = handle_webhook(
+            &secret,
+            &settings,
+            event.as_deref(),
+            signature.as_deref(),
+            &payload,
+        );
+        log::info!("{} {}", response.status, response.message);
+        if let Some(failed_run) = response.failed_run {
+            if let Err(mpsc::SendError(failed_run)) = worker.sender.send(failed_run) {
+                log::error!("The issue creation thread stopped, restarting it");
+                worker = Worker::spawn(Arc::clone(&options));
+                if worker.sender.send(failed_run).is_err() {
+                    return Err("Failed to restart the issue creation thread".into());
+                }
+            }
+        }
+        respond(request, response.status, response.message);
+    }
+    drop(worker.sender);
+    worker
+        .handle
+        .join()
+        .map_err(|_| "The issue creation thread panicked")?;
+    Ok(())
+}
+
+/// Creates the issues of the failed runs one at a time in the background
+struct Worker {
+    sender: mpsc::Sender<FailedRun>,
+    handle: thread::JoinHandle<()>,
+}
+
+impl Worker {
+    fn spawn(options: Arc<RepoIssueOptions>) -> Self {
+        let (sender, receiver) = mpsc::channel::<FailedRun>();
+        let handle = thread::spawn(move || {
+            for failed_run in receiver {
+                if let Err(e) =
+                    create_issue_from_repo_run(&failed_run.repo, failed_run.run_id, &options)
+                {
+                    log::error!(
+                        "Failed to create an issue for run {} of {}: {e}",
+                        failed_run.run_id,
+                        failed_run.repo
+                    );
+                }
+            }
+        });
+        Self { sender, handle }
+    }
+}
+
+/// Answer a request, a failure to answer (e.g. because the client disconnected) is only logged
+fn respond(request: tiny_http::Request, status: u16, message: String) {
+    if let Err(e) =
+        request.respond(tiny_http::Response::from_string(message).with_status_code(status))
+    {
+        log::warn!("Failed to respond to the request: {e}");
+    }
+}
+
+/// The value of a request header, header names are case insensitive
+fn header(request: &tiny_http::Request, name: &str) -> Option<String> {
+    request
+        .headers()
+        .iter()
+        .find(|header| header.field.as_str().as_str().eq_ignore_ascii_case(name))
+        .map(|header| header.value.as_str().to_string())
+}
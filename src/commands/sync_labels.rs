@@ -0,0 +1,35 @@
+use crate::{gh, labels::LabelPolicy};
+use std::{error::Error, io::Write};
+
+/// Create or update every label of the [taxonomy](LabelPolicy::taxonomy) in the repository
+///
+/// Existing labels are overwritten with the color and description of the policy. In dry run mode
+/// the labels are only printed.
+pub fn sync_labels(
+    github_cli: Box<dyn gh::GitHub>,
+    issue_label: &str,
+    policy: &LabelPolicy,
+    dry_run: bool,
+) -> Result<(), Box<dyn Error>> {
+    let taxonomy = policy.taxonomy(issue_label);
+    if dry_run {
+        println!("DRY RUN MODE! The following labels would be created or updated:");
+    }
+    for spec in &taxonomy {
+        if dry_run {
+            crate::macros::pipe_println!(
+                "{name}\t#{color}\t{description}",
+                name = spec.name,
+                color = spec.color,
+                description = spec.description
+            )?;
+        } else {
+            github_cli.create_label(None, &spec.name, &spec.color, &spec.description, true)?;
+            log::info!("Synced label {} (#{})", spec.name, spec.color);
+        }
+    }
+    if !dry_run {
+        log::info!("Synced {} labels", taxonomy.len());
+    }
+    Ok(())
+}
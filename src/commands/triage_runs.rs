@@ -0,0 +1,168 @@
+use super::create_issue_from_run::{handle_issue, issue_from_run, CreateIssueOptions};
+use crate::{
+    gh::{self, util::issue_number_from_url, RunFilter},
+    history::{FailureRecord, HistoryDb},
+    issue::{markdown_list, Issue, IssueOutcome},
+};
+use std::{error::Error, io::Write};
+
+/// A failure and the runs it occurred in
+#[derive(Debug)]
+pub struct DistinctFailure {
+    /// The failure in the newest run
+    pub issue: Issue,
+    /// Links to the runs with the failure, newest first
+    pub run_links: Vec<String>,
+    /// The failed jobs in each run, for the history database
+    pub records: Vec<FailureRecord>,
+}
+
+/// Analyze the runs matching the filter and group the failed jobs by fingerprint
+///
+/// Runs that can't be analyzed (e.g. no failed jobs) are skipped with a warning.
+pub fn distinct_failures(
+    github_cli: &dyn gh::GitHub,
+    filter: &RunFilter,
+    options: &CreateIssueOptions,
+) -> Result<Vec<DistinctFailure>, Box<dyn Error>> {
+    let runs = github_cli.list_runs(None, filter)?;
+    log::info!("Triaging {} run(s)", runs.len());
+    let mut failures: Vec<DistinctFailure> = vec![];
+    for run in &runs {
+        let run_id = run.database_id.to_string();
+        let issue = match issue_from_run(
+            github_cli,
+            &run_id,
+            &options.label,
+            options.kind,
+            options.log_parallelism,
+        ) {
+            Ok(issue) => issue.with_templates(options.templates.clone())?,
+            Err(e) => {
+                log::warn!("Skipping run {run_id}: {e}");
+                continue;
+            },
+        };
+        for job_issue in issue.into_per_job_issues()? {
+            let run_link = job_issue.run_link().trim().to_string();
+            let records = FailureRecord::from_issue(
+                github_cli.default_repo(),
+                &job_issue,
+                &run.created_at,
+                None,
+            );
+            match failures
+                .iter_mut()
+                .find(|failure| failure.issue.fingerprint() == job_issue.fingerprint())
+            {
+                Some(failure) => {
+                    if !failure.run_links.contains(&run_link) {
+                        failure.run_links.push(run_link);
+                    }
+                    failure.records.extend(records);
+                },
+                None => failures.push(DistinctFailure {
+                    issue: job_issue,
+                    run_links: vec![run_link],
+                    records,
+                }),
+            }
+        }
+    }
+    Ok(failures)
+}
+
+/// Triage the failed runs matching the filter, creating one issue per distinct failure
+///
+/// Issues list the affected runs. If an issue for the failure already exists, the affected runs are
+/// added to it as a comment instead.
+pub fn triage_runs(
+    github_cli: Box<dyn gh::GitHub>,
+    filter: &RunFilter,
+    options: &CreateIssueOptions,
+) -> Result<(), Box<dyn Error>> {
+    let failures = distinct_failures(github_cli.as_ref(), filter, options)?;
+    log::info!("Found {} distinct failure(s)", failures.len());
+    let mut history_db = match &options.history_db {
+        Some(path) if !options.dry_run => Some(HistoryDb::open(path)?),
+        _ => None,
+    };
+    for DistinctFailure {
+        issue,
+        run_links,
+        mut records,
+    } in failures
+    {
+        let issue = issue.with_affected_runs(run_links.clone());
+        let (issue, outcome) = handle_issue(github_cli.as_ref(), issue, None, options)?;
+        let result = match &outcome {
+            IssueOutcome::Created(url) => format!("created {url}"),
+            IssueOutcome::Reopened(url) => format!("reopened {url}"),
+            IssueOutcome::DuplicateOf(url) => {
+                if comment_affected_runs(github_cli.as_ref(), url, &run_links, options)? {
+                    format!("updated {url}")
+                } else {
+                    format!("already listed in {url}")
+                }
+            },
+            IssueOutcome::NotCreated => "not created".to_string(),
+            IssueOutcome::Suppressed => "suppressed as flaky".to_string(),
+        };
+        if let Some(db) = &mut history_db {
+            for record in &mut records {
+                record.issue_url = outcome.issue_url().map(str::to_string);
+            }
+            db.record_failures(&records)?;
+        }
+        crate::macros::pipe_println!(
+            "{runs} run(s)\t{result}\t{title}",
+            runs = run_links.len(),
+            title = issue.title()
+        )?;
+    }
+    Ok(())
+}
+
+/// Comment on an existing issue with the runs the failure occurred in
+///
+/// Runs that are already listed in the issue or in an earlier comment are left out, returns `false`
+/// if there were no new runs to comment with.
+fn comment_affected_runs(
+    github_cli: &dyn gh::GitHub,
+    issue_url: &str,
+    run_links: &[String],
+    options: &CreateIssueOptions,
+) -> Result<bool, Box<dyn Error>> {
+    let number = issue_number_from_url(issue_url)
+        .ok_or_else(|| format!("Could not get the issue number from {issue_url}"))?;
+    let mut listed: Vec<String> = github_cli
+        .issues_open_with_label(None, &options.label)?
+        .into_iter()
+        .filter(|issue| issue.url == issue_url)
+        .map(|issue| issue.body)
+        .collect();
+    listed.extend(github_cli.issue_comments(None, number)?);
+    let new_links: Vec<String> = run_links
+        .iter()
+        .filter(|link| !listed.iter().any(|text| mentions_link(text, link)))
+        .cloned()
+        .collect();
+    if new_links.is_empty() {
+        log::info!("All affected runs are already listed in {issue_url}");
+        return Ok(false);
+    }
+
+    let comment = format!("Also failed in:\n{}", markdown_list(&new_links));
+    if options.dry_run {
+        println!("DRY RUN MODE! The following comment would be added to {issue_url}:\n{comment}");
+        return Ok(true);
+    }
+    github_cli.add_issue_comment(None, number, &comment)?;
+    Ok(true)
+}
+
+/// Whether the text contains the link, a link to run `123` isn't mentioned by a link to run `1234`
+fn mentions_link(text: &str, link: &str) -> bool {
+    text.match_indices(link)
+        .any(|(start, _)| !text[start + link.len()..].starts_with(|c: char| c.is_ascii_digit()))
+}
@@ -0,0 +1,142 @@
+use super::create_issue_from_run::{create_issue_from_repo_run, RepoIssueOptions};
+use crate::{
+    gh::{self, RunFilter},
+    settings::WatchSettings,
+    watch::{Backoff, RepoState, Shutdown, WatchState},
+};
+use std::{collections::HashMap, error::Error, path::PathBuf, time::Duration};
+
+/// Number of recent runs listed per repository on every poll
+const RUNS_PER_POLL: usize = 100;
+/// A run that fails to be processed this many times is skipped
+const MAX_ATTEMPTS: u32 = 3;
+
+pub struct WatchOptions {
+    /// Repository URLs
+    pub repos: Vec<String>,
+    pub interval: Duration,
+    pub state_file: PathBuf,
+    /// Poll once and exit
+    pub once: bool,
+    pub issue: RepoIssueOptions,
+}
+
+/// Poll the repositories for newly failed runs and create issues for them until SIGINT or SIGTERM
+pub fn watch(settings: &WatchSettings, options: &WatchOptions) -> Result<(), Box<dyn Error>> {
+    let shutdown = Shutdown::register()?;
+    let mut state = WatchState::load(&options.state_file)?;
+    let mut backoff = Backoff::new(options.interval);
+    let mut attempts: HashMap<u64, u32> = HashMap::new();
+    loop {
+        let mut polled = 0;
+        let mut failures = Vec::new();
+        for repo in &options.repos {
+            if shutdown.requested() {
+                break;
+            }
+            polled += 1;
+            // A failing repository doesn't hold back the others
+            if let Err(e) = poll(
+                repo,
+                settings,
+                options,
+                &mut state,
+                &mut attempts,
+                &shutdown,
+            ) {
+                log::error!("Failed to poll {repo}: {e}");
+                failures.push(format!("{repo}: {e}"));
+            }
+        }
+        if options.once {
+            if !failures.is_empty() {
+                return Err(format!(
+                    "Failed to poll {} of {polled} repositories:\n{}",
+                    failures.len(),
+                    failures.join("\n")
+                )
+                .into());
+            }
+            break;
+        }
+        if polled > 0 && failures.len() == polled {
+            backoff.failed();
+            log::error!(
+                "Polling all repositories failed, retrying in {:?}",
+                backoff.delay()
+            );
+        } else {
+            backoff.succeeded();
+        }
+        if !shutdown.sleep(backoff.delay()) {
+            break;
+        }
+    }
+    if shutdown.requested() {
+        log::info!("Shutting down");
+    }
+    Ok(())
+}
+
+/// Process the runs of a repository that completed since the last poll
+fn poll(
+    repo: &str,
+    settings: &WatchSettings,
+    options: &WatchOptions,
+    state: &mut WatchState,
+    attempts: &mut HashMap<u64, u32>,
+    shutdown: &Shutdown,
+) -> Result<(), Box<dyn Error>> {
+    let github_cli = gh::init_github_cli_with(repo.to_string(), &options.issue.github_cli)?;
+    let filter = RunFilter {
+        limit: RUNS_PER_POLL,
+        ..Default::default()
+    };
+    let runs = github_cli.list_runs(None, &filter)?;
+
+    let Some(repo_state) = state.repos.get_mut(repo) else {
+        // Older failures have been handled before the repository was watched
+        let repo_state = RepoState::starting_at(&runs);
+        log::info!(
+            "Watching {repo} for runs after {} and {} run(s) in progress",
+            repo_state.last_run_id,
+            repo_state.pending.len()
+        );
+        state.repos.insert(repo.to_string(), repo_state);
+        return state.save(&options.state_file);
+    };
+    repo_state.track(&runs);
+    let completed = repo_state.completed(runs);
+    state.save(&options.state_file)?;
+
+    for run in completed {
+        if shutdown.requested() {
+            break;
+        }
+        if run.conclusion != "failure" {
+            log::debug!(
+                "Run {} of {repo} concluded with {}",
+                run.database_id,
+                run.conclusion
+            );
+        } else if let Some(reason) = settings.ignores(&run) {
+            log::info!("Ignored run {} of {repo}: {reason}", run.database_id);
+        } else if let Err(e) = create_issue_from_repo_run(repo, run.database_id, &options.issue) {
+            let attempt = attempts.entry(run.database_id).or_default();
+            *attempt += 1;
+            if *attempt < MAX_ATTEMPTS {
+                return Err(
+                    format!("Failed to process run {} of {repo}: {e}", run.database_id).into(),
+                );
+            }
+            log::error!(
+                "Skipping run {} of {repo} after {MAX_ATTEMPTS} failed attempts: {e}",
+                run.database_id
+            );
+        }
+        attempts.remove(&run.database_id);
+        state.processed(repo, run.database_id);
+        state.save(&options.state_file)?;
+    }
+    Ok(())
+}
@@ -1,13 +1,28 @@
 //! CLI configuration and initialization
-use crate::gh::gh_cli;
+use crate::gh::{
+    cache::DEFAULT_CACHE_TTL,
+    gh_cli,
+    retry::{
+        RetryPolicy, DEFAULT_GH_TIMEOUT, DEFAULT_INITIAL_BACKOFF, DEFAULT_MAX_BACKOFF,
+        DEFAULT_MAX_RATE_LIMIT_WAIT, DEFAULT_RETRIES,
+    },
+    GitHubCliSettings,
+};
+use crate::settings::Settings;
 use crate::util::check_gh_cli_version;
 
-use super::commands::Command;
+use super::commands::{create_issue_from_run::DEFAULT_LOG_PARALLELISM, Command};
 use clap::builder::styling::{AnsiColor, Effects, Styles};
 use clap::*;
 use std::error::Error;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use which::which;
 
+/// The host of GitHub if no other host is configured
+pub const DEFAULT_HOST: &str = "github.com";
+
 /// The minimum version of the GitHub CLI required for `gh-workflow-parser` to run as expected.
 pub const GH_CLI_MIN_VERSION: semver::Version = semver::Version::new(2, 43, 1);
 
@@ -16,12 +31,54 @@ pub const GH_CLI_MIN_VERSION: semver::Version = semver::Version::new(2, 43, 1);
 pub struct Config {
     #[command(subcommand)]
     command: Option<Command>,
+    /// Configuration file (TOML), see the `settings` module for the available settings
+    #[arg(long, global = true, value_hint = ValueHint::FilePath)]
+    config: Option<PathBuf>,
     /// Debug flag to run through a scenario without making changes
     #[arg(long, default_value_t = false, global = true)]
     dry_run: bool,
     /// Fake the GitHub CLI for testing
     #[arg(long, default_value_t = false, global = true)]
     fake_github_cli: bool,
+    /// Directory of recorded GitHub responses for the fake GitHub CLI to replay
+    #[arg(long, global = true, value_hint = ValueHint::DirPath, requires = "fake_github_cli")]
+    fixtures: Option<PathBuf>,
+    /// Record every GitHub response to a fixture directory that can be replayed with `--fixtures`
+    #[arg(long, global = true, value_hint = ValueHint::DirPath, conflicts_with = "fake_github_cli")]
+    record: Option<PathBuf>,
+    /// File the fake GitHub CLI persists created issues, labels and comments to between runs
+    #[arg(long, global = true, value_hint = ValueHint::FilePath, requires = "fake_github_cli")]
+    fake_state: Option<PathBuf>,
+    /// Host of GitHub for repositories given as `<owner>/<repo>`, e.g. of a GitHub Enterprise
+    /// Server [default: the `host` of the `[github]` section of the --config file, $GH_HOST or
+    /// github.com]
+    #[arg(long, global = true, value_hint = ValueHint::Hostname)]
+    host: Option<String>,
+    /// Local SQLite database to record analyzed failures in and query with the `history` subcommands
+    #[arg(long, global = true, value_hint = ValueHint::FilePath)]
+    history_db: Option<PathBuf>,
+    /// Maximum number of failed job logs to fetch at the same time
+    #[arg(long, global = true, default_value_t = NonZeroUsize::new(DEFAULT_LOG_PARALLELISM).unwrap())]
+    log_parallelism: NonZeroUsize,
+    /// Number of times a GitHub call is retried when it fails transiently or is rate limited
+    #[arg(long, global = true, default_value_t = DEFAULT_RETRIES)]
+    retries: u32,
+    /// Seconds to wait before the first retry of a GitHub call, doubled for every following retry
+    #[arg(long, global = true, default_value_t = DEFAULT_INITIAL_BACKOFF.as_secs(), value_name = "SECONDS")]
+    retry_backoff: u64,
+    /// Longest wait in seconds for a GitHub rate limit to reset, calls fail if it resets later
+    #[arg(long, global = true, default_value_t = DEFAULT_MAX_RATE_LIMIT_WAIT.as_secs(), value_name = "SECONDS")]
+    max_rate_limit_wait: u64,
+    /// Seconds a GitHub call may take before it's aborted, 0 waits until it finishes
+    #[arg(long, global = true, default_value_t = DEFAULT_GH_TIMEOUT.as_secs(), value_name = "SECONDS")]
+    gh_timeout: u64,
+    /// Seconds the responses of GitHub queries (e.g. labels and open issues) are cached, 0 disables
+    /// the cache
+    #[arg(long, global = true, default_value_t = DEFAULT_CACHE_TTL.as_secs(), value_name = "SECONDS")]
+    cache_ttl: u64,
+    /// Directory to keep cached GitHub responses in, so they are reused by later runs
+    #[arg(long, global = true, value_hint = ValueHint::DirPath, conflicts_with = "fake_github_cli")]
+    cache_dir: Option<PathBuf>,
     /// Verbosity level (0-4)
     #[arg(short, long, global = true, default_value_t = 2)]
     verbosity: u8,
@@ -41,6 +98,68 @@ impl Config {
         self.fake_github_cli
     }
 
+    /// Load the settings from the configuration file, or the default settings if none is given
+    pub fn settings(&self) -> Result<Settings, Box<dyn Error>> {
+        match &self.config {
+            Some(path) => Settings::from_file(path),
+            None => Ok(Settings::default()),
+        }
+    }
+
+    /// Get the host of GitHub for repositories given as `<owner>/<repo>`: `--host`, the `host` of
+    /// the `[github]` settings, `GH_HOST` or github.com
+    ///
+    /// The host of a repository given as URL is taken from the URL, see
+    /// [canonicalize_repo_url](crate::util::canonicalize_repo_url)
+    pub fn host(&self) -> Result<String, Box<dyn Error>> {
+        if let Some(host) = &self.host {
+            return Ok(host.to_owned());
+        }
+        if let Some(host) = self.settings()?.github.host {
+            return Ok(host);
+        }
+        Ok(std::env::var("GH_HOST")
+            .ok()
+            .filter(|host| !host.is_empty())
+            .unwrap_or_else(|| DEFAULT_HOST.to_string()))
+    }
+
+    /// Get the path of the failure history database
+    pub fn history_db(&self) -> Option<&Path> {
+        self.history_db.as_deref()
+    }
+
+    /// Get the maximum number of failed job logs to fetch at the same time
+    pub fn log_parallelism(&self) -> usize {
+        self.log_parallelism.get()
+    }
+
+    /// Get the settings for initializing the GitHub CLI
+    pub fn github_cli_settings(&self) -> GitHubCliSettings {
+        GitHubCliSettings {
+            fake: self.fake_github_cli,
+            fixtures: self.fixtures.clone(),
+            record: self.record.clone(),
+            fake_state: self.fake_state.clone(),
+            retry: self.retry_policy(),
+            timeout: (self.gh_timeout > 0).then(|| Duration::from_secs(self.gh_timeout)),
+            cache_ttl: Duration::from_secs(self.cache_ttl),
+            cache_dir: self.cache_dir.clone(),
+        }
+    }
+
+    /// Get the policy for retrying failed GitHub calls
+    pub fn retry_policy(&self) -> RetryPolicy {
+        let initial_backoff = Duration::from_secs(self.retry_backoff);
+        RetryPolicy {
+            retries: self.retries,
+            initial_backoff,
+            max_backoff: DEFAULT_MAX_BACKOFF.max(initial_backoff),
+            max_rate_limit_wait: Duration::from_secs(self.max_rate_limit_wait),
+            ..Default::default()
+        }
+    }
+
     /// Get the subcommand
     pub fn subcmd(&self) -> &Command {
         if self.command.is_none() {
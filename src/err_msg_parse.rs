@@ -1,5 +1,8 @@
 //! Parsing error messages from the Yocto and other workflows
-use crate::{commands::WorkflowKind, err_msg_parse::yocto_err::util::YoctoFailureKind};
+use crate::{
+    commands::WorkflowKind, err_msg_parse::yocto_err::util::YoctoFailureKind, errlog::ErrorLog,
+};
+use serde::Serialize;
 use std::error::Error;
 
 use self::yocto_err::YoctoError;
@@ -11,13 +14,22 @@ pub const LOGFILE_MAX_LEN: usize = 5000;
 
 pub mod yocto_err;
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
+#[serde(tag = "parser", content = "error", rename_all = "lowercase")]
 pub enum ErrorMessageSummary {
     Yocto(YoctoError),
     Other(String),
 }
 
 impl ErrorMessageSummary {
+    /// The name of the parser that produced the summary
+    pub fn parser(&self) -> &'static str {
+        match self {
+            ErrorMessageSummary::Yocto(_) => "yocto",
+            ErrorMessageSummary::Other(_) => "other",
+        }
+    }
+
     pub fn summary(&self) -> &str {
         match self {
             ErrorMessageSummary::Yocto(err) => err.summary(),
@@ -43,6 +55,26 @@ impl ErrorMessageSummary {
             ErrorMessageSummary::Other(_) => None,
         }
     }
+
+    /// The name of the failed recipe/package if the parser can determine it
+    pub fn recipe(&self) -> Option<String> {
+        match self {
+            ErrorMessageSummary::Yocto(err) => err.recipe(),
+            ErrorMessageSummary::Other(_) => None,
+        }
+    }
+}
+
+/// Parse the error of a failed job log: the Yocto error summary if there is one, otherwise the
+/// last lines of the log
+pub fn parse_error_log(
+    errlog: &ErrorLog,
+    workflow: WorkflowKind,
+) -> Result<ErrorMessageSummary, Box<dyn Error>> {
+    match (workflow, errlog.yocto_error_summary()) {
+        (WorkflowKind::Yocto, Some(summary)) => parse_error_message(summary, workflow),
+        _ => parse_error_message(&errlog.context(), workflow),
+    }
 }
 
 pub fn parse_error_message(
@@ -2,13 +2,14 @@ use crate::{
     commands::locate_failure_log::logfile_path_from_str, err_msg_parse::LOGFILE_MAX_LEN,
     util::first_path_from_str,
 };
+use serde::Serialize;
 use std::error::Error;
 
 use self::util::YoctoFailureKind;
 
 pub mod util;
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
 pub struct YoctoError {
     summary: String,
     kind: YoctoFailureKind,
@@ -33,9 +34,16 @@ impl YoctoError {
     pub fn logfile(&self) -> Option<&YoctoFailureLog> {
         self.logfile.as_ref()
     }
+
+    /// The recipe that failed, determined from the path of the failure logfile in the summary
+    pub fn recipe(&self) -> Option<String> {
+        let log_file_line = util::find_yocto_failure_log_str(&self.summary).ok()?;
+        let path = first_path_from_str(log_file_line).ok()?;
+        util::recipe_from_logfile_path(&path)
+    }
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
 pub struct YoctoFailureLog {
     pub name: String,
     pub contents: String,
@@ -1,4 +1,5 @@
 use std::error::Error;
+use std::path::Path;
 use strum::*;
 
 #[derive(
@@ -27,6 +28,13 @@ pub enum YoctoFailureKind {
     Misc,
 }
 
+/// Serialized as the task name e.g. `do_fetch`
+impl serde::Serialize for YoctoFailureKind {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
 impl YoctoFailureKind {
     /// Takes in a yocto logfile filename such as `log.do_fetch.21616` and attempts to determine the type
     /// of yocto task the the logfile is associated with.
@@ -52,9 +60,11 @@ impl YoctoFailureKind {
     }
 }
 
+/// The line that starts the error summary at the end of a failed Yocto build
+pub const YOCTO_ERROR_SUMMARY_SIGNATURE: &str = "--- Error summary ---";
+
 /// Find the `--- Error summary ---` section in the log and return the rest of the log.
 pub fn yocto_error_summary(log: &str) -> Result<String, Box<dyn Error>> {
-    const YOCTO_ERROR_SUMMARY_SIGNATURE: &str = "--- Error summary ---";
     let error_summary = log
         .split(YOCTO_ERROR_SUMMARY_SIGNATURE)
         .collect::<Vec<&str>>()
@@ -108,6 +118,33 @@ pub fn find_yocto_failure_log_str(log: &str) -> Result<&str, Box<dyn Error>> {
     Ok(log_file_line)
 }
 
+/// Determine the recipe name from the path of a Yocto failure logfile
+///
+/// Yocto stores task logs in `<TMPDIR>/work/<arch>/<recipe>/<version>/temp/log.<task>.<pid>`
+///
+/// # Example
+/// ```
+/// # use gh_workflow_parser::err_msg_parse::yocto_err::util::recipe_from_logfile_path;
+/// use std::path::Path;
+/// let path = Path::new("/app/yocto/build/tmp/work/x86_64-linux/sqlite3-native/3.43.2/temp/log.do_fetch.21616");
+/// assert_eq!(recipe_from_logfile_path(path).unwrap(), "sqlite3-native");
+///
+/// // Not a path in a Yocto work directory
+/// assert!(recipe_from_logfile_path(Path::new("/tmp/log.do_fetch.21616")).is_none());
+/// ```
+pub fn recipe_from_logfile_path(path: &Path) -> Option<String> {
+    let components: Vec<&str> = path
+        .components()
+        .filter_map(|c| c.as_os_str().to_str())
+        .collect();
+    let temp_idx = components.iter().rposition(|c| *c == "temp")?;
+    // The `temp` directory is preceded by `work/<arch>/<recipe>/<version>`
+    if temp_idx < 4 || components[temp_idx - 4] != "work" {
+        return None;
+    }
+    Some(components[temp_idx - 2].to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,54 +1,130 @@
 //! Contains the ErrorLog struct describing a failed job log from GitHub Actions.
+//!
+//! Logs are read line by line, so a log of any size can be parsed without loading it into memory:
+//! the prefix of each line is stripped, the last [CONTEXT_LINES] lines are kept in a ring buffer and
+//! the Yocto `--- Error summary ---` section is collected as it's encountered.
 use once_cell::sync::Lazy;
-use std::error::Error;
+use std::{collections::VecDeque, error::Error, io::BufRead};
 
 use regex::Regex;
 
+use crate::err_msg_parse::yocto_err::util::YOCTO_ERROR_SUMMARY_SIGNATURE;
+
+/// Number of lines at the end of a log that are kept as context of the error
+pub const CONTEXT_LINES: usize = 500;
+
+/// Maximum length of the Yocto error summary that is kept, the rest of a longer summary is dropped
+///
+/// The maximum size of a GitHub issue body is 65536
+pub const MAX_ERROR_SUMMARY_LEN: usize = 65536;
+
+/// Last line of a Yocto error summary that was longer than [MAX_ERROR_SUMMARY_LEN]
+pub const ERROR_SUMMARY_TRUNCATED: &str = "… (truncated)";
+
+static PREFIX_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(?P<failed_job>.*)\t(?P<failed_step>.*)\t(?P<timestamp>[0-9]{4}-[0-9]{2}-[0-9]{2})T[0-9]{2}:[0-9]{2}:[0-9]{2}.*Z ")
+        .expect("Failed to compile regex")
+});
+
 #[derive(Debug)]
 pub struct ErrorLog {
     job_id: String,
-    no_prefix_log: String,
     // Failed job/step can be retrieved from a failed job log by looking at the prefix
     prefix: ErrLogPrefix,
+    /// The last [CONTEXT_LINES] lines of the log without prefix
+    context: VecDeque<String>,
+    /// The lines after the last `--- Error summary ---` in the log
+    yocto_error_summary: Option<String>,
 }
 
 impl ErrorLog {
-    pub fn new(job_id: String, raw_log: String) -> Result<Self, Box<dyn Error>> {
-        static PREFIX_RE: Lazy<Regex> = Lazy::new(|| {
-            Regex::new(r"^(?P<failed_job>.*)\t(?P<failed_step>.*)\t(?P<timestamp>[0-9]{4}-[0-9]{2}-[0-9]{2})T[0-9]{2}:[0-9]{2}:[0-9]{2}.*Z ")
-                .expect("Failed to compile regex")
-        });
-        let first_line = raw_log
-            .lines()
-            .next()
-            .expect("Expected raw log to have at least one line");
-        let caps = PREFIX_RE.captures(first_line).map_or_else(
-            || {
-                panic!(
-                    "Expected the first line of the raw log to match the prefix regex: {first_line}"
-                )
-            },
-            |m| m,
-        );
-        let failed_job = caps.name("failed_job").unwrap().as_str().to_string();
-        let failed_step = caps.name("failed_step").unwrap().as_str().to_string();
-        let timestamp = caps.name("timestamp").unwrap().as_str().to_string();
-        let prefix = ErrLogPrefix::new(failed_job, failed_step, timestamp);
-
-        // Now trim the prefix from the log
-        let no_prefix_log =
-            raw_log
-                .lines()
-                .fold(String::with_capacity(raw_log.len() / 2), |mut acc, line| {
-                    let mut s = PREFIX_RE.replace(line, "").to_string();
-                    s.push('\n');
-                    acc.push_str(&s);
-                    acc
-                });
+    /// Read a failed job log from GitHub where every line starts with the
+    /// `job\tstep\ttimestamp` prefix
+    ///
+    /// # Errors
+    /// Returns an error if the log can't be read, is empty or the first line doesn't have the
+    /// prefix
+    pub fn new(job_id: String, raw_log: impl BufRead) -> Result<Self, Box<dyn Error>> {
+        Self::read(job_id, None, raw_log)
+    }
+
+    /// Read a log that may not have the `job\tstep\ttimestamp` prefix of the logs from GitHub,
+    /// e.g. a log saved locally
+    ///
+    /// If the first line has the prefix the log is handled like [ErrorLog::new], otherwise `job`
+    /// and `step` name the failed job and step and the timestamp is empty.
+    pub fn from_local_log(
+        job_id: String,
+        job: &str,
+        step: &str,
+        raw_log: impl BufRead,
+    ) -> Result<Self, Box<dyn Error>> {
+        let fallback = ErrLogPrefix::new(job.to_string(), step.to_string(), String::new());
+        Self::read(job_id, Some(fallback), raw_log)
+    }
+
+    fn read(
+        job_id: String,
+        fallback_prefix: Option<ErrLogPrefix>,
+        mut raw_log: impl BufRead,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut prefix = None;
+        let mut context = VecDeque::with_capacity(CONTEXT_LINES);
+        let mut yocto_error_summary: Option<String> = None;
+        let mut summary_truncated = false;
+        let mut buf = Vec::new();
+        loop {
+            buf.clear();
+            if raw_log.read_until(b'\n', &mut buf)? == 0 {
+                break;
+            }
+            let line = String::from_utf8_lossy(&buf);
+            let line = line.trim_end_matches(['\n', '\r']);
+            if prefix.is_none() {
+                if line.trim().is_empty() && fallback_prefix.is_some() {
+                    continue;
+                }
+                prefix = match (PREFIX_RE.captures(line), &fallback_prefix) {
+                    (Some(caps), _) => Some(ErrLogPrefix::new(
+                        caps["failed_job"].to_string(),
+                        caps["failed_step"].to_string(),
+                        caps["timestamp"].to_string(),
+                    )),
+                    (None, fallback) => fallback.clone(),
+                };
+                if prefix.is_none() {
+                    return Err(format!("Expected the first line of the log of job {job_id} to match the prefix regex: {line}").into());
+                }
+            }
+
+            let line = PREFIX_RE.replace(line, "");
+            if let Some((_, rest)) = line.split_once(YOCTO_ERROR_SUMMARY_SIGNATURE) {
+                // Only the last error summary is kept
+                yocto_error_summary = Some(format!("{rest}\n"));
+                summary_truncated = false;
+            } else if let Some(summary) = &mut yocto_error_summary {
+                // The lines after the cap are dropped, a single marker shows the summary is cut
+                if !summary_truncated {
+                    if summary.len() + line.len() < MAX_ERROR_SUMMARY_LEN {
+                        summary.push_str(&line);
+                    } else {
+                        summary.push_str(ERROR_SUMMARY_TRUNCATED);
+                        summary_truncated = true;
+                    }
+                    summary.push('\n');
+                }
+            }
+            if context.len() == CONTEXT_LINES {
+                context.pop_front();
+            }
+            context.push_back(line.into_owned());
+        }
+        let prefix = prefix.ok_or_else(|| format!("The log of job {job_id} is empty"))?;
         Ok(Self {
             job_id,
-            no_prefix_log,
             prefix,
+            context,
+            yocto_error_summary,
         })
     }
 
@@ -56,8 +132,19 @@ impl ErrorLog {
         &self.job_id
     }
 
-    pub fn no_prefix_log(&self) -> &str {
-        &self.no_prefix_log
+    /// The last [CONTEXT_LINES] lines of the log without prefix
+    pub fn context(&self) -> String {
+        self.context.iter().fold(String::new(), |mut acc, line| {
+            acc.push_str(line);
+            acc.push('\n');
+            acc
+        })
+    }
+
+    /// The section after the last `--- Error summary ---` of a Yocto log, at most
+    /// [MAX_ERROR_SUMMARY_LEN] long followed by [ERROR_SUMMARY_TRUNCATED] if it was longer
+    pub fn yocto_error_summary(&self) -> Option<&str> {
+        self.yocto_error_summary.as_deref()
     }
 
     pub fn failed_job(&self) -> &str {
@@ -73,7 +160,7 @@ impl ErrorLog {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ErrLogPrefix {
     failed_job: String,
     failed_step: String,
@@ -109,22 +196,92 @@ mod tests {
     use pretty_assertions::assert_eq;
 
     const TEST_LOG_STRING: &str = r#"Test template xilinx	📦 Build yocto image	2024-02-10T00:03:45.5797561Z ##[group]Run just --yes build-ci-image
-Test template xilinx	📦 Build yocto image	2024-02-10T00:03:45.5799911Z [36;1mjust --yes build-ci-image[0m
+Test template xilinx	📦 Build yocto image	2024-02-10T00:03:45.5799911Z [36;1mjust --yes build-ci-image[0m
 Test template xilinx	📦 Build yocto image	2024-02-10T00:03:45.5843410Z shell: /usr/bin/bash -e {0}
 "#;
 
     const TEST_LOG_STRING_NO_PREFIX: &str = r#"##[group]Run just --yes build-ci-image
-[36;1mjust --yes build-ci-image[0m
+[36;1mjust --yes build-ci-image[0m
 shell: /usr/bin/bash -e {0}
 "#;
 
     #[test]
     fn test_errlog_prefix() {
-        let err_log = ErrorLog::new("123".to_string(), TEST_LOG_STRING.to_owned()).unwrap();
+        let err_log = ErrorLog::new("123".to_string(), TEST_LOG_STRING.as_bytes()).unwrap();
         assert_eq!(err_log.failed_job(), "Test template xilinx");
         assert_eq!(err_log.failed_step(), "📦 Build yocto image");
         assert_eq!(err_log.timestamp(), "2024-02-10");
 
-        assert_eq!(err_log.no_prefix_log(), TEST_LOG_STRING_NO_PREFIX);
+        assert_eq!(err_log.context(), TEST_LOG_STRING_NO_PREFIX);
+        assert_eq!(err_log.yocto_error_summary(), None);
+    }
+
+    #[test]
+    fn test_errlog_requires_prefix() {
+        assert!(ErrorLog::new("1".to_string(), TEST_LOG_STRING_NO_PREFIX.as_bytes()).is_err());
+        assert!(ErrorLog::new("1".to_string(), "".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_errlog_from_local_log() {
+        let err_log =
+            ErrorLog::from_local_log("1".to_string(), "job", "step", TEST_LOG_STRING.as_bytes())
+                .unwrap();
+        assert_eq!(err_log.failed_job(), "Test template xilinx");
+        assert_eq!(err_log.context(), TEST_LOG_STRING_NO_PREFIX);
+
+        let err_log = ErrorLog::from_local_log(
+            "1".to_string(),
+            "job",
+            "step",
+            TEST_LOG_STRING_NO_PREFIX.as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(err_log.failed_job(), "job");
+        assert_eq!(err_log.failed_step(), "step");
+        assert_eq!(err_log.timestamp(), "");
+        assert_eq!(err_log.context(), TEST_LOG_STRING_NO_PREFIX);
+
+        assert!(ErrorLog::from_local_log("1".to_string(), "job", "step", "\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_errlog_keeps_last_lines_and_error_summary() {
+        let mut log = String::new();
+        for i in 0..2 * CONTEXT_LINES {
+            log.push_str(&format!(
+                "job\tstep\t2024-02-10T00:03:45.5797561Z line {i}\n"
+            ));
+        }
+        log.push_str("job\tstep\t2024-02-10T00:03:45.5797561Z NOTE: --- Error summary ---\n");
+        log.push_str("job\tstep\t2024-02-10T00:03:45.5797561Z ERROR: first\n");
+        log.push_str("job\tstep\t2024-02-10T00:03:45.5797561Z --- Error summary ---\n");
+        log.push_str("job\tstep\t2024-02-10T00:03:45.5797561Z ERROR: last\n");
+
+        let err_log = ErrorLog::new("1".to_string(), log.as_bytes()).unwrap();
+        let context = err_log.context();
+        assert_eq!(context.lines().count(), CONTEXT_LINES);
+        assert_eq!(
+            context.lines().next().unwrap(),
+            format!("line {}", CONTEXT_LINES + 4)
+        );
+        assert_eq!(context.lines().last().unwrap(), "ERROR: last");
+        assert_eq!(err_log.yocto_error_summary(), Some("\nERROR: last\n"));
+    }
+
+    #[test]
+    fn test_errlog_truncates_error_summary() {
+        let prefix = "job\tstep\t2024-02-10T00:03:45.5797561Z ";
+        let mut log = format!("{prefix}--- Error summary ---\n");
+        log.push_str(&format!("{prefix}ERROR: first\n"));
+        log.push_str(&format!("{prefix}{}\n", "x".repeat(MAX_ERROR_SUMMARY_LEN)));
+        log.push_str(&format!("{prefix}ERROR: after the long line\n"));
+        log.push_str(&format!("{prefix}ERROR: last\n"));
+
+        let err_log = ErrorLog::new("1".to_string(), log.as_bytes()).unwrap();
+        assert_eq!(
+            err_log.yocto_error_summary(),
+            Some(format!("\nERROR: first\n{ERROR_SUMMARY_TRUNCATED}\n").as_str())
+        );
     }
 }
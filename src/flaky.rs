@@ -0,0 +1,324 @@
+//! Detect flaky jobs from the history of a workflow
+//!
+//! A job is flaky if it both failed and passed for the same commit, either because it passed when
+//! the run was re-run (a new run attempt) or because it alternates between passing and failing
+//! across runs without a code change.
+//!
+//! The flake rate of a job is the share of its recent results that are failures for commits the job
+//! also passed for, i.e. failures that went away without a code change.
+use std::{collections::BTreeSet, error::Error};
+
+use serde::Serialize;
+
+use crate::{
+    gh::{GhJob, GhRun, GitHub, RunFilter},
+    issue::Issue,
+};
+
+/// Label of issues where every failed job is flaky
+pub const FLAKY_LABEL: &str = "flaky";
+
+/// The result of a job in a run attempt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JobResult {
+    pub run_id: u64,
+    pub attempt: u64,
+    /// The commit the run was triggered for, may be empty if unknown
+    pub head_sha: String,
+    pub passed: bool,
+}
+
+impl JobResult {
+    /// Results are compared by commit, or by run if the commit is unknown (attempts of a run always
+    /// share the commit)
+    fn commit_key(&self) -> String {
+        if self.head_sha.is_empty() {
+            format!("run {}", self.run_id)
+        } else {
+            self.head_sha.clone()
+        }
+    }
+}
+
+/// The jobs of a run attempt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunAttempt {
+    pub run_id: u64,
+    pub attempt: u64,
+    /// The commit the run was triggered for, may be empty if unknown
+    pub head_sha: String,
+    pub jobs: Vec<GhJob>,
+}
+
+/// The flakiness of a job over its recent results
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Flakiness {
+    pub job: String,
+    /// Failures for commits the job also passed for
+    pub flaky_failures: usize,
+    /// All recent results of the job
+    pub results: usize,
+}
+
+impl Flakiness {
+    /// Analyze the results of a job
+    ///
+    /// # Example
+    /// ```
+    /// # use gh_workflow_parser::flaky::{Flakiness, JobResult};
+    /// let result = |run_id, attempt, head_sha: &str, passed| JobResult {
+    ///     run_id,
+    ///     attempt,
+    ///     head_sha: head_sha.to_string(),
+    ///     passed,
+    /// };
+    /// let flakiness = Flakiness::from_results(
+    ///     "build",
+    ///     &[
+    ///         result(3, 1, "c0ffee", false),
+    ///         result(2, 2, "beef", true),
+    ///         result(2, 1, "beef", false),
+    ///         result(1, 1, "cafe", true),
+    ///     ],
+    /// );
+    /// assert!(flakiness.is_flaky());
+    /// assert_eq!(flakiness.rate(), 0.25);
+    /// ```
+    pub fn from_results(job: &str, results: &[JobResult]) -> Self {
+        let commits_with = |passed: bool| -> BTreeSet<String> {
+            results
+                .iter()
+                .filter(|result| result.passed == passed)
+                .map(JobResult::commit_key)
+                .collect()
+        };
+        let passed_commits = commits_with(true);
+        let flaky_commits: BTreeSet<String> = commits_with(false)
+            .intersection(&passed_commits)
+            .cloned()
+            .collect();
+        let flaky_failures = results
+            .iter()
+            .filter(|result| !result.passed && flaky_commits.contains(&result.commit_key()))
+            .count();
+        Self {
+            job: job.to_string(),
+            flaky_failures,
+            results: results.len(),
+        }
+    }
+
+    pub fn is_flaky(&self) -> bool {
+        self.flaky_failures > 0
+    }
+
+    /// The share of results that are flaky failures (0 to 1)
+    pub fn rate(&self) -> f64 {
+        if self.results == 0 {
+            0.0
+        } else {
+            self.flaky_failures as f64 / self.results as f64
+        }
+    }
+}
+
+/// Detects flaky jobs of an issue from the recent runs of its workflow
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlakinessDetector {
+    /// How many recent runs of the workflow to look at
+    pub history: usize,
+    /// Don't create issues for flaky failures with a flake rate below this (0 to 1)
+    pub suppress_below: Option<f64>,
+}
+
+impl FlakinessDetector {
+    /// The jobs of all attempts of the runs, newest first
+    pub fn run_attempts(
+        github_cli: &dyn GitHub,
+        runs: &[GhRun],
+    ) -> Result<Vec<RunAttempt>, Box<dyn Error>> {
+        let mut attempts = vec![];
+        for run in runs {
+            for attempt in (1..=run.attempt).rev() {
+                attempts.push(RunAttempt {
+                    run_id: run.database_id,
+                    attempt,
+                    head_sha: run.head_sha.clone(),
+                    jobs: github_cli.run_jobs(None, run.database_id, attempt)?,
+                });
+            }
+        }
+        Ok(attempts)
+    }
+
+    /// The results of a job in the run attempts
+    pub fn job_results(attempts: &[RunAttempt], job: &str) -> Vec<JobResult> {
+        attempts
+            .iter()
+            .filter_map(|attempt| {
+                let passed = match attempt.jobs.iter().find(|j| j.name == job) {
+                    Some(j) if j.conclusion == "success" => true,
+                    Some(j) if j.conclusion == "failure" => false,
+                    // Skipped, cancelled, in progress or not part of the run
+                    _ => return None,
+                };
+                Some(JobResult {
+                    run_id: attempt.run_id,
+                    attempt: attempt.attempt,
+                    head_sha: attempt.head_sha.clone(),
+                    passed,
+                })
+            })
+            .collect()
+    }
+
+    /// The attempts of the recent runs of the workflow and branch of the issue, newest first
+    ///
+    /// The attempts are the same for all issues of a run, so they are only listed once per run.
+    pub fn recent_attempts(
+        &self,
+        github_cli: &dyn GitHub,
+        issue: &Issue,
+    ) -> Result<Vec<RunAttempt>, Box<dyn Error>> {
+        let filter = RunFilter {
+            workflow: Some(issue.workflow().to_string()).filter(|w| !w.is_empty()),
+            branch: Some(issue.branch().to_string()).filter(|b| !b.is_empty()),
+            limit: self.history,
+            ..Default::default()
+        };
+        let runs = github_cli.list_runs(None, &filter)?;
+        log::debug!("Looking for flaky jobs in {} recent run(s)", runs.len());
+        Self::run_attempts(github_cli, &runs)
+    }
+
+    /// The flakiness of each failed job of the issue in the attempts of recent runs
+    pub fn detect(attempts: &[RunAttempt], issue: &Issue) -> Vec<Flakiness> {
+        issue
+            .failed_jobs()
+            .iter()
+            .map(|job| {
+                let results = Self::job_results(attempts, job.name());
+                let flakiness = Flakiness::from_results(job.name(), &results);
+                if flakiness.is_flaky() {
+                    log::info!(
+                        "Job {} is flaky with a flake rate of {:.0}%",
+                        job.name(),
+                        flakiness.rate() * 100.0
+                    );
+                }
+                flakiness
+            })
+            .collect()
+    }
+
+    /// Detect the flaky jobs in the attempts of recent runs and mark the issue with them
+    pub fn mark(attempts: &[RunAttempt], issue: Issue) -> Issue {
+        let flakiness = Self::detect(attempts, &issue);
+        issue.with_flakiness(flakiness)
+    }
+
+    /// Whether issue creation is suppressed, i.e. all failed jobs are flaky with a flake rate below
+    /// the threshold
+    pub fn suppresses(&self, issue: &Issue) -> bool {
+        let Some(threshold) = self.suppress_below else {
+            return false;
+        };
+        issue.is_flaky()
+            && issue
+                .flakiness()
+                .iter()
+                .all(|flakiness| !flakiness.is_flaky() || flakiness.rate() < threshold)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn result(run_id: u64, attempt: u64, head_sha: &str, passed: bool) -> JobResult {
+        JobResult {
+            run_id,
+            attempt,
+            head_sha: head_sha.to_string(),
+            passed,
+        }
+    }
+
+    #[test]
+    fn test_job_results() {
+        let job = |name: &str, conclusion: &str| GhJob {
+            name: name.to_string(),
+            conclusion: conclusion.to_string(),
+            ..Default::default()
+        };
+        let attempts = [
+            RunAttempt {
+                run_id: 2,
+                attempt: 2,
+                head_sha: "a".to_string(),
+                jobs: vec![job("build", "success"), job("test", "failure")],
+            },
+            RunAttempt {
+                run_id: 2,
+                attempt: 1,
+                head_sha: "a".to_string(),
+                jobs: vec![job("build", "failure"), job("test", "")],
+            },
+            RunAttempt {
+                run_id: 1,
+                attempt: 1,
+                head_sha: "b".to_string(),
+                jobs: vec![job("build", "skipped")],
+            },
+        ];
+        assert_eq!(
+            FlakinessDetector::job_results(&attempts, "build"),
+            [result(2, 2, "a", true), result(2, 1, "a", false)]
+        );
+        assert_eq!(
+            FlakinessDetector::job_results(&attempts, "test"),
+            [result(2, 2, "a", false)]
+        );
+    }
+
+    #[test]
+    fn test_passed_on_retry_is_flaky() {
+        let flakiness =
+            Flakiness::from_results("build", &[result(1, 2, "", true), result(1, 1, "", false)]);
+        assert!(flakiness.is_flaky());
+        assert_eq!(flakiness.rate(), 0.5);
+    }
+
+    #[test]
+    fn test_consistent_failure_is_not_flaky() {
+        // Failing since a commit and passing before it
+        let flakiness = Flakiness::from_results(
+            "build",
+            &[
+                result(3, 2, "b", false),
+                result(3, 1, "b", false),
+                result(2, 1, "b", false),
+                result(1, 1, "a", true),
+            ],
+        );
+        assert!(!flakiness.is_flaky());
+        assert_eq!(flakiness.rate(), 0.0);
+        assert_eq!(Flakiness::from_results("build", &[]).rate(), 0.0);
+    }
+
+    #[test]
+    fn test_alternating_for_same_commit_is_flaky() {
+        let flakiness = Flakiness::from_results(
+            "build",
+            &[
+                result(4, 1, "a", false),
+                result(3, 1, "a", true),
+                result(2, 1, "a", false),
+                result(1, 1, "a", true),
+            ],
+        );
+        assert_eq!(flakiness.flaky_failures, 2);
+        assert_eq!(flakiness.rate(), 0.5);
+    }
+}
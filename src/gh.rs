@@ -1,11 +1,19 @@
 //! Functions for interacting with GitHub via the `gh` CLI
+use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::ffi::{OsStr, OsString};
+use std::fmt::{self, Display, Formatter};
 use std::path::PathBuf;
+use std::str::FromStr;
 use std::sync::OnceLock;
+use std::time::Duration;
 
+pub mod actions;
+pub mod cache;
+pub mod fixture;
 pub mod gh_cli;
 pub mod gh_cli_fake;
+pub mod retry;
 pub mod util;
 
 /// Get the GitHub CLI and initialize it with a default repository
@@ -28,31 +36,370 @@ pub mod util;
 /// let github_cli = init_github_cli("https://example.com/repo".to_string(), false);
 /// ```
 pub fn init_github_cli(repo: String, fake: bool) -> Box<dyn GitHub> {
-    if fake {
-        Box::new(gh_cli_fake::GitHubCliFake::new(repo))
+    let settings = GitHubCliSettings {
+        fake,
+        ..Default::default()
+    };
+    init_github_cli_with(repo, &settings)
+        .expect("Initializing without a state file of the fake GitHub CLI can't fail")
+}
+
+/// Settings for how the GitHub CLI interface is initialized
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct GitHubCliSettings {
+    /// Use the fake GitHub CLI
+    pub fake: bool,
+    /// Directory the fake GitHub CLI replays responses from
+    pub fixtures: Option<PathBuf>,
+    /// Directory the real GitHub CLI records responses to
+    pub record: Option<PathBuf>,
+    /// File the fake GitHub CLI persists created issues and labels to
+    pub fake_state: Option<PathBuf>,
+    /// How the real GitHub CLI retries failed calls
+    pub retry: retry::RetryPolicy,
+    /// How long a call of the real GitHub CLI may take, `None` waits until it finishes
+    pub timeout: Option<Duration>,
+    /// How long the real GitHub CLI caches the responses of queries, zero disables the cache
+    pub cache_ttl: Duration,
+    /// Directory the real GitHub CLI keeps cached responses in so later runs can reuse them
+    pub cache_dir: Option<PathBuf>,
+}
+
+/// Get the GitHub CLI and initialize it with a default repository according to the settings
+///
+/// # Example
+///
+/// ```
+/// # use gh_workflow_parser::gh::{init_github_cli_with, GitHubCliSettings};
+/// let settings = GitHubCliSettings {
+///     fake: true,
+///     fixtures: Some("tests/fixtures".into()),
+///     ..Default::default()
+/// };
+/// let github_cli = init_github_cli_with("https://github.com/luftkode/distro-template".to_string(), &settings)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+///
+/// # Errors
+/// Returns an error if the state file of the fake GitHub CLI cannot be loaded
+pub fn init_github_cli_with(
+    repo: String,
+    settings: &GitHubCliSettings,
+) -> Result<Box<dyn GitHub>, Box<dyn Error>> {
+    if settings.fake {
+        let mut fake = match &settings.fixtures {
+            Some(dir) => gh_cli_fake::GitHubCliFake::with_fixtures(
+                repo,
+                fixture::Fixtures::new(dir.to_owned()),
+            ),
+            None => gh_cli_fake::GitHubCliFake::new(repo),
+        };
+        if let Some(path) = &settings.fake_state {
+            fake = fake.with_state_file(path.to_owned())?;
+        }
+        Ok(Box::new(fake))
     } else {
-        Box::new(gh_cli::GitHubCli::new(repo))
+        util::set_command_timeout(settings.timeout);
+        let github_cli = match &settings.record {
+            Some(dir) => {
+                gh_cli::GitHubCli::with_recorder(repo, fixture::Fixtures::new(dir.to_owned()))
+            },
+            None => gh_cli::GitHubCli::new(repo),
+        };
+        let github_cli: Box<dyn GitHub> = Box::new(retry::RetryingGitHub::new(
+            Box::new(github_cli),
+            settings.retry.clone(),
+        ));
+        if settings.cache_ttl.is_zero() {
+            return Ok(github_cli);
+        }
+        let mut cached = cache::CachingGitHub::new(github_cli, settings.cache_ttl);
+        if let Some(dir) = &settings.cache_dir {
+            cached = cached.with_dir(dir.to_owned());
+        }
+        Ok(Box::new(cached))
+    }
+}
+
+/// An issue in a GitHub repository
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GhIssue {
+    pub number: u64,
+    pub url: String,
+    pub title: String,
+    pub body: String,
+}
+
+/// A workflow run in a GitHub repository as listed by `gh run list`
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GhRun {
+    pub database_id: u64,
+    pub display_title: String,
+    pub workflow_name: String,
+    pub head_branch: String,
+    /// e.g. `failure` or `success`, empty while the run is in progress
+    pub conclusion: String,
+    /// ISO 8601 timestamp e.g. `2024-02-11T00:03:19Z`
+    pub created_at: String,
+    pub url: String,
+    /// The commit the run was triggered for
+    #[serde(default)]
+    pub head_sha: String,
+    /// The number of the latest attempt of the run, runs are re-run in a new attempt
+    #[serde(default = "first_attempt")]
+    pub attempt: u64,
+}
+
+fn first_attempt() -> u64 {
+    1
+}
+
+/// A job of a workflow run attempt as listed by `gh run view --json jobs`
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GhJob {
+    pub database_id: u64,
+    pub name: String,
+    /// e.g. `failure` or `success`, empty while the job is in progress
+    pub conclusion: String,
+    /// ISO 8601 timestamp, empty if unknown
+    #[serde(default)]
+    pub started_at: String,
+    /// ISO 8601 timestamp, empty if unknown
+    #[serde(default)]
+    pub completed_at: String,
+    #[serde(default)]
+    pub steps: Vec<GhStep>,
+}
+
+impl GhJob {
+    /// The first failed step of the job
+    pub fn failed_step(&self) -> Option<&GhStep> {
+        self.steps.iter().find(|step| step.conclusion == "failure")
+    }
+
+    /// Seconds from the start of the job until it failed: the end of the failed step, or the end of
+    /// the job if the steps are unknown
+    ///
+    /// # Example
+    /// ```
+    /// # use gh_workflow_parser::gh::{GhJob, GhStep};
+    /// let mut job = GhJob {
+    ///     conclusion: "failure".to_string(),
+    ///     started_at: "2024-02-11T00:03:36Z".to_string(),
+    ///     completed_at: "2024-02-11T00:09:17Z".to_string(),
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(job.time_to_failure(), Some(341));
+    /// job.steps.push(GhStep {
+    ///     name: "Build".to_string(),
+    ///     conclusion: "failure".to_string(),
+    ///     completed_at: "2024-02-11T00:08:56Z".to_string(),
+    ///     ..Default::default()
+    /// });
+    /// assert_eq!(job.time_to_failure(), Some(320));
+    /// ```
+    pub fn time_to_failure(&self) -> Option<i64> {
+        if self.conclusion != "failure" {
+            return None;
+        }
+        let failed_at = self
+            .failed_step()
+            .map_or(&self.completed_at, |step| &step.completed_at);
+        let failed_at = crate::util::unix_seconds_from_iso(failed_at)?;
+        let started_at = crate::util::unix_seconds_from_iso(&self.started_at)?;
+        Some(failed_at - started_at)
+    }
+}
+
+/// A step of a job as listed by `gh run view --json jobs`
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GhStep {
+    pub name: String,
+    pub number: u64,
+    /// e.g. `failure`, `success` or `skipped`
+    pub conclusion: String,
+    /// ISO 8601 timestamp, empty if unknown
+    #[serde(default)]
+    pub started_at: String,
+    /// ISO 8601 timestamp, empty if unknown
+    #[serde(default)]
+    pub completed_at: String,
+}
+
+/// Which workflow runs to list, unset fields match any run
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RunFilter {
+    /// Workflow name or file name e.g. `build.yml`
+    pub workflow: Option<String>,
+    pub branch: Option<String>,
+    /// e.g. `failure`
+    pub status: Option<String>,
+    /// ISO 8601 date of the earliest run to list (inclusive)
+    pub created_since: Option<String>,
+    /// ISO 8601 date of the latest run to list (inclusive)
+    pub created_until: Option<String>,
+    /// Maximum number of runs
+    pub limit: usize,
+}
+
+impl RunFilter {
+    /// The date range in GitHub search syntax e.g. `2024-02-01..2024-02-12` or `>=2024-02-01`
+    ///
+    /// # Example
+    /// ```
+    /// # use gh_workflow_parser::gh::RunFilter;
+    /// let mut filter = RunFilter {
+    ///     created_since: Some("2024-02-01".to_string()),
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(filter.created_range().unwrap(), ">=2024-02-01");
+    /// filter.created_until = Some("2024-02-12".to_string());
+    /// assert_eq!(filter.created_range().unwrap(), "2024-02-01..2024-02-12");
+    /// ```
+    pub fn created_range(&self) -> Option<String> {
+        match (&self.created_since, &self.created_until) {
+            (Some(since), Some(until)) => Some(format!("{since}..{until}")),
+            (Some(since), None) => Some(format!(">={since}")),
+            (None, Some(until)) => Some(format!("<={until}")),
+            (None, None) => None,
+        }
+    }
+
+    /// Whether a run matches the filter, the limit is not applied
+    pub fn matches(&self, run: &GhRun) -> bool {
+        let created = run.created_at.get(..10).unwrap_or(&run.created_at);
+        self.workflow
+            .as_ref()
+            .is_none_or(|workflow| &run.workflow_name == workflow)
+            && self
+                .branch
+                .as_ref()
+                .is_none_or(|branch| &run.head_branch == branch)
+            && self
+                .status
+                .as_ref()
+                .is_none_or(|status| &run.conclusion == status)
+            && self
+                .created_since
+                .as_deref()
+                .is_none_or(|since| created >= since)
+            && self
+                .created_until
+                .as_deref()
+                .is_none_or(|until| created <= until)
+    }
+}
+
+/// A GitHub Project (v2) identified by its owner (user or organization) and number, e.g.
+/// `luftkode/3` for <https://github.com/orgs/luftkode/projects/3>
+///
+/// # Example
+/// ```
+/// # use gh_workflow_parser::gh::ProjectRef;
+/// let project: ProjectRef = "luftkode/3".parse().unwrap();
+/// assert_eq!(project.owner, "luftkode");
+/// assert_eq!(project.number, 3);
+/// assert_eq!(project.to_string(), "luftkode/3");
+/// assert!("luftkode".parse::<ProjectRef>().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectRef {
+    pub owner: String,
+    pub number: u64,
+}
+
+impl FromStr for ProjectRef {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (owner, number) = s
+            .rsplit_once('/')
+            .ok_or_else(|| format!("Expected <owner>/<number>, got: {s}"))?;
+        let number = number
+            .parse()
+            .map_err(|e| format!("Invalid project number in {s}: {e}"))?;
+        if owner.is_empty() {
+            return Err(format!("Missing project owner in {s}"));
+        }
+        Ok(Self {
+            owner: owner.to_string(),
+            number,
+        })
     }
 }
 
+impl Display for ProjectRef {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.owner, self.number)
+    }
+}
+
+/// A job log that is read line by line
+pub type LogReader = Box<dyn std::io::BufRead + Send>;
+
 /// Trait describing the methods that the GitHub CLI should implement
-pub trait GitHub {
+pub trait GitHub: Send + Sync {
     /// Get the summary of a run in a GitHub repository, if `repo` is `None` the default repository is used
     /// Returns the summary as a [String]
     fn run_summary(&self, repo: Option<&str>, run_id: &str) -> Result<String, Box<dyn Error>>;
 
     /// Get the log of a failed job in a GitHub repository, if `repo` is `None` the default repository is used
-    /// Returns the log as a [String]
-    fn failed_job_log(&self, repo: Option<&str>, job_id: &str) -> Result<String, Box<dyn Error>>;
+    /// Returns a [LogReader] streaming the log, so large logs aren't loaded into memory
+    fn failed_job_log(&self, repo: Option<&str>, job_id: &str)
+        -> Result<LogReader, Box<dyn Error>>;
+
+    /// List the workflow runs matching the filter, newest first, if `repo` is `None` the default
+    /// repository is used
+    fn list_runs(
+        &self,
+        repo: Option<&str>,
+        filter: &RunFilter,
+    ) -> Result<Vec<GhRun>, Box<dyn Error>>;
+
+    /// List the jobs of an attempt of a workflow run (attempts start at 1), if `repo` is `None` the
+    /// default repository is used
+    fn run_jobs(
+        &self,
+        repo: Option<&str>,
+        run_id: u64,
+        attempt: u64,
+    ) -> Result<Vec<GhJob>, Box<dyn Error>>;
 
     /// Create an issue in a GitHub repository, if `repo` is `None` the default repository is used
+    /// The labels must exist in the repository, `assignees` are GitHub user names (without `@`)
+    /// Returns the URL of the created issue
     fn create_issue(
         &self,
         repo: Option<&str>,
         title: &str,
         body: &str,
         labels: &[String],
-    ) -> Result<(), Box<dyn Error>>;
+        assignees: &[String],
+    ) -> Result<String, Box<dyn Error>>;
+
+    /// Get the open issues with a specific label in a GitHub repository, if `repo` is `None` the default repository is used
+    /// Returns [`Vec<GhIssue>`](Vec) of issues
+    fn issues_open_with_label(
+        &self,
+        repo: Option<&str>,
+        label: &str,
+    ) -> Result<Vec<GhIssue>, Box<dyn Error>>;
+
+    /// Get the issues with a specific label that were closed on or after `closed_since` (an ISO 8601
+    /// date e.g. `2024-02-11`) in a GitHub repository, if `repo` is `None` the default repository is used
+    /// Returns [`Vec<GhIssue>`](Vec) of issues
+    fn issues_closed_with_label(
+        &self,
+        repo: Option<&str>,
+        label: &str,
+        closed_since: &str,
+    ) -> Result<Vec<GhIssue>, Box<dyn Error>>;
+
+    /// Reopen a closed issue in a GitHub repository, if `repo` is `None` the default repository is used
+    fn reopen_issue(&self, repo: Option<&str>, number: u64) -> Result<(), Box<dyn Error>>;
 
     /// Get the bodies of open issues with a specific label in a GitHub repository, if `repo` is `None` the default repository is used
     /// Returns [`Vec<String>`](Vec) of issue bodies
@@ -60,7 +407,13 @@ pub trait GitHub {
         &self,
         repo: Option<&str>,
         label: &str,
-    ) -> Result<Vec<String>, Box<dyn Error>>;
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        Ok(self
+            .issues_open_with_label(repo, label)?
+            .into_iter()
+            .map(|issue| issue.body)
+            .collect())
+    }
 
     /// Get all labels in a GitHub repository, if `repo` is `None` the default repository is used
     /// Returns [`Vec<String>`](Vec) of GitHub labels
@@ -78,6 +431,55 @@ pub trait GitHub {
         force: bool,
     ) -> Result<(), Box<dyn Error>>;
 
+    /// Add a comment to an issue in a GitHub repository, if `repo` is `None` the default repository is used
+    fn add_issue_comment(
+        &self,
+        repo: Option<&str>,
+        number: u64,
+        body: &str,
+    ) -> Result<(), Box<dyn Error>>;
+
+    /// Get the comments on an issue in a GitHub repository, oldest first, if `repo` is `None` the
+    /// default repository is used
+    /// Returns [`Vec<String>`](Vec) of comment bodies
+    fn issue_comments(
+        &self,
+        repo: Option<&str>,
+        number: u64,
+    ) -> Result<Vec<String>, Box<dyn Error>>;
+
+    /// Set the milestone (by title) of an issue in a GitHub repository, if `repo` is `None` the
+    /// default repository is used
+    fn set_issue_milestone(
+        &self,
+        repo: Option<&str>,
+        number: u64,
+        milestone: &str,
+    ) -> Result<(), Box<dyn Error>>;
+
+    /// Add an issue (by URL) to a GitHub Project (v2)
+    /// Returns the ID of the project item
+    fn add_issue_to_project(
+        &self,
+        project: &ProjectRef,
+        issue_url: &str,
+    ) -> Result<String, Box<dyn Error>>;
+
+    /// Set a field (by name) of a project item, the value is the name of the option for single
+    /// select fields and the text for text fields
+    fn set_project_item_field(
+        &self,
+        project: &ProjectRef,
+        item_id: &str,
+        field: &str,
+        value: &str,
+    ) -> Result<(), Box<dyn Error>>;
+
+    /// Get the contents of a file on the default branch of a GitHub repository, if `repo` is `None`
+    /// the default repository is used
+    /// Returns `None` if the file does not exist
+    fn repo_file(&self, repo: Option<&str>, path: &str) -> Result<Option<String>, Box<dyn Error>>;
+
     /// Get the default repository for the GitHub CLI
     fn default_repo(&self) -> &str;
 }
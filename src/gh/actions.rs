@@ -0,0 +1,243 @@
+//! Output for when running inside a GitHub Actions workflow
+//!
+//! - A markdown job summary is appended to the file named by `GITHUB_STEP_SUMMARY`
+//! - `::error` workflow commands are printed to stdout to annotate the run
+//! - Step outputs are appended to the file named by `GITHUB_OUTPUT`
+//!
+//! See <https://docs.github.com/en/actions/using-workflows/workflow-commands-for-github-actions>
+use std::{error::Error, fmt::Write as _, io::Write, path::Path};
+
+use crate::issue::{Issue, IssueOutcome};
+
+/// Environment variable with the path of the job summary file
+pub const GITHUB_STEP_SUMMARY_ENV: &str = "GITHUB_STEP_SUMMARY";
+/// Environment variable with the path of the step outputs file
+pub const GITHUB_OUTPUT_ENV: &str = "GITHUB_OUTPUT";
+
+/// Write the job summary and annotations for an issue of an analyzed run
+///
+/// The summary is skipped with a warning if `GITHUB_STEP_SUMMARY` is not set, e.g. when not running
+/// in GitHub Actions.
+pub fn report(issue: &Issue, outcome: &IssueOutcome) -> Result<(), Box<dyn Error>> {
+    match std::env::var_os(GITHUB_STEP_SUMMARY_ENV) {
+        Some(path) => append_to_file(Path::new(&path), &step_summary(issue, outcome))?,
+        None => log::warn!("{GITHUB_STEP_SUMMARY_ENV} is not set, skipping the job summary"),
+    }
+
+    for annotation in error_annotations(issue) {
+        crate::macros::pipe_println!("{annotation}")?;
+    }
+    Ok(())
+}
+
+/// Write the step outputs for all issues of an analyzed run
+///
+/// Step outputs are set once per step, so they are written once for all issues of the run. They
+/// are skipped with a warning if `GITHUB_OUTPUT` is not set.
+pub fn report_outputs(issues: &[(Issue, IssueOutcome)]) -> Result<(), Box<dyn Error>> {
+    match std::env::var_os(GITHUB_OUTPUT_ENV) {
+        Some(path) => append_to_file(Path::new(&path), &step_outputs(issues))?,
+        None => log::warn!("{GITHUB_OUTPUT_ENV} is not set, skipping the step outputs"),
+    }
+    Ok(())
+}
+
+/// Markdown job summary of the failure, reusing the rendering of the failed jobs in the issue body
+pub fn step_summary(issue: &Issue, outcome: &IssueOutcome) -> String {
+    let mut summary = format!(
+        "## {title}\n\n**Run ID**: {run_id} [LINK TO RUN]({run_link})\n",
+        title = issue.title(),
+        run_id = issue.run_id(),
+        run_link = issue.run_link(),
+    );
+    match outcome {
+        IssueOutcome::Created(url) => {
+            let _ = writeln!(summary, "\n**Created issue:** {url}");
+        },
+        IssueOutcome::DuplicateOf(url) => {
+            let _ = writeln!(summary, "\n**Duplicate of:** {url}");
+        },
+        IssueOutcome::Reopened(url) => {
+            let _ = writeln!(summary, "\n**Reopened regressed issue:** {url}");
+        },
+        IssueOutcome::Suppressed => {
+            let _ = writeln!(summary, "\n**Flaky failure, no issue created**");
+        },
+        IssueOutcome::NotCreated => (),
+    }
+    for job in issue.failed_jobs() {
+        let _ = writeln!(summary, "{job}");
+    }
+    summary
+}
+
+/// `::error` workflow commands for each failed job
+pub fn error_annotations(issue: &Issue) -> Vec<String> {
+    issue
+        .failed_jobs()
+        .iter()
+        .map(|job| {
+            let title = match job.failure_label() {
+                Some(label) => format!("{} failed at {} ({label})", job.name(), job.failed_step()),
+                None => format!("{} failed at {}", job.name(), job.failed_step()),
+            };
+            error_annotation(&title, job.error_message().summary().trim())
+        })
+        .collect()
+}
+
+/// Format an `::error` workflow command with escaped title and message
+///
+/// # Example
+/// ```
+/// # use gh_workflow_parser::gh::actions::error_annotation;
+/// assert_eq!(
+///     error_annotation("Build: do_fetch", "line 1\nline 2"),
+///     "::error title=Build%3A do_fetch::line 1%0Aline 2"
+/// );
+/// ```
+pub fn error_annotation(title: &str, message: &str) -> String {
+    format!(
+        "::error title={}::{}",
+        escape_property(title),
+        escape_data(message)
+    )
+}
+
+/// Step outputs in the `GITHUB_OUTPUT` file format
+///
+/// Sets `fingerprint`, and `issue-url` or `duplicate-of` depending on the outcomes. `reopened` is
+/// set to `true` if an `issue-url` is a reopened issue. With several issues (one per failed job)
+/// the values are separated by spaces.
+pub fn step_outputs(issues: &[(Issue, IssueOutcome)]) -> String {
+    let mut fingerprints = vec![];
+    let mut issue_urls = vec![];
+    let mut duplicates = vec![];
+    let mut reopened = false;
+    for (issue, outcome) in issues {
+        fingerprints.push(issue.fingerprint());
+        match outcome {
+            IssueOutcome::Created(url) => issue_urls.push(url.as_str()),
+            IssueOutcome::DuplicateOf(url) => duplicates.push(url.as_str()),
+            IssueOutcome::Reopened(url) => {
+                issue_urls.push(url.as_str());
+                reopened = true;
+            },
+            IssueOutcome::NotCreated | IssueOutcome::Suppressed => (),
+        }
+    }
+    let mut outputs = format!("fingerprint={}\n", fingerprints.join(" "));
+    if !issue_urls.is_empty() {
+        let _ = writeln!(outputs, "issue-url={}", issue_urls.join(" "));
+    }
+    if !duplicates.is_empty() {
+        let _ = writeln!(outputs, "duplicate-of={}", duplicates.join(" "));
+    }
+    if reopened {
+        let _ = writeln!(outputs, "reopened=true");
+    }
+    outputs
+}
+
+fn escape_data(s: &str) -> String {
+    s.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn escape_property(s: &str) -> String {
+    escape_data(s).replace(':', "%3A").replace(',', "%2C")
+}
+
+fn append_to_file(path: &Path, contents: &str) -> Result<(), Box<dyn Error>> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Failed to open {path:?}: {e}"))?;
+    file.write_all(contents.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{err_msg_parse::ErrorMessageSummary, issue::FailedJob};
+    use pretty_assertions::assert_eq;
+
+    fn example_issue() -> Issue {
+        Issue::new(
+            "7858139663".to_string(),
+            "https://github.com/luftkode/distro-template/actions/runs/7858139663".to_string(),
+            "Use template and build image".to_string(),
+            "master".to_string(),
+            vec![FailedJob::new(
+                "Test template xilinx".to_string(),
+                "21442749267".to_string(),
+                "https://github.com/luftkode/distro-template/actions/runs/7858139663/job/21442749267".to_string(),
+                "📦 Build yocto image".to_string(),
+                ErrorMessageSummary::Other("ERROR: 100% broken, really\n".to_string()),
+            )],
+            "bug".to_string(),
+        )
+    }
+
+    #[test]
+    fn test_error_annotations() {
+        let annotations = error_annotations(&example_issue());
+        assert_eq!(
+            annotations,
+            ["::error title=Test template xilinx failed at 📦 Build yocto image::ERROR: 100%25 broken, really"]
+        );
+    }
+
+    #[test]
+    fn test_step_outputs() {
+        let issue = example_issue();
+        let fingerprint = issue.fingerprint().to_string();
+        let outputs = step_outputs(&[(
+            issue,
+            IssueOutcome::DuplicateOf("https://github.com/foo/bar/issues/3".to_string()),
+        )]);
+        assert_eq!(
+            outputs,
+            format!(
+                "fingerprint={fingerprint}\nduplicate-of=https://github.com/foo/bar/issues/3\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_step_outputs_several_issues() {
+        let fingerprint = example_issue().fingerprint().to_string();
+        let outputs = step_outputs(&[
+            (
+                example_issue(),
+                IssueOutcome::Created("https://github.com/foo/bar/issues/4".to_string()),
+            ),
+            (
+                example_issue(),
+                IssueOutcome::Reopened("https://github.com/foo/bar/issues/1".to_string()),
+            ),
+            (example_issue(), IssueOutcome::NotCreated),
+        ]);
+        assert_eq!(
+            outputs,
+            format!(
+                "fingerprint={fingerprint} {fingerprint} {fingerprint}\nissue-url=https://github.com/foo/bar/issues/4 https://github.com/foo/bar/issues/1\nreopened=true\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_step_summary_contains_job_section() {
+        let issue = example_issue();
+        let summary = step_summary(
+            &issue,
+            &IssueOutcome::Created("https://github.com/foo/bar/issues/4".to_string()),
+        );
+        assert!(summary.starts_with(&format!("## {}\n", issue.title())));
+        assert!(summary.contains("**Created issue:** https://github.com/foo/bar/issues/4"));
+        assert!(summary.contains(&issue.failed_jobs()[0].to_string()));
+    }
+}
@@ -0,0 +1,409 @@
+//! Cache the responses of repeated GitHub queries
+//!
+//! [CachingGitHub] wraps another [GitHub] implementation and keeps the responses of queries that
+//! are repeated for the same repository, e.g. the labels and open issues that are looked up for
+//! every issue created by `triage-runs` and `watch`, until they're older than the TTL. Calls that
+//! change something invalidate the responses they affect, e.g. creating an issue invalidates the
+//! open issues of the repository.
+//!
+//! Responses are kept in memory and, if a directory is set, on disk so later runs can reuse them.
+//! The layout of the cache directory is keyed by the repository like a
+//! [fixture directory](super::fixture):
+//!
+//! ```text
+//! <root>/github.com/<owner>/<repo>/
+//! ├── runs/<run_id>/summary.json
+//! ├── runs/<run_id>/attempts/<attempt>/jobs.json
+//! ├── issues/open/<label>.json
+//! ├── issues/closed/<label>/<closed_since>.json
+//! ├── files/<path>.json            # percent-encoded path
+//! └── labels.json
+//! ```
+//!
+//! Runs are always listed from GitHub so new runs are seen when polling, and job logs are streamed
+//! without being cached.
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+    time::{Duration, SystemTime},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::{
+    fixture::{percent_encode_file_name, Fixtures},
+    GhIssue, GhJob, GhRun, GitHub, LogReader, ProjectRef, RunFilter,
+};
+
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// A cached response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    stored_at: SystemTime,
+    value: serde_json::Value,
+}
+
+/// A [GitHub] implementation caching the responses of the queries of another
+pub struct CachingGitHub {
+    inner: Box<dyn GitHub>,
+    ttl: Duration,
+    dir: Option<PathBuf>,
+    /// Cached responses by their path relative to the cache directory
+    memory: Mutex<BTreeMap<PathBuf, Entry>>,
+}
+
+impl CachingGitHub {
+    /// Cache responses in memory for `ttl`
+    pub fn new(inner: Box<dyn GitHub>, ttl: Duration) -> Self {
+        Self {
+            inner,
+            ttl,
+            dir: None,
+            memory: Mutex::default(),
+        }
+    }
+
+    /// Also keep the responses in a directory so later runs can reuse them
+    pub fn with_dir(mut self, dir: PathBuf) -> Self {
+        self.dir = Some(dir);
+        self
+    }
+
+    /// The path of the responses of a repository relative to the cache directory
+    fn repo_key(&self, repo: Option<&str>) -> PathBuf {
+        Fixtures::new(PathBuf::new()).repo_dir(repo.unwrap_or(self.inner.default_repo()))
+    }
+
+    fn is_fresh(&self, entry: &Entry) -> bool {
+        SystemTime::now()
+            .duration_since(entry.stored_at)
+            .is_ok_and(|age| age < self.ttl)
+    }
+
+    /// Get the cached response, or fetch and cache it
+    fn cached<T: Serialize + DeserializeOwned>(
+        &self,
+        key: PathBuf,
+        fetch: impl FnOnce() -> Result<T, Box<dyn Error>>,
+    ) -> Result<T, Box<dyn Error>> {
+        if let Some(entry) = self.get(&key) {
+            match serde_json::from_value(entry.value) {
+                Ok(value) => {
+                    log::debug!("Using the cached response {key:?}");
+                    return Ok(value);
+                },
+                Err(e) => log::warn!("Ignoring the invalid cached response {key:?}: {e}"),
+            }
+        }
+        let value = fetch()?;
+        self.put(key, serde_json::to_value(&value)?);
+        Ok(value)
+    }
+
+    fn get(&self, key: &Path) -> Option<Entry> {
+        let mut memory = self.memory.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(entry) = memory.get(key).filter(|entry| self.is_fresh(entry)) {
+            return Some(entry.clone());
+        }
+        let path = self.dir.as_ref()?.join(key);
+        let entry: Entry = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|e| log::warn!("Ignoring the invalid cache file {path:?}: {e}"))
+                .ok()?,
+            Err(_) => return None,
+        };
+        if !self.is_fresh(&entry) {
+            return None;
+        }
+        memory.insert(key.to_owned(), entry.clone());
+        Some(entry)
+    }
+
+    fn put(&self, key: PathBuf, value: serde_json::Value) {
+        let entry = Entry {
+            stored_at: SystemTime::now(),
+            value,
+        };
+        if let Some(dir) = &self.dir {
+            let path = dir.join(&key);
+            let written = path
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|()| fs::write(&path, serde_json::to_string(&entry)?));
+            if let Err(e) = written {
+                log::warn!("Failed to write the cache file {path:?}: {e}");
+            }
+        }
+        self.memory
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(key, entry);
+    }
+
+    /// Remove the cached responses at `prefix` e.g. all issues of a repository
+    fn invalidate(&self, prefix: PathBuf) {
+        log::debug!("Invalidating the cached responses {prefix:?}");
+        self.memory
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|key, _| !key.starts_with(&prefix));
+        if let Some(dir) = &self.dir {
+            let path = dir.join(prefix);
+            let removed = if path.is_dir() {
+                fs::remove_dir_all(&path)
+            } else {
+                fs::remove_file(&path)
+            };
+            if let Err(e) = removed.or_else(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => Ok(()),
+                _ => Err(e),
+            }) {
+                log::warn!("Failed to remove the cache file {path:?}: {e}");
+            }
+        }
+    }
+}
+
+impl GitHub for CachingGitHub {
+    fn run_summary(&self, repo: Option<&str>, run_id: &str) -> Result<String, Box<dyn Error>> {
+        let key = self
+            .repo_key(repo)
+            .join("runs")
+            .join(run_id)
+            .join("summary.json");
+        self.cached(key, || self.inner.run_summary(repo, run_id))
+    }
+
+    fn failed_job_log(
+        &self,
+        repo: Option<&str>,
+        job_id: &str,
+    ) -> Result<LogReader, Box<dyn Error>> {
+        self.inner.failed_job_log(repo, job_id)
+    }
+
+    fn list_runs(
+        &self,
+        repo: Option<&str>,
+        filter: &RunFilter,
+    ) -> Result<Vec<GhRun>, Box<dyn Error>> {
+        self.inner.list_runs(repo, filter)
+    }
+
+    fn run_jobs(
+        &self,
+        repo: Option<&str>,
+        run_id: u64,
+        attempt: u64,
+    ) -> Result<Vec<GhJob>, Box<dyn Error>> {
+        let key = self
+            .repo_key(repo)
+            .join("runs")
+            .join(run_id.to_string())
+            .join("attempts")
+            .join(attempt.to_string())
+            .join("jobs.json");
+        self.cached(key, || self.inner.run_jobs(repo, run_id, attempt))
+    }
+
+    fn create_issue(
+        &self,
+        repo: Option<&str>,
+        title: &str,
+        body: &str,
+        labels: &[String],
+        assignees: &[String],
+    ) -> Result<String, Box<dyn Error>> {
+        let url = self
+            .inner
+            .create_issue(repo, title, body, labels, assignees);
+        self.invalidate(self.repo_key(repo).join("issues").join("open"));
+        url
+    }
+
+    fn issues_open_with_label(
+        &self,
+        repo: Option<&str>,
+        label: &str,
+    ) -> Result<Vec<GhIssue>, Box<dyn Error>> {
+        let key = self
+            .repo_key(repo)
+            .join("issues")
+            .join("open")
+            .join(format!("{}.json", percent_encode_file_name(label)));
+        self.cached(key, || self.inner.issues_open_with_label(repo, label))
+    }
+
+    fn issues_closed_with_label(
+        &self,
+        repo: Option<&str>,
+        label: &str,
+        closed_since: &str,
+    ) -> Result<Vec<GhIssue>, Box<dyn Error>> {
+        let key = self
+            .repo_key(repo)
+            .join("issues")
+            .join("closed")
+            .join(percent_encode_file_name(label))
+            .join(format!("{}.json", percent_encode_file_name(closed_since)));
+        self.cached(key, || {
+            self.inner
+                .issues_closed_with_label(repo, label, closed_since)
+        })
+    }
+
+    fn reopen_issue(&self, repo: Option<&str>, number: u64) -> Result<(), Box<dyn Error>> {
+        let reopened = self.inner.reopen_issue(repo, number);
+        // The labels of the issue are unknown, so the open and closed issues of every label are
+        // invalidated
+        self.invalidate(self.repo_key(repo).join("issues"));
+        reopened
+    }
+
+    fn all_labels(&self, repo: Option<&str>) -> Result<Vec<String>, Box<dyn Error>> {
+        let key = self.repo_key(repo).join("labels.json");
+        self.cached(key, || self.inner.all_labels(repo))
+    }
+
+    fn create_label(
+        &self,
+        repo: Option<&str>,
+        name: &str,
+        color: &str,
+        description: &str,
+        force: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        let created = self
+            .inner
+            .create_label(repo, name, color, description, force);
+        self.invalidate(self.repo_key(repo).join("labels.json"));
+        created
+    }
+
+    fn add_issue_comment(
+        &self,
+        repo: Option<&str>,
+        number: u64,
+        body: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        self.inner.add_issue_comment(repo, number, body)
+    }
+
+    fn issue_comments(
+        &self,
+        repo: Option<&str>,
+        number: u64,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        // Comments are read right before commenting, a cached list could be missing a comment
+        self.inner.issue_comments(repo, number)
+    }
+
+    fn set_issue_milestone(
+        &self,
+        repo: Option<&str>,
+        number: u64,
+        milestone: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        self.inner.set_issue_milestone(repo, number, milestone)
+    }
+
+    fn add_issue_to_project(
+        &self,
+        project: &ProjectRef,
+        issue_url: &str,
+    ) -> Result<String, Box<dyn Error>> {
+        self.inner.add_issue_to_project(project, issue_url)
+    }
+
+    fn set_project_item_field(
+        &self,
+        project: &ProjectRef,
+        item_id: &str,
+        field: &str,
+        value: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        self.inner
+            .set_project_item_field(project, item_id, field, value)
+    }
+
+    fn repo_file(&self, repo: Option<&str>, path: &str) -> Result<Option<String>, Box<dyn Error>> {
+        let key = self
+            .repo_key(repo)
+            .join("files")
+            .join(format!("{}.json", percent_encode_file_name(path)));
+        self.cached(key, || self.inner.repo_file(repo, path))
+    }
+
+    fn default_repo(&self) -> &str {
+        self.inner.default_repo()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gh::gh_cli_fake::GitHubCliFake;
+    use pretty_assertions::assert_eq;
+
+    const REPO: &str = "https://github.com/luftkode/distro-template";
+
+    fn caching_fake(ttl: Duration) -> CachingGitHub {
+        CachingGitHub::new(Box::new(GitHubCliFake::new(REPO.to_string())), ttl)
+    }
+
+    #[test]
+    fn test_writes_invalidate_cached_responses() {
+        let github = caching_fake(Duration::from_secs(60));
+        assert!(github.all_labels(None).unwrap().is_empty());
+        assert!(github
+            .issues_open_with_label(None, "bug")
+            .unwrap()
+            .is_empty());
+
+        github
+            .create_label(None, "bug", "FF0000", "A bug", false)
+            .unwrap();
+        assert_eq!(github.all_labels(None).unwrap(), vec!["bug".to_string()]);
+
+        github
+            .create_issue(None, "Title", "Body", &["bug".to_string()], &[])
+            .unwrap();
+        let issues = github.issues_open_with_label(None, "bug").unwrap();
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].title, "Title");
+    }
+
+    #[test]
+    fn test_cached_responses_on_disk() {
+        let dir = temp_dir::TempDir::new().unwrap();
+        let github = caching_fake(Duration::from_secs(60)).with_dir(dir.path().to_owned());
+        github
+            .create_label(None, "bug", "FF0000", "A bug", false)
+            .unwrap();
+        assert_eq!(github.all_labels(None).unwrap(), vec!["bug".to_string()]);
+        assert!(dir
+            .path()
+            .join("github.com/luftkode/distro-template/labels.json")
+            .is_file());
+
+        // A new fake without the label gets the cached labels
+        let github = caching_fake(Duration::from_secs(60)).with_dir(dir.path().to_owned());
+        assert_eq!(github.all_labels(None).unwrap(), vec!["bug".to_string()]);
+
+        // unless they're older than the TTL
+        let github = caching_fake(Duration::ZERO).with_dir(dir.path().to_owned());
+        assert!(github.all_labels(None).unwrap().is_empty());
+
+        github
+            .create_label(None, "flaky", "FF0000", "Flaky", false)
+            .unwrap();
+        assert!(!dir
+            .path()
+            .join("github.com/luftkode/distro-template/labels.json")
+            .exists());
+    }
+}
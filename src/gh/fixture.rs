@@ -0,0 +1,386 @@
+//! Directories of recorded GitHub responses that can be replayed with the fake GitHub CLI
+//!
+//! The layout of a fixture directory is keyed by the repository (without the URL scheme):
+//!
+//! ```text
+//! <root>/github.com/<owner>/<repo>/
+//! ├── runs.json                    # `gh run list`
+//! ├── runs/<run_id>/summary.txt    # `gh run view <run_id>`
+//! ├── runs/<run_id>/attempts/<attempt>/jobs.json  # `gh run view <run_id> --attempt <attempt> --json jobs`
+//! ├── jobs/<job_id>.log            # `gh run view --job <job_id> --log-failed`
+//! ├── issues/<label>.json          # open issues with the label (percent-encoded file name)
+//! ├── issues/closed/<label>.json   # recently closed issues with the label
+//! ├── files/<path>                 # files on the default branch e.g. `files/.github/CODEOWNERS`
+//! └── labels.json                  # names of all labels
+//! ```
+//!
+//! A fixture directory is written by running with `--record <dir>` and replayed with
+//! `--fake-github-cli --fixtures <dir>`.
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, BufReader, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use super::{GhIssue, GhJob, GhRun};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fixtures {
+    root: PathBuf,
+}
+
+impl Fixtures {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The directory of a repository, the URL scheme is stripped from the repository URL
+    pub fn repo_dir(&self, repo: &str) -> PathBuf {
+        let repo = repo
+            .split_once("://")
+            .map_or(repo, |(_scheme, rest)| rest)
+            .trim_matches('/');
+        self.root.join(repo)
+    }
+
+    pub fn runs_path(&self, repo: &str) -> PathBuf {
+        self.repo_dir(repo).join("runs.json")
+    }
+
+    pub fn run_summary_path(&self, repo: &str, run_id: &str) -> PathBuf {
+        self.repo_dir(repo)
+            .join("runs")
+            .join(run_id)
+            .join("summary.txt")
+    }
+
+    pub fn run_jobs_path(&self, repo: &str, run_id: u64, attempt: u64) -> PathBuf {
+        self.repo_dir(repo)
+            .join("runs")
+            .join(run_id.to_string())
+            .join("attempts")
+            .join(attempt.to_string())
+            .join("jobs.json")
+    }
+
+    pub fn job_log_path(&self, repo: &str, job_id: &str) -> PathBuf {
+        self.repo_dir(repo)
+            .join("jobs")
+            .join(format!("{job_id}.log"))
+    }
+
+    pub fn issues_path(&self, repo: &str, label: &str) -> PathBuf {
+        self.repo_dir(repo)
+            .join("issues")
+            .join(format!("{}.json", percent_encode_file_name(label)))
+    }
+
+    pub fn closed_issues_path(&self, repo: &str, label: &str) -> PathBuf {
+        self.repo_dir(repo)
+            .join("issues")
+            .join("closed")
+            .join(format!("{}.json", percent_encode_file_name(label)))
+    }
+
+    pub fn repo_file_path(&self, repo: &str, path: &str) -> PathBuf {
+        self.repo_dir(repo).join("files").join(path)
+    }
+
+    pub fn labels_path(&self, repo: &str) -> PathBuf {
+        self.repo_dir(repo).join("labels.json")
+    }
+
+    /// Read the runs of a repository, a missing fixture means there are no runs
+    pub fn read_runs(&self, repo: &str) -> Result<Vec<GhRun>, Box<dyn Error>> {
+        let path = self.runs_path(repo);
+        if !path.exists() {
+            log::debug!("No runs fixture at {path:?}");
+            return Ok(vec![]);
+        }
+        Ok(serde_json::from_str(&read(&path)?)?)
+    }
+
+    /// Record listed runs, runs that were recorded before are kept
+    pub fn write_runs(&self, repo: &str, runs: &[GhRun]) -> Result<(), Box<dyn Error>> {
+        let mut all_runs = self.read_runs(repo)?;
+        all_runs.retain(|run| !runs.iter().any(|r| r.database_id == run.database_id));
+        all_runs.extend_from_slice(runs);
+        all_runs.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        write(
+            &self.runs_path(repo),
+            &serde_json::to_string_pretty(&all_runs)?,
+        )
+    }
+
+    pub fn read_run_summary(&self, repo: &str, run_id: &str) -> Result<String, Box<dyn Error>> {
+        read(&self.run_summary_path(repo, run_id))
+    }
+
+    pub fn write_run_summary(
+        &self,
+        repo: &str,
+        run_id: &str,
+        summary: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        write(&self.run_summary_path(repo, run_id), summary)
+    }
+
+    /// Read the jobs of a run attempt, a missing fixture means the jobs are unknown
+    pub fn read_run_jobs(
+        &self,
+        repo: &str,
+        run_id: u64,
+        attempt: u64,
+    ) -> Result<Vec<GhJob>, Box<dyn Error>> {
+        let path = self.run_jobs_path(repo, run_id, attempt);
+        if !path.exists() {
+            log::debug!("No jobs fixture at {path:?}");
+            return Ok(vec![]);
+        }
+        Ok(serde_json::from_str(&read(&path)?)?)
+    }
+
+    pub fn write_run_jobs(
+        &self,
+        repo: &str,
+        run_id: u64,
+        attempt: u64,
+        jobs: &[GhJob],
+    ) -> Result<(), Box<dyn Error>> {
+        write(
+            &self.run_jobs_path(repo, run_id, attempt),
+            &serde_json::to_string_pretty(jobs)?,
+        )
+    }
+
+    pub fn open_job_log(
+        &self,
+        repo: &str,
+        job_id: &str,
+    ) -> Result<BufReader<File>, Box<dyn Error>> {
+        let path = self.job_log_path(repo, job_id);
+        let file =
+            File::open(&path).map_err(|e| format!("Failed to read fixture {path:?}: {e}"))?;
+        Ok(BufReader::new(file))
+    }
+
+    /// Record a job log while it's read
+    pub fn record_job_log<R: Read>(
+        &self,
+        repo: &str,
+        job_id: &str,
+        log: R,
+    ) -> Result<Recording<R>, Box<dyn Error>> {
+        let path = self.job_log_path(repo, job_id);
+        log::debug!("Recording fixture {path:?}");
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file =
+            File::create(&path).map_err(|e| format!("Failed to write fixture {path:?}: {e}"))?;
+        Ok(Recording { reader: log, file })
+    }
+
+    /// Read the open issues with a label, a missing fixture means there are no such issues
+    pub fn read_issues(&self, repo: &str, label: &str) -> Result<Vec<GhIssue>, Box<dyn Error>> {
+        read_issues(&self.issues_path(repo, label))
+    }
+
+    pub fn write_issues(
+        &self,
+        repo: &str,
+        label: &str,
+        issues: &[GhIssue],
+    ) -> Result<(), Box<dyn Error>> {
+        write(
+            &self.issues_path(repo, label),
+            &serde_json::to_string_pretty(issues)?,
+        )
+    }
+
+    /// Read the closed issues with a label as they were recorded (regardless of when they were
+    /// closed), a missing fixture means there are no such issues
+    pub fn read_closed_issues(
+        &self,
+        repo: &str,
+        label: &str,
+    ) -> Result<Vec<GhIssue>, Box<dyn Error>> {
+        read_issues(&self.closed_issues_path(repo, label))
+    }
+
+    pub fn write_closed_issues(
+        &self,
+        repo: &str,
+        label: &str,
+        issues: &[GhIssue],
+    ) -> Result<(), Box<dyn Error>> {
+        write(
+            &self.closed_issues_path(repo, label),
+            &serde_json::to_string_pretty(issues)?,
+        )
+    }
+
+    /// Read a file of the repository, a missing fixture means the file doesn't exist
+    pub fn read_repo_file(&self, repo: &str, path: &str) -> Result<Option<String>, Box<dyn Error>> {
+        let fixture_path = self.repo_file_path(repo, path);
+        if !fixture_path.exists() {
+            return Ok(None);
+        }
+        read(&fixture_path).map(Some)
+    }
+
+    pub fn write_repo_file(
+        &self,
+        repo: &str,
+        path: &str,
+        contents: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        write(&self.repo_file_path(repo, path), contents)
+    }
+
+    /// Read the labels of a repository, a missing fixture means there are no labels
+    pub fn read_labels(&self, repo: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let path = self.labels_path(repo);
+        if !path.exists() {
+            log::debug!("No labels fixture at {path:?}");
+            return Ok(vec![]);
+        }
+        Ok(serde_json::from_str(&read(&path)?)?)
+    }
+
+    pub fn write_labels(&self, repo: &str, labels: &[String]) -> Result<(), Box<dyn Error>> {
+        write(
+            &self.labels_path(repo),
+            &serde_json::to_string_pretty(labels)?,
+        )
+    }
+}
+
+/// A reader that writes everything that is read to a fixture
+#[derive(Debug)]
+pub struct Recording<R> {
+    reader: R,
+    file: File,
+}
+
+impl<R: Read> Read for Recording<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.reader.read(buf)?;
+        self.file.write_all(&buf[..len])?;
+        Ok(len)
+    }
+}
+
+fn read_issues(path: &Path) -> Result<Vec<GhIssue>, Box<dyn Error>> {
+    if !path.exists() {
+        log::debug!("No issues fixture at {path:?}");
+        return Ok(vec![]);
+    }
+    Ok(serde_json::from_str(&read(path)?)?)
+}
+
+fn read(path: &Path) -> Result<String, Box<dyn Error>> {
+    std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read fixture {path:?}: {e}").into())
+}
+
+fn write(path: &Path, contents: &str) -> Result<(), Box<dyn Error>> {
+    log::debug!("Recording fixture {path:?}");
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, contents)
+        .map_err(|e| format!("Failed to write fixture {path:?}: {e}").into())
+}
+
+/// Encode a string so it can be used as a file name, bytes other than ASCII alphanumerics, `-`, `_`
+/// and `.` are percent-encoded
+///
+/// # Example
+/// ```
+/// # use gh_workflow_parser::gh::fixture::percent_encode_file_name;
+/// assert_eq!(percent_encode_file_name("CI scheduled build"), "CI%20scheduled%20build");
+/// assert_eq!(percent_encode_file_name("yocto/do_fetch"), "yocto%2Fdo_fetch");
+/// ```
+pub fn percent_encode_file_name(s: &str) -> String {
+    s.bytes()
+        .fold(String::with_capacity(s.len()), |mut out, b| {
+            if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.') {
+                out.push(b as char);
+            } else {
+                out.push_str(&format!("%{b:02X}"));
+            }
+            out
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use temp_dir::TempDir;
+
+    const REPO: &str = "https://github.com/luftkode/distro-template";
+
+    #[test]
+    fn test_fixture_paths() {
+        let fixtures = Fixtures::new(PathBuf::from("/fixtures"));
+        assert_eq!(
+            fixtures.run_summary_path(REPO, "7858139663"),
+            PathBuf::from(
+                "/fixtures/github.com/luftkode/distro-template/runs/7858139663/summary.txt"
+            )
+        );
+        assert_eq!(
+            fixtures.job_log_path(REPO, "21442749267"),
+            PathBuf::from("/fixtures/github.com/luftkode/distro-template/jobs/21442749267.log")
+        );
+        assert_eq!(
+            fixtures.issues_path(REPO, "CI scheduled build"),
+            PathBuf::from(
+                "/fixtures/github.com/luftkode/distro-template/issues/CI%20scheduled%20build.json"
+            )
+        );
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let dir = TempDir::new().unwrap();
+        let fixtures = Fixtures::new(dir.path().to_path_buf());
+        let issues = vec![GhIssue {
+            number: 3,
+            url: format!("{REPO}/issues/3"),
+            title: "Title".to_string(),
+            body: "Body".to_string(),
+        }];
+
+        fixtures.write_run_summary(REPO, "1", "summary").unwrap();
+        let mut recording = fixtures
+            .record_job_log(REPO, "2", "log".as_bytes())
+            .unwrap();
+        io::copy(&mut recording, &mut io::sink()).unwrap();
+        fixtures.write_issues(REPO, "bug", &issues).unwrap();
+        fixtures
+            .write_labels(REPO, &["bug".to_string(), "do_fetch".to_string()])
+            .unwrap();
+
+        assert_eq!(fixtures.read_run_summary(REPO, "1").unwrap(), "summary");
+        let mut log = String::new();
+        fixtures
+            .open_job_log(REPO, "2")
+            .unwrap()
+            .read_to_string(&mut log)
+            .unwrap();
+        assert_eq!(log, "log");
+        assert_eq!(fixtures.read_issues(REPO, "bug").unwrap(), issues);
+        assert_eq!(fixtures.read_labels(REPO).unwrap(), ["bug", "do_fetch"]);
+        // Missing fixtures for lists are empty, missing runs are an error
+        assert!(fixtures.read_issues(REPO, "other").unwrap().is_empty());
+        assert!(fixtures.read_run_summary(REPO, "404").is_err());
+    }
+}
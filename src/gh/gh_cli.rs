@@ -1,13 +1,36 @@
-use super::{util, GitHub};
+use std::io::BufReader;
+
+use super::{
+    fixture::Fixtures, util, GhIssue, GhJob, GhRun, GitHub, LogReader, ProjectRef, RunFilter,
+};
 
 #[derive(Debug, Default, Clone)]
 pub struct GitHubCli {
     repo: String,
+    recorder: Option<Fixtures>,
 }
 
 impl GitHubCli {
     pub fn new(repo: String) -> Self {
-        Self { repo }
+        Self {
+            repo,
+            recorder: None,
+        }
+    }
+
+    /// Record every response from GitHub to a [fixture directory](super::fixture) that can be
+    /// replayed with [GitHubCliFake](super::gh_cli_fake::GitHubCliFake)
+    pub fn with_recorder(repo: String, recorder: Fixtures) -> Self {
+        Self {
+            repo,
+            recorder: Some(recorder),
+        }
+    }
+
+    /// The host of the default repository, `gh` uses it for requests that aren't for a repository
+    /// e.g. of GitHub Projects
+    fn host(&self) -> Option<&str> {
+        util::repo_host_and_slug(&self.repo).map(|(host, _slug)| host)
     }
 }
 
@@ -18,16 +41,52 @@ impl GitHub for GitHubCli {
         run_id: &str,
     ) -> Result<String, Box<dyn std::error::Error>> {
         let target_repo = repo.unwrap_or(&self.repo);
-        util::run_summary(target_repo, run_id)
+        let summary = util::run_summary(target_repo, run_id)?;
+        if let Some(recorder) = &self.recorder {
+            recorder.write_run_summary(target_repo, run_id, &summary)?;
+        }
+        Ok(summary)
     }
 
     fn failed_job_log(
         &self,
         repo: Option<&str>,
         job_id: &str,
-    ) -> Result<String, Box<dyn std::error::Error>> {
+    ) -> Result<LogReader, Box<dyn std::error::Error>> {
+        let target_repo = repo.unwrap_or(&self.repo);
+        let log = util::failed_job_log(target_repo, job_id)?;
+        if let Some(recorder) = &self.recorder {
+            let recording = recorder.record_job_log(target_repo, job_id, log)?;
+            return Ok(Box::new(BufReader::new(recording)));
+        }
+        Ok(Box::new(BufReader::new(log)))
+    }
+
+    fn list_runs(
+        &self,
+        repo: Option<&str>,
+        filter: &RunFilter,
+    ) -> Result<Vec<GhRun>, Box<dyn std::error::Error>> {
+        let target_repo = repo.unwrap_or(&self.repo);
+        let runs = util::list_runs(target_repo, filter)?;
+        if let Some(recorder) = &self.recorder {
+            recorder.write_runs(target_repo, &runs)?;
+        }
+        Ok(runs)
+    }
+
+    fn run_jobs(
+        &self,
+        repo: Option<&str>,
+        run_id: u64,
+        attempt: u64,
+    ) -> Result<Vec<GhJob>, Box<dyn std::error::Error>> {
         let target_repo = repo.unwrap_or(&self.repo);
-        util::failed_job_log(target_repo, job_id)
+        let jobs = util::run_jobs(target_repo, run_id, attempt)?;
+        if let Some(recorder) = &self.recorder {
+            recorder.write_run_jobs(target_repo, run_id, attempt, &jobs)?;
+        }
+        Ok(jobs)
     }
 
     fn create_issue(
@@ -36,23 +95,55 @@ impl GitHub for GitHubCli {
         title: &str,
         body: &str,
         labels: &[String],
-    ) -> Result<(), Box<dyn std::error::Error>> {
+        assignees: &[String],
+    ) -> Result<String, Box<dyn std::error::Error>> {
         let target_repo = repo.unwrap_or(&self.repo);
-        util::create_issue(target_repo, title, body, labels)
+        util::create_issue(target_repo, title, body, labels, assignees)
     }
 
-    fn issue_bodies_open_with_label(
+    fn issues_open_with_label(
         &self,
         repo: Option<&str>,
         label: &str,
-    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    ) -> Result<Vec<GhIssue>, Box<dyn std::error::Error>> {
+        let target_repo = repo.unwrap_or(&self.repo);
+        let issues = util::issues_open_with_label(target_repo, label)?;
+        if let Some(recorder) = &self.recorder {
+            recorder.write_issues(target_repo, label, &issues)?;
+        }
+        Ok(issues)
+    }
+
+    fn issues_closed_with_label(
+        &self,
+        repo: Option<&str>,
+        label: &str,
+        closed_since: &str,
+    ) -> Result<Vec<GhIssue>, Box<dyn std::error::Error>> {
+        let target_repo = repo.unwrap_or(&self.repo);
+        let issues = util::issues_closed_with_label(target_repo, label, closed_since)?;
+        if let Some(recorder) = &self.recorder {
+            recorder.write_closed_issues(target_repo, label, &issues)?;
+        }
+        Ok(issues)
+    }
+
+    fn reopen_issue(
+        &self,
+        repo: Option<&str>,
+        number: u64,
+    ) -> Result<(), Box<dyn std::error::Error>> {
         let target_repo = repo.unwrap_or(&self.repo);
-        util::issue_bodies_open_with_label(target_repo, label)
+        util::reopen_issue(target_repo, number)
     }
 
     fn all_labels(&self, repo: Option<&str>) -> Result<Vec<String>, Box<dyn std::error::Error>> {
         let target_repo = repo.unwrap_or(&self.repo);
-        util::all_labels(target_repo)
+        let labels = util::all_labels(target_repo)?;
+        if let Some(recorder) = &self.recorder {
+            recorder.write_labels(target_repo, &labels)?;
+        }
+        Ok(labels)
     }
 
     fn create_label(
@@ -67,6 +158,66 @@ impl GitHub for GitHubCli {
         util::create_label(target_repo, name, color, description, force)
     }
 
+    fn add_issue_comment(
+        &self,
+        repo: Option<&str>,
+        number: u64,
+        body: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let target_repo = repo.unwrap_or(&self.repo);
+        util::add_issue_comment(target_repo, number, body)
+    }
+
+    fn issue_comments(
+        &self,
+        repo: Option<&str>,
+        number: u64,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let target_repo = repo.unwrap_or(&self.repo);
+        util::issue_comments(target_repo, number)
+    }
+
+    fn set_issue_milestone(
+        &self,
+        repo: Option<&str>,
+        number: u64,
+        milestone: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let target_repo = repo.unwrap_or(&self.repo);
+        util::set_issue_milestone(target_repo, number, milestone)
+    }
+
+    fn add_issue_to_project(
+        &self,
+        project: &ProjectRef,
+        issue_url: &str,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        util::add_issue_to_project(self.host(), project, issue_url)
+    }
+
+    fn set_project_item_field(
+        &self,
+        project: &ProjectRef,
+        item_id: &str,
+        field: &str,
+        value: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        util::set_project_item_field(self.host(), project, item_id, field, value)
+    }
+
+    fn repo_file(
+        &self,
+        repo: Option<&str>,
+        path: &str,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let target_repo = repo.unwrap_or(&self.repo);
+        let contents = util::repo_file(target_repo, path)?;
+        if let (Some(recorder), Some(contents)) = (&self.recorder, &contents) {
+            recorder.write_repo_file(target_repo, path, contents)?;
+        }
+        Ok(contents)
+    }
+
     fn default_repo(&self) -> &str {
         &self.repo
     }
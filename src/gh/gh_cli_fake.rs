@@ -1,13 +1,75 @@
-use super::GitHub;
+use std::{
+    error::Error,
+    path::PathBuf,
+    sync::{Mutex, MutexGuard},
+};
 
-#[derive(Debug, Default, Clone)]
+use super::{fixture::Fixtures, GhIssue, GhJob, GhRun, GitHub, LogReader, ProjectRef, RunFilter};
+use store::{FakeIssue, FakeProjectItem, FakeStore, IssueState};
+
+pub mod store;
+
+/// Fake GitHub CLI that doesn't interact with GitHub
+///
+/// Run summaries and job logs are replayed from a [fixture directory](super::fixture) if one is
+/// set, otherwise a hardcoded response from an actual run is returned.
+///
+/// Created issues, labels and comments are kept in a [FakeStore] that is saved to the state file
+/// after every change if one is set.
+#[derive(Debug, Default)]
 pub struct GitHubCliFake {
     repo: String,
+    fixtures: Option<Fixtures>,
+    store: Mutex<FakeStore>,
+    state_file: Option<PathBuf>,
 }
 
 impl GitHubCliFake {
     pub fn new(repo: String) -> Self {
-        Self { repo }
+        Self {
+            repo,
+            ..Default::default()
+        }
+    }
+
+    /// Replay responses from the fixture directory
+    pub fn with_fixtures(repo: String, fixtures: Fixtures) -> Self {
+        Self {
+            repo,
+            fixtures: Some(fixtures),
+            ..Default::default()
+        }
+    }
+
+    /// Load the state from the file (if it exists) and save every change to it
+    pub fn with_state_file(mut self, path: PathBuf) -> Result<Self, Box<dyn Error>> {
+        self.store = Mutex::new(FakeStore::load(&path)?);
+        self.state_file = Some(path);
+        Ok(self)
+    }
+
+    /// All issues created through the fake
+    pub fn issues(&self) -> Vec<FakeIssue> {
+        self.store().issues.clone()
+    }
+
+    /// All project items added through the fake
+    pub fn project_items(&self) -> Vec<FakeProjectItem> {
+        self.store().project_items.clone()
+    }
+
+    fn store(&self) -> MutexGuard<'_, FakeStore> {
+        self.store.lock().expect("Fake GitHub store lock poisoned")
+    }
+
+    /// Apply a change to the store and persist it
+    fn update<T>(&self, f: impl FnOnce(&mut FakeStore) -> T) -> Result<T, Box<dyn Error>> {
+        let mut store = self.store();
+        let res = f(&mut store);
+        if let Some(path) = &self.state_file {
+            store.save(path)?;
+        }
+        Ok(res)
     }
 }
 
@@ -19,6 +81,9 @@ impl GitHub for GitHubCliFake {
     ) -> Result<String, Box<dyn std::error::Error>> {
         let target_repo = repo.unwrap_or(&self.repo);
         log::info!("Fake run summary for repo={target_repo} and run_id={run_id}");
+        if let Some(fixtures) = &self.fixtures {
+            return fixtures.read_run_summary(target_repo, run_id);
+        }
 
         // Return a fake run summary from an actual run output
         const TEST_OUTPUT_VIEW_RUN: &str = r#"
@@ -61,15 +126,61 @@ impl GitHub for GitHubCliFake {
         &self,
         repo: Option<&str>,
         job_id: &str,
-    ) -> Result<String, Box<dyn std::error::Error>> {
+    ) -> Result<LogReader, Box<dyn std::error::Error>> {
         let target_repo = repo.unwrap_or(&self.repo);
         log::info!("Fake failed job log for repo={target_repo} and job_id={job_id}");
+        if let Some(fixtures) = &self.fixtures {
+            return Ok(Box::new(fixtures.open_job_log(target_repo, job_id)?));
+        }
         // Return a fake log from an actual run output
         const TEST_LOG_STRING: &str = r#"Test template xilinx	📦 Build yocto image	2024-02-10T00:03:45.5797561Z ##[group]Run just --yes build-ci-image
 Test template xilinx	📦 Build yocto image	2024-02-10T00:03:45.5799911Z [36;1mjust --yes build-ci-image[0m
 Test template xilinx	📦 Build yocto image	2024-02-10T00:03:45.5843410Z shell: /usr/bin/bash -e {0}
 "#;
-        Ok(TEST_LOG_STRING.to_string())
+        Ok(Box::new(TEST_LOG_STRING.as_bytes()))
+    }
+
+    fn list_runs(
+        &self,
+        repo: Option<&str>,
+        filter: &RunFilter,
+    ) -> Result<Vec<GhRun>, Box<dyn std::error::Error>> {
+        let target_repo = repo.unwrap_or(&self.repo);
+        log::info!("Fake list_runs for repo={target_repo} and filter={filter:?}");
+        let runs = match &self.fixtures {
+            Some(fixtures) => fixtures.read_runs(target_repo)?,
+            // The run of the hardcoded run summary
+            None => vec![GhRun {
+                database_id: 7858139663,
+                display_title: "Use template and build image".to_string(),
+                workflow_name: "Use template and build image".to_string(),
+                head_branch: "master".to_string(),
+                conclusion: "failure".to_string(),
+                created_at: "2024-02-11T00:03:19Z".to_string(),
+                url: format!("{target_repo}/actions/runs/7858139663"),
+                head_sha: String::new(),
+                attempt: 1,
+            }],
+        };
+        Ok(runs
+            .into_iter()
+            .filter(|run| filter.matches(run))
+            .take(filter.limit)
+            .collect())
+    }
+
+    fn run_jobs(
+        &self,
+        repo: Option<&str>,
+        run_id: u64,
+        attempt: u64,
+    ) -> Result<Vec<GhJob>, Box<dyn std::error::Error>> {
+        let target_repo = repo.unwrap_or(&self.repo);
+        log::info!("Fake run_jobs for repo={target_repo}, run_id={run_id} and attempt={attempt}");
+        match &self.fixtures {
+            Some(fixtures) => fixtures.read_run_jobs(target_repo, run_id, attempt),
+            None => Ok(vec![]),
+        }
     }
 
     fn create_issue(
@@ -78,29 +189,93 @@ Test template xilinx	📦 Build yocto image	2024-02-10T00:03:45.5843410Z shell:
         title: &str,
         body: &str,
         labels: &[String],
-    ) -> Result<(), Box<dyn std::error::Error>> {
+        assignees: &[String],
+    ) -> Result<String, Box<dyn std::error::Error>> {
         let target_repo = repo.unwrap_or(&self.repo);
         log::info!(
-            "Fake create_issue for repo={target_repo}, title={title}, body={body}, labels={labels:?}"
+            "Fake create_issue for repo={target_repo}, title={title}, body={body}, labels={labels:?}, assignees={assignees:?}"
         );
-        Ok(())
+        // Like `gh`, fail if a label doesn't exist
+        let existing_labels = self.all_labels(Some(target_repo))?;
+        if let Some(label) = labels.iter().find(|l| !existing_labels.contains(l)) {
+            return Err(format!("could not add label: '{label}' not found").into());
+        }
+        self.update(|store| {
+            let issue = store.create_issue(target_repo, title, body, labels);
+            issue.assignees = assignees.to_vec();
+            issue.url.clone()
+        })
     }
 
-    fn issue_bodies_open_with_label(
+    fn issues_open_with_label(
         &self,
         repo: Option<&str>,
         label: &str,
-    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    ) -> Result<Vec<GhIssue>, Box<dyn std::error::Error>> {
+        let target_repo = repo.unwrap_or(&self.repo);
+        log::info!("Fake issues_open_with_label for repo={target_repo} and label={label}");
+        let mut issues = match &self.fixtures {
+            Some(fixtures) => fixtures.read_issues(target_repo, label)?,
+            None => vec![],
+        };
+        issues.extend(
+            self.store()
+                .issues_with_label(target_repo, label, IssueState::Open)
+                .map(FakeIssue::to_gh_issue),
+        );
+        Ok(issues)
+    }
+
+    fn issues_closed_with_label(
+        &self,
+        repo: Option<&str>,
+        label: &str,
+        closed_since: &str,
+    ) -> Result<Vec<GhIssue>, Box<dyn std::error::Error>> {
         let target_repo = repo.unwrap_or(&self.repo);
-        Ok(vec![format!(
-            "Fake issue body for repo={target_repo} and label={label}"
-        )])
+        log::info!("Fake issues_closed_with_label for repo={target_repo}, label={label} and closed_since={closed_since}");
+        let mut issues = match &self.fixtures {
+            Some(fixtures) => fixtures.read_closed_issues(target_repo, label)?,
+            None => vec![],
+        };
+        issues.extend(
+            self.store()
+                .issues_closed_since(target_repo, label, closed_since)
+                .map(FakeIssue::to_gh_issue),
+        );
+        Ok(issues)
+    }
+
+    fn reopen_issue(
+        &self,
+        repo: Option<&str>,
+        number: u64,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let target_repo = repo.unwrap_or(&self.repo);
+        log::info!("Fake reopen_issue for repo={target_repo} and number={number}");
+        self.update(|store| match store.issue_mut(target_repo, number) {
+            Some(issue) => {
+                issue.state = IssueState::Open;
+                issue.closed_at = None;
+                Ok(())
+            },
+            None => Err(format!("Issue #{number} does not exist in {target_repo}").into()),
+        })?
     }
 
     fn all_labels(&self, repo: Option<&str>) -> Result<Vec<String>, Box<dyn std::error::Error>> {
         let target_repo = repo.unwrap_or(&self.repo);
         log::info!("Fake all_labels for repo={target_repo}");
-        Ok(vec!["fake-label".to_string()])
+        let mut labels = match &self.fixtures {
+            Some(fixtures) => fixtures.read_labels(target_repo)?,
+            None => vec![],
+        };
+        for label in self.store().label_names(target_repo) {
+            if !labels.contains(&label) {
+                labels.push(label);
+            }
+        }
+        Ok(labels)
     }
 
     fn create_label(
@@ -115,9 +290,106 @@ Test template xilinx	📦 Build yocto image	2024-02-10T00:03:45.5843410Z shell:
         log::info!(
             "Fake create_label for repo={target_repo}, name={name}, color={color}, description={description}, force={force}"
         );
+        let created =
+            self.update(|store| store.create_label(target_repo, name, color, description, force))?;
+        if !created {
+            return Err(format!("Label {name} already exists in {target_repo}").into());
+        }
         Ok(())
     }
 
+    fn add_issue_comment(
+        &self,
+        repo: Option<&str>,
+        number: u64,
+        body: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let target_repo = repo.unwrap_or(&self.repo);
+        log::info!("Fake add_issue_comment for repo={target_repo}, number={number}, body={body}");
+        self.update(|store| match store.issue_mut(target_repo, number) {
+            Some(issue) => {
+                issue.comments.push(body.to_string());
+                Ok(())
+            },
+            None => Err(format!("Issue #{number} does not exist in {target_repo}").into()),
+        })?
+    }
+
+    fn issue_comments(
+        &self,
+        repo: Option<&str>,
+        number: u64,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let target_repo = repo.unwrap_or(&self.repo);
+        log::info!("Fake issue_comments for repo={target_repo}, number={number}");
+        // Issues of the fixtures have no comments
+        Ok(self
+            .store()
+            .issue_mut(target_repo, number)
+            .map(|issue| issue.comments.clone())
+            .unwrap_or_default())
+    }
+
+    fn set_issue_milestone(
+        &self,
+        repo: Option<&str>,
+        number: u64,
+        milestone: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let target_repo = repo.unwrap_or(&self.repo);
+        log::info!(
+            "Fake set_issue_milestone for repo={target_repo}, number={number}, milestone={milestone}"
+        );
+        self.update(|store| match store.issue_mut(target_repo, number) {
+            Some(issue) => {
+                issue.milestone = Some(milestone.to_string());
+                Ok(())
+            },
+            None => Err(format!("Issue #{number} does not exist in {target_repo}").into()),
+        })?
+    }
+
+    fn add_issue_to_project(
+        &self,
+        project: &ProjectRef,
+        issue_url: &str,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        log::info!("Fake add_issue_to_project for project={project}, issue_url={issue_url}");
+        self.update(|store| store.add_project_item(project, issue_url).id.clone())
+    }
+
+    fn set_project_item_field(
+        &self,
+        project: &ProjectRef,
+        item_id: &str,
+        field: &str,
+        value: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        log::info!(
+            "Fake set_project_item_field for project={project}, item_id={item_id}, field={field}, value={value}"
+        );
+        self.update(|store| match store.project_item_mut(project, item_id) {
+            Some(item) => {
+                item.fields.insert(field.to_string(), value.to_string());
+                Ok(())
+            },
+            None => Err(format!("Item {item_id} does not exist in project {project}").into()),
+        })?
+    }
+
+    fn repo_file(
+        &self,
+        repo: Option<&str>,
+        path: &str,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let target_repo = repo.unwrap_or(&self.repo);
+        log::info!("Fake repo_file for repo={target_repo} and path={path}");
+        match &self.fixtures {
+            Some(fixtures) => fixtures.read_repo_file(target_repo, path),
+            None => Ok(None),
+        }
+    }
+
     fn default_repo(&self) -> &str {
         &self.repo
     }
@@ -0,0 +1,326 @@
+//! In-memory state of the fake GitHub CLI
+//!
+//! The store can be persisted to a JSON file so the state carries over between runs, e.g. to
+//! check that running `create-issue-from-run` twice creates a single issue.
+use std::{collections::BTreeMap, error::Error, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::gh::{GhIssue, ProjectRef};
+
+/// The state of an issue
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IssueState {
+    #[default]
+    Open,
+    Closed,
+}
+
+/// An issue created through the fake GitHub CLI
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FakeIssue {
+    pub repo: String,
+    pub number: u64,
+    pub url: String,
+    pub title: String,
+    pub body: String,
+    pub labels: Vec<String>,
+    #[serde(default)]
+    pub assignees: Vec<String>,
+    pub state: IssueState,
+    /// ISO 8601 date or timestamp of when the issue was closed
+    #[serde(default)]
+    pub closed_at: Option<String>,
+    /// Title of the milestone
+    #[serde(default)]
+    pub milestone: Option<String>,
+    pub comments: Vec<String>,
+}
+
+impl FakeIssue {
+    pub fn to_gh_issue(&self) -> GhIssue {
+        GhIssue {
+            number: self.number,
+            url: self.url.clone(),
+            title: self.title.clone(),
+            body: self.body.clone(),
+        }
+    }
+}
+
+/// A label created through the fake GitHub CLI
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FakeLabel {
+    pub repo: String,
+    pub name: String,
+    pub color: String,
+    pub description: String,
+}
+
+/// An issue added to a GitHub Project (v2) through the fake GitHub CLI
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FakeProjectItem {
+    /// The project as `<owner>/<number>`
+    pub project: String,
+    pub id: String,
+    pub issue_url: String,
+    /// Field values by field name
+    pub fields: BTreeMap<String, String>,
+}
+
+/// Issues, labels and project items of all repositories the fake GitHub CLI has been used with
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FakeStore {
+    pub issues: Vec<FakeIssue>,
+    pub labels: Vec<FakeLabel>,
+    #[serde(default)]
+    pub project_items: Vec<FakeProjectItem>,
+}
+
+impl FakeStore {
+    /// Load the store from a file, a missing file is an empty store
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        if !path.exists() {
+            log::debug!("No fake GitHub state at {path:?}, starting empty");
+            return Ok(Self::default());
+        }
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read fake GitHub state {path:?}: {e}"))?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+            .map_err(|e| format!("Failed to write fake GitHub state {path:?}: {e}").into())
+    }
+
+    /// Add an issue and return it
+    pub fn create_issue(
+        &mut self,
+        repo: &str,
+        title: &str,
+        body: &str,
+        labels: &[String],
+    ) -> &mut FakeIssue {
+        let number = self
+            .issues
+            .iter()
+            .filter(|issue| issue.repo == repo)
+            .map(|issue| issue.number)
+            .max()
+            .unwrap_or(0)
+            + 1;
+        self.issues.push(FakeIssue {
+            repo: repo.to_string(),
+            number,
+            url: format!("{repo}/issues/{number}"),
+            title: title.to_string(),
+            body: body.to_string(),
+            labels: labels.to_vec(),
+            assignees: vec![],
+            state: IssueState::Open,
+            closed_at: None,
+            milestone: None,
+            comments: vec![],
+        });
+        self.issues.last_mut().expect("Issue was just added")
+    }
+
+    pub fn issue_mut(&mut self, repo: &str, number: u64) -> Option<&mut FakeIssue> {
+        self.issues
+            .iter_mut()
+            .find(|issue| issue.repo == repo && issue.number == number)
+    }
+
+    pub fn issues_with_label<'a>(
+        &'a self,
+        repo: &'a str,
+        label: &'a str,
+        state: IssueState,
+    ) -> impl Iterator<Item = &'a FakeIssue> {
+        self.issues.iter().filter(move |issue| {
+            issue.repo == repo && issue.state == state && issue.labels.iter().any(|l| l == label)
+        })
+    }
+
+    /// Issues with the label that were closed on or after the date, issues without a closing date
+    /// are included
+    pub fn issues_closed_since<'a>(
+        &'a self,
+        repo: &'a str,
+        label: &'a str,
+        closed_since: &'a str,
+    ) -> impl Iterator<Item = &'a FakeIssue> {
+        self.issues_with_label(repo, label, IssueState::Closed)
+            .filter(move |issue| {
+                issue
+                    .closed_at
+                    .as_deref()
+                    .is_none_or(|closed_at| closed_at >= closed_since)
+            })
+    }
+
+    /// Add an issue to a project and return the item, an issue is only added once like on GitHub
+    pub fn add_project_item(
+        &mut self,
+        project: &ProjectRef,
+        issue_url: &str,
+    ) -> &mut FakeProjectItem {
+        let project = project.to_string();
+        let position = match self
+            .project_items
+            .iter()
+            .position(|item| item.project == project && item.issue_url == issue_url)
+        {
+            Some(position) => position,
+            None => {
+                self.project_items.push(FakeProjectItem {
+                    id: format!("PVTI_{}", self.project_items.len() + 1),
+                    project,
+                    issue_url: issue_url.to_string(),
+                    fields: BTreeMap::new(),
+                });
+                self.project_items.len() - 1
+            },
+        };
+        &mut self.project_items[position]
+    }
+
+    pub fn project_item_mut(
+        &mut self,
+        project: &ProjectRef,
+        item_id: &str,
+    ) -> Option<&mut FakeProjectItem> {
+        let project = project.to_string();
+        self.project_items
+            .iter_mut()
+            .find(|item| item.project == project && item.id == item_id)
+    }
+
+    pub fn label_names(&self, repo: &str) -> Vec<String> {
+        self.labels
+            .iter()
+            .filter(|label| label.repo == repo)
+            .map(|label| label.name.clone())
+            .collect()
+    }
+
+    /// Add a label, an existing label is only overwritten if `force` is true
+    ///
+    /// Returns false if the label already exists and was not overwritten
+    pub fn create_label(
+        &mut self,
+        repo: &str,
+        name: &str,
+        color: &str,
+        description: &str,
+        force: bool,
+    ) -> bool {
+        let label = FakeLabel {
+            repo: repo.to_string(),
+            name: name.to_string(),
+            color: color.to_string(),
+            description: description.to_string(),
+        };
+        match self
+            .labels
+            .iter_mut()
+            .find(|l| l.repo == repo && l.name == name)
+        {
+            Some(existing) if force => *existing = label,
+            Some(_) => return false,
+            None => self.labels.push(label),
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use temp_dir::TempDir;
+
+    const REPO: &str = "https://github.com/luftkode/distro-template";
+
+    #[test]
+    fn test_create_issue_numbers_and_labels() {
+        let mut store = FakeStore::default();
+        let first = store
+            .create_issue(REPO, "first", "body", &["bug".to_string()])
+            .clone();
+        let second = store.create_issue(REPO, "second", "body", &[]).clone();
+        let other_repo = store
+            .create_issue("https://github.com/foo/bar", "other", "body", &[])
+            .clone();
+
+        assert_eq!(first.url, format!("{REPO}/issues/1"));
+        assert_eq!(second.number, 2);
+        assert_eq!(other_repo.number, 1);
+        assert_eq!(
+            store
+                .issues_with_label(REPO, "bug", IssueState::Open)
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_issues_closed_since() {
+        let mut store = FakeStore::default();
+        for (number, closed_at) in [(1, "2024-01-01"), (2, "2024-02-11T00:09:04Z")] {
+            store.create_issue(REPO, "title", "body", &["bug".to_string()]);
+            let issue = store.issue_mut(REPO, number).unwrap();
+            issue.state = IssueState::Closed;
+            issue.closed_at = Some(closed_at.to_string());
+        }
+        let closed: Vec<u64> = store
+            .issues_closed_since(REPO, "bug", "2024-02-01")
+            .map(|issue| issue.number)
+            .collect();
+        assert_eq!(closed, [2]);
+    }
+
+    #[test]
+    fn test_create_label_force() {
+        let mut store = FakeStore::default();
+        assert!(store.create_label(REPO, "bug", "FF0000", "", false));
+        assert!(!store.create_label(REPO, "bug", "00FF00", "", false));
+        assert!(store.create_label(REPO, "bug", "00FF00", "Bugs", true));
+        assert_eq!(store.labels.len(), 1);
+        assert_eq!(store.labels[0].color, "00FF00");
+    }
+
+    #[test]
+    fn test_project_items() {
+        let mut store = FakeStore::default();
+        let project: ProjectRef = "luftkode/3".parse().unwrap();
+        let issue_url = format!("{REPO}/issues/1");
+        let id = store.add_project_item(&project, &issue_url).id.clone();
+        // Adding the same issue again returns the existing item
+        assert_eq!(store.add_project_item(&project, &issue_url).id, id);
+        store
+            .project_item_mut(&project, &id)
+            .unwrap()
+            .fields
+            .insert("Status".to_string(), "Triage".to_string());
+        assert_eq!(store.project_items.len(), 1);
+        assert_eq!(store.project_items[0].fields["Status"], "Triage");
+        assert!(store
+            .project_item_mut(&"luftkode/4".parse().unwrap(), &id)
+            .is_none());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("state.json");
+        assert_eq!(FakeStore::load(&path).unwrap(), FakeStore::default());
+
+        let mut store = FakeStore::default();
+        store.create_issue(REPO, "title", "body", &["bug".to_string()]);
+        store.save(&path).unwrap();
+        assert_eq!(FakeStore::load(&path).unwrap(), store);
+    }
+}
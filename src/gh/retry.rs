@@ -0,0 +1,458 @@
+//! Retry GitHub calls that fail transiently or are rate limited
+//!
+//! [RetryingGitHub] wraps another [GitHub] implementation and retries calls that fail with a
+//! [transient](GhCliErrorKind::Transient) error with exponential backoff and jitter, and calls that
+//! are [rate limited](GhCliErrorKind::RateLimited) once the rate limit resets.
+//!
+//! Calls that change something in GitHub (e.g. creating an issue) are only retried when rate
+//! limited, as a server error or timeout doesn't tell whether the change was made, and retrying it
+//! could e.g. create a duplicate issue.
+use std::{
+    error::Error,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use super::{
+    util::{GhCliError, GhCliErrorKind},
+    GhIssue, GhJob, GhRun, GitHub, LogReader, ProjectRef, RunFilter,
+};
+
+pub const DEFAULT_RETRIES: u32 = 3;
+pub const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_secs(2);
+pub const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// How long a GitHub call may take before it's aborted
+pub const DEFAULT_GH_TIMEOUT: Duration = Duration::from_secs(300);
+/// How long to wait after hitting a secondary rate limit, GitHub doesn't tell when it resets
+pub const DEFAULT_RATE_LIMIT_WAIT: Duration = Duration::from_secs(60);
+/// Longest wait for a rate limit to reset before the call fails instead
+pub const DEFAULT_MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(15 * 60);
+
+/// How failed GitHub calls are retried
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Number of times a failed call is retried
+    pub retries: u32,
+    /// Delay before the first retry of a transient failure, doubled for every following retry
+    pub initial_backoff: Duration,
+    /// Maximum delay before a retry of a transient failure
+    pub max_backoff: Duration,
+    /// Delay before retrying a rate limited call when the reset time of the rate limit is unknown
+    pub rate_limit_wait: Duration,
+    /// A rate limited call fails instead of being retried if the rate limit resets later than this
+    pub max_rate_limit_wait: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: DEFAULT_RETRIES,
+            initial_backoff: DEFAULT_INITIAL_BACKOFF,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+            rate_limit_wait: DEFAULT_RATE_LIMIT_WAIT,
+            max_rate_limit_wait: DEFAULT_MAX_RATE_LIMIT_WAIT,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The delay before retry number `retry` (starting at 0) of a transient failure
+    ///
+    /// The initial backoff is doubled for every retry up to the maximum backoff, and a random
+    /// jitter of up to half of the delay is subtracted so concurrent calls don't retry in lockstep.
+    ///
+    /// # Example
+    /// ```
+    /// # use gh_workflow_parser::gh::retry::RetryPolicy;
+    /// # use std::time::Duration;
+    /// let policy = RetryPolicy {
+    ///     initial_backoff: Duration::from_secs(2),
+    ///     max_backoff: Duration::from_secs(60),
+    ///     ..Default::default()
+    /// };
+    /// let third = policy.backoff(2);
+    /// assert!(third > Duration::from_secs(4) && third <= Duration::from_secs(8));
+    /// assert!(policy.backoff(10) <= Duration::from_secs(60));
+    /// ```
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let delay = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_backoff);
+        delay - delay.mul_f64(fastrand::f64() / 2.0)
+    }
+
+    /// How long to wait before retrying a call that failed with `err`, `None` if it can't be
+    /// retried
+    fn delay(&self, err: &(dyn Error + 'static), retry: u32, idempotent: bool) -> Option<Duration> {
+        match GhCliError::find(err)?.kind() {
+            GhCliErrorKind::RateLimited { reset: Some(reset) } => {
+                let wait = reset
+                    .duration_since(SystemTime::now())
+                    .unwrap_or_default()
+                    .saturating_add(Duration::from_secs(1));
+                if wait > self.max_rate_limit_wait {
+                    log::error!(
+                        "The rate limit resets in {wait:.0?}, longer than the maximum wait of {:.0?}",
+                        self.max_rate_limit_wait
+                    );
+                    return None;
+                }
+                Some(wait)
+            },
+            GhCliErrorKind::RateLimited { reset: None } => {
+                Some(self.rate_limit_wait.min(self.max_rate_limit_wait))
+            },
+            GhCliErrorKind::Transient if idempotent => Some(self.backoff(retry)),
+            GhCliErrorKind::Transient | GhCliErrorKind::Permanent => None,
+        }
+    }
+
+    /// Make a call until it succeeds, fails in a way that can't be retried, or the retries are
+    /// used up
+    ///
+    /// `idempotent` calls can be made again without changing the outcome, only they are retried
+    /// after transient failures. Every retry is logged with `what` describing the call.
+    pub fn retry<T>(
+        &self,
+        what: &str,
+        idempotent: bool,
+        mut call: impl FnMut() -> Result<T, Box<dyn Error>>,
+    ) -> Result<T, Box<dyn Error>> {
+        let mut retry = 0;
+        loop {
+            let err = match call() {
+                Ok(value) => return Ok(value),
+                Err(err) => err,
+            };
+            let delay = if retry < self.retries {
+                self.delay(err.as_ref(), retry, idempotent)
+            } else {
+                None
+            };
+            let Some(delay) = delay else {
+                return Err(err);
+            };
+            retry += 1;
+            log::warn!(
+                "{what} failed, retry {retry}/{retries} in {delay:.1?}: {err}",
+                retries = self.retries
+            );
+            thread::sleep(delay);
+        }
+    }
+}
+
+/// A [GitHub] implementation retrying the calls of another according to a [RetryPolicy]
+pub struct RetryingGitHub {
+    inner: Box<dyn GitHub>,
+    policy: RetryPolicy,
+}
+
+impl RetryingGitHub {
+    pub fn new(inner: Box<dyn GitHub>, policy: RetryPolicy) -> Self {
+        Self { inner, policy }
+    }
+}
+
+impl GitHub for RetryingGitHub {
+    fn run_summary(&self, repo: Option<&str>, run_id: &str) -> Result<String, Box<dyn Error>> {
+        self.policy.retry(
+            &format!("Getting the summary of run {run_id}"),
+            true,
+            || self.inner.run_summary(repo, run_id),
+        )
+    }
+
+    fn failed_job_log(
+        &self,
+        repo: Option<&str>,
+        job_id: &str,
+    ) -> Result<LogReader, Box<dyn Error>> {
+        self.policy
+            .retry(&format!("Getting the log of job {job_id}"), true, || {
+                let mut log = self.inner.failed_job_log(repo, job_id)?;
+                // A request that GitHub rejects fails before any of the log is printed, so reading
+                // the start of the log catches the failures that can be retried
+                log.fill_buf()?;
+                Ok(log)
+            })
+    }
+
+    fn list_runs(
+        &self,
+        repo: Option<&str>,
+        filter: &RunFilter,
+    ) -> Result<Vec<GhRun>, Box<dyn Error>> {
+        self.policy
+            .retry("Listing runs", true, || self.inner.list_runs(repo, filter))
+    }
+
+    fn run_jobs(
+        &self,
+        repo: Option<&str>,
+        run_id: u64,
+        attempt: u64,
+    ) -> Result<Vec<GhJob>, Box<dyn Error>> {
+        self.policy
+            .retry(&format!("Listing the jobs of run {run_id}"), true, || {
+                self.inner.run_jobs(repo, run_id, attempt)
+            })
+    }
+
+    fn create_issue(
+        &self,
+        repo: Option<&str>,
+        title: &str,
+        body: &str,
+        labels: &[String],
+        assignees: &[String],
+    ) -> Result<String, Box<dyn Error>> {
+        self.policy.retry("Creating an issue", false, || {
+            self.inner
+                .create_issue(repo, title, body, labels, assignees)
+        })
+    }
+
+    fn issues_open_with_label(
+        &self,
+        repo: Option<&str>,
+        label: &str,
+    ) -> Result<Vec<GhIssue>, Box<dyn Error>> {
+        self.policy.retry("Listing open issues", true, || {
+            self.inner.issues_open_with_label(repo, label)
+        })
+    }
+
+    fn issues_closed_with_label(
+        &self,
+        repo: Option<&str>,
+        label: &str,
+        closed_since: &str,
+    ) -> Result<Vec<GhIssue>, Box<dyn Error>> {
+        self.policy.retry("Listing closed issues", true, || {
+            self.inner
+                .issues_closed_with_label(repo, label, closed_since)
+        })
+    }
+
+    fn reopen_issue(&self, repo: Option<&str>, number: u64) -> Result<(), Box<dyn Error>> {
+        self.policy
+            .retry(&format!("Reopening issue #{number}"), false, || {
+                self.inner.reopen_issue(repo, number)
+            })
+    }
+
+    fn all_labels(&self, repo: Option<&str>) -> Result<Vec<String>, Box<dyn Error>> {
+        self.policy
+            .retry("Listing labels", true, || self.inner.all_labels(repo))
+    }
+
+    fn create_label(
+        &self,
+        repo: Option<&str>,
+        name: &str,
+        color: &str,
+        description: &str,
+        force: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        self.policy
+            .retry(&format!("Creating label {name}"), false, || {
+                self.inner
+                    .create_label(repo, name, color, description, force)
+            })
+    }
+
+    fn add_issue_comment(
+        &self,
+        repo: Option<&str>,
+        number: u64,
+        body: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        self.policy
+            .retry(&format!("Commenting on issue #{number}"), false, || {
+                self.inner.add_issue_comment(repo, number, body)
+            })
+    }
+
+    fn issue_comments(
+        &self,
+        repo: Option<&str>,
+        number: u64,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        self.policy.retry(
+            &format!("Listing the comments on issue #{number}"),
+            true,
+            || self.inner.issue_comments(repo, number),
+        )
+    }
+
+    fn set_issue_milestone(
+        &self,
+        repo: Option<&str>,
+        number: u64,
+        milestone: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        // Setting the milestone again doesn't change anything
+        self.policy.retry(
+            &format!("Setting the milestone of issue #{number}"),
+            true,
+            || self.inner.set_issue_milestone(repo, number, milestone),
+        )
+    }
+
+    fn add_issue_to_project(
+        &self,
+        project: &ProjectRef,
+        issue_url: &str,
+    ) -> Result<String, Box<dyn Error>> {
+        self.policy.retry(
+            &format!("Adding {issue_url} to project {project}"),
+            false,
+            || self.inner.add_issue_to_project(project, issue_url),
+        )
+    }
+
+    fn set_project_item_field(
+        &self,
+        project: &ProjectRef,
+        item_id: &str,
+        field: &str,
+        value: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        // Setting the field again doesn't change anything
+        self.policy.retry(
+            &format!("Setting field {field} of project item {item_id}"),
+            true,
+            || {
+                self.inner
+                    .set_project_item_field(project, item_id, field, value)
+            },
+        )
+    }
+
+    fn repo_file(&self, repo: Option<&str>, path: &str) -> Result<Option<String>, Box<dyn Error>> {
+        self.policy
+            .retry(&format!("Getting {path} from the repository"), true, || {
+                self.inner.repo_file(repo, path)
+            })
+    }
+
+    fn default_repo(&self) -> &str {
+        self.inner.default_repo()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::io;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            retries: 2,
+            initial_backoff: Duration::ZERO,
+            max_backoff: Duration::ZERO,
+            rate_limit_wait: Duration::ZERO,
+            max_rate_limit_wait: Duration::from_secs(60),
+        }
+    }
+
+    fn failing(kind: GhCliErrorKind, failures: u32) -> impl FnMut() -> Result<u32, Box<dyn Error>> {
+        let mut calls = 0;
+        move || {
+            calls += 1;
+            if calls <= failures {
+                Err(GhCliError::new("Failed", "oops", kind).into())
+            } else {
+                Ok(calls)
+            }
+        }
+    }
+
+    #[test]
+    fn test_retry_transient_failures() {
+        let calls = policy()
+            .retry("Call", true, failing(GhCliErrorKind::Transient, 2))
+            .unwrap();
+        assert_eq!(calls, 3);
+
+        let err = policy()
+            .retry("Call", true, failing(GhCliErrorKind::Transient, 3))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Failed. Failure: oops");
+    }
+
+    #[test]
+    fn test_retry_only_rate_limited_changes() {
+        let calls = policy()
+            .retry(
+                "Call",
+                false,
+                failing(GhCliErrorKind::RateLimited { reset: None }, 1),
+            )
+            .unwrap();
+        assert_eq!(calls, 2);
+
+        let calls = policy()
+            .retry(
+                "Call",
+                false,
+                failing(
+                    GhCliErrorKind::RateLimited {
+                        reset: Some(SystemTime::UNIX_EPOCH),
+                    },
+                    1,
+                ),
+            )
+            .unwrap();
+        assert_eq!(calls, 2);
+
+        assert!(policy()
+            .retry("Call", false, failing(GhCliErrorKind::Transient, 1))
+            .is_err());
+    }
+
+    #[test]
+    fn test_no_retry_past_max_rate_limit_wait() {
+        let reset = SystemTime::now() + Duration::from_secs(3600);
+        let err = policy()
+            .retry(
+                "Call",
+                true,
+                failing(GhCliErrorKind::RateLimited { reset: Some(reset) }, 1),
+            )
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Failed. Failure: oops");
+    }
+
+    #[test]
+    fn test_no_retry_of_permanent_failures() {
+        assert!(policy()
+            .retry("Call", true, failing(GhCliErrorKind::Permanent, 1))
+            .is_err());
+
+        let mut calls = 0;
+        let result: Result<(), _> = policy().retry("Call", true, || {
+            calls += 1;
+            Err("Not a gh failure".into())
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn test_retry_failures_wrapped_in_io_errors() {
+        let mut calls = 0;
+        let result = policy().retry("Call", true, || {
+            calls += 1;
+            if calls == 1 {
+                let err = GhCliError::new("Failed", "HTTP 502", GhCliErrorKind::Transient);
+                return Err(io::Error::other(err).into());
+            }
+            Ok(calls)
+        });
+        assert_eq!(result.unwrap(), 2);
+    }
+}
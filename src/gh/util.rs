@@ -1,8 +1,23 @@
-use std::{error::Error, process::Command};
-
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fmt,
+    io::{self, Read},
+    process::{Child, ChildStdout, Command, ExitStatus, Output, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex, MutexGuard, PoisonError, RwLock,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use once_cell::sync::Lazy;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+use wait_timeout::ChildExt;
 
-use crate::gh::gh_cli;
+use crate::gh::{gh_cli, GhIssue, GhJob, GhRun, ProjectRef, RunFilter};
 
 pub fn repo_url_to_job_url(repo_url: &str, run_id: &str, job_id: &str) -> String {
     let run_url = repo_url_to_run_url(repo_url, run_id);
@@ -18,64 +33,476 @@ pub fn run_url_to_job_url(run_url: &str, job_id: &str) -> String {
 }
 
 pub fn run_summary(repo: &str, run_id: &str) -> Result<String, Box<dyn Error>> {
-    let output = Command::new(gh_cli())
-        .arg("run")
-        .arg(format!("--repo={repo}"))
-        .arg("view")
-        .arg(run_id)
-        .output()?;
+    let output = run_gh(
+        repo_command(repo)
+            .arg("run")
+            .arg(format!("--repo={repo}"))
+            .arg("view")
+            .arg(run_id),
+        format!("Failed to get logs for repo={repo} run_id={run_id}"),
+    )?;
+
+    Ok(String::from_utf8_lossy(&output).to_string())
+}
 
-    assert!(
-        output.status.success(),
-        "Failed to get logs for repo={repo} run_id={run_id}. Failure: {stderr}",
-        stderr = String::from_utf8_lossy(&output.stderr)
-    );
+/// A `gh` command for the host, `gh` sends the requests that aren't for a repository URL (e.g. of
+/// `gh project` and `gh api`) to the host in `GH_HOST`, github.com by default
+fn gh_command(host: Option<&str>) -> Command {
+    let mut cmd = Command::new(gh_cli());
+    if let Some(host) = host {
+        cmd.env("GH_HOST", host);
+    }
+    cmd
+}
+
+/// A `gh` command for the host of the repository URL
+fn repo_command(repo: &str) -> Command {
+    gh_command(repo_host_and_slug(repo).map(|(host, _slug)| host))
+}
 
-    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+/// The host a command is for, `None` for the default host
+fn command_host(cmd: &Command) -> Option<String> {
+    cmd.get_envs()
+        .find(|(key, _)| *key == "GH_HOST")
+        .and_then(|(_, value)| value?.to_str())
+        .map(str::to_string)
 }
 
-pub fn failed_job_log(repo: &str, job_id: &str) -> Result<String, Box<dyn Error>> {
-    let output = Command::new(gh_cli())
-        .arg("run")
+/// Stream the log of a failed job from the output of `gh`
+pub fn failed_job_log(repo: &str, job_id: &str) -> Result<CommandOutput, Box<dyn Error>> {
+    let mut cmd = repo_command(repo);
+    cmd.arg("run")
         .arg("view")
         .arg("--repo")
         .arg(repo)
         .arg("--job")
         .arg(job_id)
-        .arg("--log-failed")
-        .output()?;
+        .arg("--log-failed");
+    CommandOutput::spawn(cmd, format!("Failed to get logs for job ID: {job_id}"))
+}
 
-    assert!(
-        output.status.success(),
-        "Failed to get logs for job ID: {job_id}. Failure: {stderr}",
-        stderr = String::from_utf8_lossy(&output.stderr)
-    );
+/// The stdout of a running command, when all of it is read the command must have succeeded
+///
+/// The command is killed if it runs longer than the [command timeout](set_command_timeout).
+#[derive(Debug)]
+pub struct CommandOutput {
+    child: Arc<Mutex<Child>>,
+    stdout: ChildStdout,
+    /// Reads stderr in the background so the command can't block on a full stderr pipe
+    stderr: Option<JoinHandle<String>>,
+    /// The error message if the command fails
+    failure: String,
+    /// The host the command is for, `None` for the default host
+    host: Option<String>,
+    finished: bool,
+    timeout: Option<Duration>,
+    deadline: Option<Instant>,
+    /// Set when the command was killed for running longer than the timeout
+    timed_out: Arc<AtomicBool>,
+    /// Stops the watchdog killing the command after the timeout when dropped
+    _watchdog: Option<mpsc::Sender<()>>,
+}
+
+impl CommandOutput {
+    pub fn spawn(cmd: Command, failure: String) -> Result<Self, Box<dyn Error>> {
+        Self::spawn_with_timeout(cmd, failure, command_timeout())
+    }
+
+    /// Spawn the command and kill it if it runs longer than `timeout`, `None` waits until it
+    /// finishes
+    pub fn spawn_with_timeout(
+        mut cmd: Command,
+        failure: String,
+        timeout: Option<Duration>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let host = command_host(&cmd);
+        let mut child = cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or("The stdout of the command is not piped")?;
+        let mut stderr = child
+            .stderr
+            .take()
+            .ok_or("The stderr of the command is not piped")?;
+        let stderr = thread::spawn(move || {
+            let mut buf = Vec::new();
+            let _ = stderr.read_to_end(&mut buf);
+            String::from_utf8_lossy(&buf).into_owned()
+        });
+        let child = Arc::new(Mutex::new(child));
+        let timed_out = Arc::new(AtomicBool::new(false));
+        let watchdog = timeout.map(|timeout| {
+            let (sender, receiver) = mpsc::channel::<()>();
+            let child = Arc::clone(&child);
+            let timed_out = Arc::clone(&timed_out);
+            thread::spawn(move || {
+                if let Err(mpsc::RecvTimeoutError::Timeout) = receiver.recv_timeout(timeout) {
+                    timed_out.store(true, Ordering::SeqCst);
+                    let _ = lock_child(&child).kill();
+                }
+            });
+            sender
+        });
+        Ok(Self {
+            child,
+            stdout,
+            stderr: Some(stderr),
+            failure,
+            host,
+            finished: false,
+            timeout,
+            deadline: timeout.map(|timeout| Instant::now() + timeout),
+            timed_out,
+            _watchdog: watchdog,
+        })
+    }
+
+    /// Wait for the command to exit, killing it at the deadline
+    fn wait(&self) -> io::Result<ExitStatus> {
+        let mut child = lock_child(&self.child);
+        let Some(deadline) = self.deadline else {
+            return child.wait();
+        };
+        match child.wait_timeout(deadline.saturating_duration_since(Instant::now()))? {
+            Some(status) => Ok(status),
+            None => {
+                self.timed_out.store(true, Ordering::SeqCst);
+                let _ = child.kill();
+                child.wait()
+            },
+        }
+    }
+}
+
+/// Lock the child process, it's shared with the watchdog
+fn lock_child(child: &Mutex<Child>) -> MutexGuard<'_, Child> {
+    child.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl Read for CommandOutput {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.stdout.read(buf)?;
+        if len == 0 && !buf.is_empty() && !self.finished {
+            self.finished = true;
+            let status = self.wait()?;
+            if self.timed_out.load(Ordering::SeqCst) {
+                return Err(io::Error::other(GhCliError::new(
+                    &self.failure,
+                    &format!("Timed out after {:?}", self.timeout),
+                    GhCliErrorKind::Transient,
+                )));
+            }
+            if !status.success() {
+                let stderr = self
+                    .stderr
+                    .take()
+                    .and_then(|stderr| stderr.join().ok())
+                    .unwrap_or_default();
+                return Err(io::Error::other(GhCliError::from_stderr(
+                    &self.failure,
+                    &stderr,
+                    self.host.as_deref(),
+                )));
+            }
+        }
+        Ok(len)
+    }
+}
+
+impl Drop for CommandOutput {
+    fn drop(&mut self) {
+        if !self.finished {
+            // The output wasn't read to the end
+            let mut child = lock_child(&self.child);
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// How long a `gh` command may run before it's killed, `None` waits until it finishes
+static COMMAND_TIMEOUT: RwLock<Option<Duration>> = RwLock::new(None);
+
+/// Set how long a `gh` command may run before it's killed, `None` waits until it finishes
+///
+/// For streamed output e.g. the [log of a failed job](failed_job_log) this includes the time the
+/// reader takes to read the output.
+pub fn set_command_timeout(timeout: Option<Duration>) {
+    *COMMAND_TIMEOUT
+        .write()
+        .unwrap_or_else(PoisonError::into_inner) = timeout;
+}
+
+fn command_timeout() -> Option<Duration> {
+    *COMMAND_TIMEOUT
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+static RATE_LIMITED_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)rate limit|HTTP 429|abuse detection").expect("Failed to compile regex")
+});
+
+static TRANSIENT_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)HTTP 5[0-9]{2}|timeout|timed out|connection (reset|refused)|unexpected EOF")
+        .expect("Failed to compile regex")
+});
+
+/// How a failed `gh` command may be retried
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GhCliErrorKind {
+    /// GitHub rejected the request because of a rate limit, it can be retried after the reset
+    /// time if known
+    RateLimited { reset: Option<SystemTime> },
+    /// A server error, network error or timeout, retrying it may succeed
+    Transient,
+    /// Any other failure e.g. a missing repository or invalid arguments
+    Permanent,
+}
+
+impl GhCliErrorKind {
+    /// Classify a failure by the error message `gh` printed to stderr
+    ///
+    /// The reset time of a rate limit isn't in the message, see [rate_limit_reset]
+    ///
+    /// # Example
+    /// ```
+    /// # use gh_workflow_parser::gh::util::GhCliErrorKind;
+    /// assert_eq!(
+    ///     GhCliErrorKind::from_stderr("HTTP 403: API rate limit exceeded for user ID 1."),
+    ///     GhCliErrorKind::RateLimited { reset: None }
+    /// );
+    /// assert_eq!(GhCliErrorKind::from_stderr("HTTP 502: Bad Gateway"), GhCliErrorKind::Transient);
+    /// assert_eq!(GhCliErrorKind::from_stderr("HTTP 404: Not Found"), GhCliErrorKind::Permanent);
+    /// ```
+    pub fn from_stderr(stderr: &str) -> Self {
+        if RATE_LIMITED_RE.is_match(stderr) {
+            Self::RateLimited { reset: None }
+        } else if TRANSIENT_RE.is_match(stderr) {
+            Self::Transient
+        } else {
+            Self::Permanent
+        }
+    }
+}
+
+/// A `gh` command that failed
+#[derive(Debug)]
+pub struct GhCliError {
+    /// What failed e.g. `Failed to list runs`
+    failure: String,
+    /// Why it failed, usually the error message `gh` printed to stderr
+    detail: String,
+    kind: GhCliErrorKind,
+}
 
-    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+impl GhCliError {
+    pub fn new(failure: &str, detail: &str, kind: GhCliErrorKind) -> Self {
+        Self {
+            failure: failure.to_string(),
+            detail: detail.to_string(),
+            kind,
+        }
+    }
+
+    /// A command for the host (`None` for the default host) that failed with the error message
+    /// `stderr`, if it was rate limited the reset time is looked up with [rate_limit_reset]
+    pub fn from_stderr(failure: &str, stderr: &str, host: Option<&str>) -> Self {
+        let kind = match GhCliErrorKind::from_stderr(stderr) {
+            GhCliErrorKind::RateLimited { .. } => GhCliErrorKind::RateLimited {
+                reset: rate_limit_reset(host),
+            },
+            kind => kind,
+        };
+        Self::new(failure, stderr, kind)
+    }
+
+    /// Find the [GhCliError] behind an error returned from a [GitHub](super::GitHub) call, also
+    /// when it was wrapped in an [io::Error] by a [LogReader](super::LogReader)
+    pub fn find<'a>(err: &'a (dyn Error + 'static)) -> Option<&'a Self> {
+        err.downcast_ref::<Self>().or_else(|| {
+            err.downcast_ref::<io::Error>()
+                .and_then(|err| err.get_ref())
+                .and_then(|err| err.downcast_ref::<Self>())
+        })
+    }
+
+    pub fn detail(&self) -> &str {
+        &self.detail
+    }
+
+    pub fn kind(&self) -> GhCliErrorKind {
+        self.kind
+    }
+}
+
+impl fmt::Display for GhCliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}. Failure: {}", self.failure, self.detail)
+    }
+}
+
+impl Error for GhCliError {}
+
+/// Run a `gh` command to completion within the [command timeout](set_command_timeout) and return
+/// its stdout
+///
+/// # Errors
+/// Returns a [GhCliError] starting with `failure` if the command fails or times out
+fn run_gh(cmd: &mut Command, failure: impl Into<String>) -> Result<Vec<u8>, GhCliError> {
+    let failure = failure.into();
+    let timeout = command_timeout();
+    match output_with_timeout(cmd, timeout) {
+        Ok(Some(output)) if output.status.success() => Ok(output.stdout),
+        Ok(Some(output)) => Err(GhCliError::from_stderr(
+            &failure,
+            &String::from_utf8_lossy(&output.stderr),
+            command_host(cmd).as_deref(),
+        )),
+        Ok(None) => Err(GhCliError::new(
+            &failure,
+            &format!("Timed out after {timeout:?}"),
+            GhCliErrorKind::Transient,
+        )),
+        Err(e) => Err(GhCliError::new(
+            &failure,
+            &e.to_string(),
+            GhCliErrorKind::Permanent,
+        )),
+    }
 }
 
-/// Create an issue in the GitHub repository
+/// Run a command to completion, `None` if it didn't finish within the timeout and was killed
+fn output_with_timeout(cmd: &mut Command, timeout: Option<Duration>) -> io::Result<Option<Output>> {
+    let Some(timeout) = timeout else {
+        return cmd.output().map(Some);
+    };
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    // Read the output in the background so the command can't block on a full pipe
+    let stdout = child.stdout.take().map(read_in_background);
+    let stderr = child.stderr.take().map(read_in_background);
+    let Some(status) = child.wait_timeout(timeout)? else {
+        let _ = child.kill();
+        let _ = child.wait();
+        return Ok(None);
+    };
+    Ok(Some(Output {
+        status,
+        stdout: stdout.and_then(|t| t.join().ok()).unwrap_or_default(),
+        stderr: stderr.and_then(|t| t.join().ok()).unwrap_or_default(),
+    }))
+}
+
+fn read_in_background(mut pipe: impl Read + Send + 'static) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = pipe.read_to_end(&mut buf);
+        buf
+    })
+}
+
+/// The time the exhausted GitHub rate limits of the host (`None` for the default host) reset,
+/// `None` if no rate limit is exhausted (e.g. after hitting a secondary rate limit) or it can't be
+/// looked up
+pub fn rate_limit_reset(host: Option<&str>) -> Option<SystemTime> {
+    #[derive(Deserialize)]
+    struct Limit {
+        remaining: u64,
+        reset: u64,
+    }
+    #[derive(Deserialize)]
+    struct RateLimit {
+        resources: BTreeMap<String, Limit>,
+    }
+
+    // Looking up the rate limit doesn't count against it
+    let output = output_with_timeout(
+        gh_command(host).arg("api").arg("rate_limit"),
+        command_timeout(),
+    )
+    .ok()??;
+    if !output.status.success() {
+        return None;
+    }
+    let limits: RateLimit = serde_json::from_slice(&output.stdout).ok()?;
+    limits
+        .resources
+        .values()
+        .filter(|limit| limit.remaining == 0)
+        .map(|limit| UNIX_EPOCH + Duration::from_secs(limit.reset))
+        .max()
+}
+
+/// List the workflow runs matching the filter, newest first
+pub fn list_runs(repo: &str, filter: &RunFilter) -> Result<Vec<GhRun>, Box<dyn Error>> {
+    let mut cmd = repo_command(repo);
+    cmd.arg("run")
+        .arg("list")
+        .arg("--repo")
+        .arg(repo)
+        .arg("--limit")
+        .arg(filter.limit.to_string())
+        .arg("--json")
+        .arg("databaseId,displayTitle,workflowName,headBranch,conclusion,createdAt,url,headSha,attempt");
+    if let Some(workflow) = &filter.workflow {
+        cmd.arg("--workflow").arg(workflow);
+    }
+    if let Some(branch) = &filter.branch {
+        cmd.arg("--branch").arg(branch);
+    }
+    if let Some(status) = &filter.status {
+        cmd.arg("--status").arg(status);
+    }
+    if let Some(created) = filter.created_range() {
+        cmd.arg("--created").arg(created);
+    }
+
+    let output = run_gh(&mut cmd, "Failed to list runs")?;
+
+    Ok(serde_json::from_slice(&output)?)
+}
+
+/// List the jobs of an attempt of a workflow run
+pub fn run_jobs(repo: &str, run_id: u64, attempt: u64) -> Result<Vec<GhJob>, Box<dyn Error>> {
+    let output = run_gh(
+        repo_command(repo)
+            .arg("run")
+            .arg("view")
+            .arg(run_id.to_string())
+            .arg("--repo")
+            .arg(repo)
+            .arg("--attempt")
+            .arg(attempt.to_string())
+            .arg("--json")
+            .arg("jobs"),
+        format!("Failed to get the jobs of run {run_id} attempt {attempt}"),
+    )?;
+
+    #[derive(Deserialize)]
+    struct RunJobs {
+        jobs: Vec<GhJob>,
+    }
+    Ok(serde_json::from_slice::<RunJobs>(&output)?.jobs)
+}
+
+/// Create an issue in the GitHub repository and return the URL of the created issue
+///
+/// The labels must exist in the repository, see [LabelPolicy::ensure_labels](crate::labels::LabelPolicy::ensure_labels)
 pub fn create_issue(
     repo: &str,
     title: &str,
     body: &str,
     labels: &[String],
-) -> Result<(), Box<dyn Error>> {
-    // First check if the labels exist on the repository
-    let existing_labels = all_labels(repo)?;
-    for label in labels {
-        if !existing_labels.contains(label) {
-            log::info!("Label {label} does not exist in the repository. Creating it...");
-            create_label(repo, label, "FF0000", "", false)?;
-        } else {
-            log::debug!(
-                "Label {label} already exists in the repository, continuing without creating it."
-            )
-        }
-    }
+    assignees: &[String],
+) -> Result<String, Box<dyn Error>> {
     // format the labels into a single string separated by commas
     let labels = labels.join(",");
-    let mut command = Command::new(gh_cli());
+    let mut command = repo_command(repo);
     command
         .arg("issue")
         .arg("create")
@@ -87,74 +514,289 @@ pub fn create_issue(
         .arg(body)
         .arg("--label")
         .arg(labels);
+    if !assignees.is_empty() {
+        command.arg("--assignee").arg(assignees.join(","));
+    }
 
     log::debug!("Debug view of command struct: {command:?}");
     // Run the command
-    let output = command.output()?;
+    let output = run_gh(&mut command, "Failed to create issue")?;
 
-    assert!(
-        output.status.success(),
-        "Failed to create issue. Failure: {stderr}",
-        stderr = String::from_utf8_lossy(&output.stderr)
-    );
+    // `gh issue create` prints the URL of the created issue
+    Ok(String::from_utf8_lossy(&output).trim().to_string())
+}
 
-    Ok(())
+/// Get the open issues with a specific label
+pub fn issues_open_with_label(repo: &str, label: &str) -> Result<Vec<GhIssue>, Box<dyn Error>> {
+    let output = run_gh(
+        &mut issue_list_command(repo, label),
+        "Failed to list issues",
+    )?;
+
+    let output = String::from_utf8_lossy(&output);
+    let parsed: Vec<GhIssue> = serde_json::from_str(&output)?;
+    Ok(parsed)
 }
 
-/// Get the bodies of open issues with a specific label
-pub fn issue_bodies_open_with_label(
-    repo: &str,
-    label: &str,
-) -> Result<Vec<String>, Box<dyn Error>> {
-    let output = Command::new(gh_cli())
-        .arg("issue")
+/// Maximum number of issues listed, `gh issue list` only lists 30 by default
+pub const ISSUES_LIMIT: usize = 1000;
+
+/// The `gh issue list` command listing up to [ISSUES_LIMIT] open issues with the label
+fn issue_list_command(repo: &str, label: &str) -> Command {
+    let mut cmd = repo_command(repo);
+    cmd.arg("issue")
         .arg("list")
         .arg("--repo")
         .arg(repo)
         .arg("--label")
         .arg(label)
+        .arg("--limit")
+        .arg(ISSUES_LIMIT.to_string())
         .arg("--json")
-        .arg("body")
-        .output()
-        .expect("Failed to list issues");
-
-    assert!(
-        output.status.success(),
-        "Failed to list issues. Failure: {stderr}",
-        stderr = String::from_utf8_lossy(&output.stderr)
-    );
+        .arg("number,url,title,body");
+    cmd
+}
 
-    let output = String::from_utf8_lossy(&output.stdout);
+/// Get the issues with a specific label that were closed on or after the date
+pub fn issues_closed_with_label(
+    repo: &str,
+    label: &str,
+    closed_since: &str,
+) -> Result<Vec<GhIssue>, Box<dyn Error>> {
+    let output = run_gh(
+        issue_list_command(repo, label)
+            .arg("--state")
+            .arg("closed")
+            .arg("--search")
+            .arg(format!("closed:>={closed_since}")),
+        "Failed to list closed issues",
+    )?;
+
+    let output = String::from_utf8_lossy(&output);
+    let parsed: Vec<GhIssue> = serde_json::from_str(&output)?;
+    Ok(parsed)
+}
 
-    /// Helper struct to deserialize a JSON array of github issue bodies
-    #[derive(Serialize, Deserialize)]
-    struct GhIssueBody {
-        pub body: String,
+/// Reopen a closed issue in the GitHub repository
+pub fn reopen_issue(repo: &str, number: u64) -> Result<(), Box<dyn Error>> {
+    run_gh(
+        repo_command(repo)
+            .arg("issue")
+            .arg("reopen")
+            .arg(number.to_string())
+            .arg("--repo")
+            .arg(repo),
+        format!("Failed to reopen issue #{number}"),
+    )?;
+
+    Ok(())
+}
+
+/// The number of an issue from its URL
+///
+/// # Example
+/// ```
+/// # use gh_workflow_parser::gh::util::issue_number_from_url;
+/// assert_eq!(issue_number_from_url("https://github.com/luftkode/distro-template/issues/42"), Some(42));
+/// assert_eq!(issue_number_from_url("https://github.com/luftkode/distro-template"), None);
+/// ```
+pub fn issue_number_from_url(url: &str) -> Option<u64> {
+    let (_, number) = url.trim().trim_end_matches('/').rsplit_once("/issues/")?;
+    number.parse().ok()
+}
+
+/// Set the milestone of an issue by the title of the milestone
+pub fn set_issue_milestone(repo: &str, number: u64, milestone: &str) -> Result<(), Box<dyn Error>> {
+    run_gh(
+        repo_command(repo)
+            .arg("issue")
+            .arg("edit")
+            .arg(number.to_string())
+            .arg("--repo")
+            .arg(repo)
+            .arg("--milestone")
+            .arg(milestone),
+        format!("Failed to set milestone {milestone} on issue #{number}"),
+    )?;
+
+    Ok(())
+}
+
+/// Add an issue to a GitHub Project (v2) and return the ID of the project item
+///
+/// Requires the `project` scope, e.g. `gh auth refresh -s project`
+pub fn add_issue_to_project(
+    host: Option<&str>,
+    project: &ProjectRef,
+    issue_url: &str,
+) -> Result<String, Box<dyn Error>> {
+    let output = run_gh(
+        gh_command(host)
+            .arg("project")
+            .arg("item-add")
+            .arg(project.number.to_string())
+            .arg("--owner")
+            .arg(&project.owner)
+            .arg("--url")
+            .arg(issue_url)
+            .arg("--format")
+            .arg("json"),
+        format!("Failed to add {issue_url} to project {project}"),
+    )?;
+
+    #[derive(Deserialize)]
+    struct ProjectItem {
+        id: String,
     }
+    let item: ProjectItem = serde_json::from_slice(&output)?;
+    Ok(item.id)
+}
 
-    let parsed: Vec<GhIssueBody> = serde_json::from_str(&output)?;
-    Ok(parsed.into_iter().map(|item| item.body).collect())
+/// Set a field of a project item by the names of the field and (for single select fields) option
+///
+/// Requires the `project` scope, e.g. `gh auth refresh -s project`
+pub fn set_project_item_field(
+    host: Option<&str>,
+    project: &ProjectRef,
+    item_id: &str,
+    field: &str,
+    value: &str,
+) -> Result<(), Box<dyn Error>> {
+    #[derive(Deserialize)]
+    struct Project {
+        id: String,
+    }
+    #[derive(Deserialize)]
+    struct FieldOption {
+        id: String,
+        name: String,
+    }
+    #[derive(Deserialize)]
+    struct Field {
+        id: String,
+        name: String,
+        #[serde(default)]
+        options: Vec<FieldOption>,
+    }
+    #[derive(Deserialize)]
+    struct FieldList {
+        fields: Vec<Field>,
+    }
+
+    let project_json = |subcommand: &str| -> Result<Vec<u8>, Box<dyn Error>> {
+        let output = run_gh(
+            gh_command(host)
+                .arg("project")
+                .arg(subcommand)
+                .arg(project.number.to_string())
+                .arg("--owner")
+                .arg(&project.owner)
+                .arg("--format")
+                .arg("json"),
+            format!("Failed to get project {project}"),
+        )?;
+        Ok(output)
+    };
+    let project_id = serde_json::from_slice::<Project>(&project_json("view")?)?.id;
+    let fields = serde_json::from_slice::<FieldList>(&project_json("field-list")?)?.fields;
+    let field = fields
+        .iter()
+        .find(|f| f.name == field)
+        .ok_or_else(|| format!("Project {project} has no field named {field}"))?;
+
+    let mut cmd = gh_command(host);
+    cmd.arg("project")
+        .arg("item-edit")
+        .arg("--id")
+        .arg(item_id)
+        .arg("--project-id")
+        .arg(&project_id)
+        .arg("--field-id")
+        .arg(&field.id);
+    if field.options.is_empty() {
+        cmd.arg("--text").arg(value);
+    } else {
+        let option = field
+            .options
+            .iter()
+            .find(|option| option.name == value)
+            .ok_or_else(|| {
+                format!(
+                    "Field {} of project {project} has no option {value}",
+                    field.name
+                )
+            })?;
+        cmd.arg("--single-select-option-id").arg(&option.id);
+    }
+
+    run_gh(
+        &mut cmd,
+        format!(
+            "Failed to set field {field} of project item {item_id}",
+            field = field.name
+        ),
+    )?;
+
+    Ok(())
 }
 
-/// Get all labels in a GitHub repository
-pub fn all_labels(repo: &str) -> Result<Vec<String>, Box<dyn Error>> {
-    let output = Command::new(gh_cli())
-        .arg("--repo")
-        .arg(repo)
-        .arg("label")
-        .arg("list")
-        .arg("--json")
-        .arg("name")
-        .output()?;
+/// The host and `owner/repo` of a repository URL e.g. `("github.com", "luftkode/distro-template")`
+///
+/// # Example
+/// ```
+/// # use gh_workflow_parser::gh::util::repo_host_and_slug;
+/// assert_eq!(
+///     repo_host_and_slug("https://github.com/luftkode/distro-template"),
+///     Some(("github.com", "luftkode/distro-template"))
+/// );
+/// assert_eq!(repo_host_and_slug("distro-template"), None);
+/// ```
+pub fn repo_host_and_slug(repo_url: &str) -> Option<(&str, &str)> {
+    let without_scheme = repo_url
+        .split_once("://")
+        .map_or(repo_url, |(_scheme, rest)| rest)
+        .trim_end_matches('/');
+    let (host, slug) = without_scheme.split_once('/')?;
+    slug.contains('/').then_some((host, slug))
+}
 
-    assert!(
-        output.status.success(),
-        "Failed to list labels. Failure: {stderr}",
-        stderr = String::from_utf8_lossy(&output.stderr)
+/// Get the contents of a file on the default branch of the repository, `None` if it doesn't exist
+pub fn repo_file(repo: &str, path: &str) -> Result<Option<String>, Box<dyn Error>> {
+    let (host, slug) =
+        repo_host_and_slug(repo).ok_or_else(|| format!("Invalid repository URL: {repo}"))?;
+    let output = run_gh(
+        repo_command(repo)
+            .arg("api")
+            .arg("--hostname")
+            .arg(host)
+            .arg("-H")
+            .arg("Accept: application/vnd.github.raw")
+            .arg(format!("repos/{slug}/contents/{path}")),
+        format!("Failed to get {path} from {repo}"),
     );
 
+    match output {
+        Ok(output) => Ok(Some(String::from_utf8_lossy(&output).to_string())),
+        Err(e) if e.detail().contains("Not Found") || e.detail().contains("HTTP 404") => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Get all labels in a GitHub repository
+pub fn all_labels(repo: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let output = run_gh(
+        repo_command(repo)
+            .arg("--repo")
+            .arg(repo)
+            .arg("label")
+            .arg("list")
+            .arg("--json")
+            .arg("name"),
+        "Failed to list labels",
+    )?;
+
     // Parse the received JSON vector of objects with a `name` field
-    let output = String::from_utf8_lossy(&output.stdout);
+    let output = String::from_utf8_lossy(&output);
     #[derive(Serialize, Deserialize)]
     struct Label {
         name: String,
@@ -173,7 +815,7 @@ pub fn create_label(
     description: &str,
     force: bool,
 ) -> Result<(), Box<dyn Error>> {
-    let mut cmd = Command::new(gh_cli());
+    let mut cmd = repo_command(repo);
     cmd.arg("label")
         .arg("create")
         .arg(name)
@@ -188,31 +830,147 @@ pub fn create_label(
         cmd.arg("--force");
     }
 
-    let output = cmd.output()?;
-    assert!(
-        output.status.success(),
-        "Failed to create label. Failure: {stderr}",
-        stderr = String::from_utf8_lossy(&output.stderr)
-    );
+    run_gh(&mut cmd, "Failed to create label")?;
+
+    Ok(())
+}
+
+/// Add a comment to an issue in the GitHub repository
+pub fn add_issue_comment(repo: &str, number: u64, body: &str) -> Result<(), Box<dyn Error>> {
+    run_gh(
+        repo_command(repo)
+            .arg("issue")
+            .arg("comment")
+            .arg(number.to_string())
+            .arg("--repo")
+            .arg(repo)
+            .arg("--body")
+            .arg(body),
+        format!("Failed to comment on issue #{number}"),
+    )?;
 
     Ok(())
 }
 
+/// List the bodies of the comments on an issue in the GitHub repository, oldest first
+pub fn issue_comments(repo: &str, number: u64) -> Result<Vec<String>, Box<dyn Error>> {
+    let output = run_gh(
+        repo_command(repo)
+            .arg("issue")
+            .arg("view")
+            .arg(number.to_string())
+            .arg("--repo")
+            .arg(repo)
+            .arg("--json")
+            .arg("comments"),
+        format!("Failed to list the comments on issue #{number}"),
+    )?;
+
+    #[derive(Deserialize)]
+    struct Comment {
+        body: String,
+    }
+    #[derive(Deserialize)]
+    struct IssueComments {
+        comments: Vec<Comment>,
+    }
+    Ok(serde_json::from_slice::<IssueComments>(&output)?
+        .comments
+        .into_iter()
+        .map(|comment| comment.body)
+        .collect())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use pretty_assertions::assert_eq;
 
+    #[cfg(unix)]
+    #[test]
+    fn test_command_output_checks_status() {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg("echo log; echo oops >&2; exit 1");
+        let mut output = CommandOutput::spawn(cmd, "Failed".to_string()).unwrap();
+        let mut log = String::new();
+        let err = output.read_to_string(&mut log).unwrap_err();
+        assert_eq!(log, "log\n");
+        assert_eq!(err.to_string(), "Failed. Failure: oops\n");
+
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg("echo log");
+        let mut output = CommandOutput::spawn(cmd, "Failed".to_string()).unwrap();
+        let mut log = String::new();
+        output.read_to_string(&mut log).unwrap();
+        assert_eq!(log, "log\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_command_output_times_out() {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg("echo log; exec sleep 10");
+        let started = Instant::now();
+        let mut output = CommandOutput::spawn_with_timeout(
+            cmd,
+            "Failed".to_string(),
+            Some(Duration::from_millis(200)),
+        )
+        .unwrap();
+        let mut log = String::new();
+        let err = output.read_to_string(&mut log).unwrap_err();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(log, "log\n");
+        assert!(err.to_string().contains("Timed out"), "{err}");
+        let err = GhCliError::find(&err).unwrap();
+        assert_eq!(err.kind(), GhCliErrorKind::Transient);
+    }
+
+    #[test]
+    fn test_issue_list_command_has_limit() {
+        let cmd = issue_list_command("https://github.com/luftkode/distro-template", "bug");
+        let args: Vec<_> = cmd.get_args().collect();
+        let limit = args.iter().position(|arg| *arg == "--limit").unwrap();
+        assert_eq!(args[limit + 1], ISSUES_LIMIT.to_string().as_str());
+    }
+
+    #[test]
+    fn test_commands_for_the_host_of_the_repository() {
+        let cmd = repo_command("https://github.example.com/luftkode/distro-template");
+        assert_eq!(command_host(&cmd).as_deref(), Some("github.example.com"));
+        assert_eq!(command_host(&gh_command(None)), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_output_with_timeout() {
+        let output = output_with_timeout(
+            Command::new("sh").arg("-c").arg("echo out; echo err >&2"),
+            Some(Duration::from_secs(10)),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(output.stdout, b"out\n");
+        assert_eq!(output.stderr, b"err\n");
+
+        let output = output_with_timeout(
+            Command::new("sh").arg("-c").arg("sleep 10"),
+            Some(Duration::from_millis(100)),
+        )
+        .unwrap();
+        assert!(output.is_none());
+    }
+
     #[test]
     #[ignore = "This test requires a GitHub repository"]
     fn test_issue_body_display() {
-        let issue_bodies = issue_bodies_open_with_label(
+        let issues = issues_open_with_label(
             "https://github.com/luftkode/distro-template",
             "CI scheduled build",
         )
         .unwrap();
-        for body in issue_bodies {
-            println!("{body}");
+        for issue in issues {
+            println!("{}", issue.body);
         }
     }
 
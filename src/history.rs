@@ -0,0 +1,648 @@
+//! Local database of analyzed failures
+//!
+//! With `--history-db <file>` every analyzed failure is recorded in a local SQLite database, one row
+//! per failed job and run. Green runs are recorded with `history sync`, so the time from the first
+//! failure of a job to the next green run of its workflow (the time to recovery) can be computed.
+//!
+//! The database is queried with the `history` subcommands: the jobs and fingerprints that fail most
+//! often, the failure timeline of a job and the mean time to recovery per job.
+use std::{collections::BTreeMap, error::Error, path::Path};
+
+use rusqlite::{params, Connection, Row};
+use serde::Serialize;
+
+use crate::{gh::GhRun, issue::Issue, util::unix_seconds_from_iso};
+
+/// Version of the database schema, stored in `PRAGMA user_version`
+pub const SCHEMA_VERSION: i64 = 1;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    repo TEXT NOT NULL,
+    run_id TEXT NOT NULL,
+    workflow TEXT NOT NULL,
+    branch TEXT NOT NULL,
+    conclusion TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    PRIMARY KEY (repo, run_id)
+);
+CREATE TABLE IF NOT EXISTS failures (
+    repo TEXT NOT NULL,
+    run_id TEXT NOT NULL,
+    run_link TEXT NOT NULL,
+    workflow TEXT NOT NULL,
+    branch TEXT NOT NULL,
+    job_id TEXT NOT NULL,
+    job_name TEXT NOT NULL,
+    job_fingerprint TEXT NOT NULL,
+    issue_fingerprint TEXT NOT NULL,
+    parser TEXT NOT NULL,
+    failure_label TEXT,
+    recipe TEXT,
+    labels TEXT NOT NULL,
+    issue_url TEXT,
+    failed_at TEXT NOT NULL,
+    PRIMARY KEY (repo, run_id, job_id)
+);
+CREATE INDEX IF NOT EXISTS failures_by_job ON failures (job_name, failed_at);
+CREATE INDEX IF NOT EXISTS failures_by_fingerprint ON failures (job_fingerprint);
+";
+
+/// A failed job of a run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FailureRecord {
+    pub repo: String,
+    pub run_id: String,
+    pub run_link: String,
+    pub workflow: String,
+    pub branch: String,
+    pub job_id: String,
+    pub job_name: String,
+    pub job_fingerprint: String,
+    /// Fingerprint of the issue the job was part of
+    pub issue_fingerprint: String,
+    /// The parser that produced the error summary e.g. `yocto`
+    pub parser: String,
+    pub failure_label: Option<String>,
+    pub recipe: Option<String>,
+    pub labels: Vec<String>,
+    /// The issue created for the failure, or the existing issue it's a duplicate of
+    pub issue_url: Option<String>,
+    /// When the run was created (ISO 8601)
+    pub failed_at: String,
+}
+
+impl FailureRecord {
+    /// A record for each failed job of the issue
+    pub fn from_issue(
+        repo: &str,
+        issue: &Issue,
+        failed_at: &str,
+        issue_url: Option<&str>,
+    ) -> Vec<Self> {
+        issue
+            .failed_jobs()
+            .iter()
+            .map(|job| Self {
+                repo: repo.to_string(),
+                run_id: issue.run_id().to_string(),
+                run_link: issue.run_link().trim().to_string(),
+                workflow: issue.workflow().to_string(),
+                branch: issue.branch().to_string(),
+                job_id: job.id().to_string(),
+                job_name: job.name().to_string(),
+                job_fingerprint: job.fingerprint().to_string(),
+                issue_fingerprint: issue.fingerprint().to_string(),
+                parser: job.error_message().parser().to_string(),
+                failure_label: job.failure_label(),
+                recipe: job.error_message().recipe(),
+                labels: issue.labels().to_vec(),
+                issue_url: issue_url.map(str::to_string),
+                failed_at: failed_at.to_string(),
+            })
+            .collect()
+    }
+
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let labels: String = row.get("labels")?;
+        Ok(Self {
+            repo: row.get("repo")?,
+            run_id: row.get("run_id")?,
+            run_link: row.get("run_link")?,
+            workflow: row.get("workflow")?,
+            branch: row.get("branch")?,
+            job_id: row.get("job_id")?,
+            job_name: row.get("job_name")?,
+            job_fingerprint: row.get("job_fingerprint")?,
+            issue_fingerprint: row.get("issue_fingerprint")?,
+            parser: row.get("parser")?,
+            failure_label: row.get("failure_label")?,
+            recipe: row.get("recipe")?,
+            labels: serde_json::from_str(&labels).unwrap_or_default(),
+            issue_url: row.get("issue_url")?,
+            failed_at: row.get("failed_at")?,
+        })
+    }
+}
+
+/// Which failures a query looks at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryQuery {
+    /// Only failures in this repository
+    pub repo: Option<String>,
+    /// Only failures on or after this date (YYYY-MM-DD)
+    pub since: Option<String>,
+    /// Maximum number of results
+    pub limit: usize,
+}
+
+impl Default for HistoryQuery {
+    fn default() -> Self {
+        Self {
+            repo: None,
+            since: None,
+            limit: 10,
+        }
+    }
+}
+
+/// How often a job failed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct JobFailures {
+    pub job_name: String,
+    pub workflow: String,
+    pub failures: usize,
+    pub first_failed_at: String,
+    pub last_failed_at: String,
+}
+
+/// How often a failure with the same fingerprint occurred
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FingerprintFailures {
+    pub fingerprint: String,
+    pub failures: usize,
+    pub last_failed_at: String,
+    /// The job, failure label, recipe and issue of the last occurrence
+    pub job_name: String,
+    pub failure_label: Option<String>,
+    pub recipe: Option<String>,
+    pub issue_url: Option<String>,
+}
+
+/// The time to recovery of a job: from its first failure to the next green run of the workflow
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Recovery {
+    pub job_name: String,
+    pub workflow: String,
+    pub branch: String,
+    pub failures: usize,
+    /// How many times the job recovered after failing
+    pub recoveries: usize,
+    /// Mean time to recovery in seconds
+    pub mean_seconds: Option<i64>,
+    /// When the job started failing if it hasn't recovered yet
+    pub failing_since: Option<String>,
+}
+
+/// The failure history database
+#[derive(Debug)]
+pub struct HistoryDb {
+    conn: Connection,
+}
+
+impl HistoryDb {
+    /// Open the database, creating it if it doesn't exist
+    pub fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let conn = Connection::open(path)
+            .map_err(|e| format!("Could not open history database {}: {e}", path.display()))?;
+        Self::init(conn)
+    }
+
+    /// Open a database that is only kept in memory
+    pub fn open_in_memory() -> Result<Self, Box<dyn Error>> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self, Box<dyn Error>> {
+        let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version > SCHEMA_VERSION {
+            return Err(format!(
+                "The history database has schema version {version}, this version of gh-workflow-parser supports up to {SCHEMA_VERSION}"
+            )
+            .into());
+        }
+        conn.execute_batch(SCHEMA)?;
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        Ok(Self { conn })
+    }
+
+    /// Record failed jobs, replacing earlier records of the same job in the same run
+    ///
+    /// The runs of the failures are recorded as failed unless they are already recorded.
+    pub fn record_failures(&mut self, records: &[FailureRecord]) -> Result<(), Box<dyn Error>> {
+        let tx = self.conn.transaction()?;
+        for record in records {
+            tx.execute(
+                "INSERT OR REPLACE INTO failures (repo, run_id, run_link, workflow, branch, job_id,
+                    job_name, job_fingerprint, issue_fingerprint, parser, failure_label, recipe,
+                    labels, issue_url, failed_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+                params![
+                    record.repo,
+                    record.run_id,
+                    record.run_link,
+                    record.workflow,
+                    record.branch,
+                    record.job_id,
+                    record.job_name,
+                    record.job_fingerprint,
+                    record.issue_fingerprint,
+                    record.parser,
+                    record.failure_label,
+                    record.recipe,
+                    serde_json::to_string(&record.labels)?,
+                    record.issue_url,
+                    record.failed_at,
+                ],
+            )?;
+            tx.execute(
+                "INSERT OR IGNORE INTO runs (repo, run_id, workflow, branch, conclusion, created_at)
+                VALUES (?1, ?2, ?3, ?4, 'failure', ?5)",
+                params![
+                    record.repo,
+                    record.run_id,
+                    record.workflow,
+                    record.branch,
+                    record.failed_at
+                ],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Record runs with their conclusion, replacing earlier records of the same runs
+    pub fn record_runs(&mut self, repo: &str, runs: &[GhRun]) -> Result<(), Box<dyn Error>> {
+        let tx = self.conn.transaction()?;
+        for run in runs {
+            tx.execute(
+                "INSERT OR REPLACE INTO runs (repo, run_id, workflow, branch, conclusion, created_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    repo,
+                    run.database_id.to_string(),
+                    run.workflow_name,
+                    run.head_branch,
+                    run.conclusion,
+                    run.created_at
+                ],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// The jobs that failed most often
+    pub fn top_jobs(&self, query: &HistoryQuery) -> Result<Vec<JobFailures>, Box<dyn Error>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT job_name, workflow, COUNT(*), MIN(failed_at), MAX(failed_at) FROM failures
+            WHERE (?1 IS NULL OR repo = ?1) AND (?2 IS NULL OR failed_at >= ?2)
+            GROUP BY job_name, workflow
+            ORDER BY COUNT(*) DESC, MAX(failed_at) DESC
+            LIMIT ?3",
+        )?;
+        let jobs = stmt
+            .query_map(
+                params![query.repo, query.since, query.limit as i64],
+                |row| {
+                    Ok(JobFailures {
+                        job_name: row.get(0)?,
+                        workflow: row.get(1)?,
+                        failures: row.get(2)?,
+                        first_failed_at: row.get(3)?,
+                        last_failed_at: row.get(4)?,
+                    })
+                },
+            )?
+            .collect::<Result<_, _>>()?;
+        Ok(jobs)
+    }
+
+    /// The failures that occurred most often, grouped by the fingerprint of the failed job
+    pub fn top_fingerprints(
+        &self,
+        query: &HistoryQuery,
+    ) -> Result<Vec<FingerprintFailures>, Box<dyn Error>> {
+        // With a single MAX() aggregate, SQLite takes the other columns from the row with the max
+        let mut stmt = self.conn.prepare(
+            "SELECT job_fingerprint, COUNT(*), MAX(failed_at), job_name, failure_label, recipe,
+                issue_url
+            FROM failures
+            WHERE (?1 IS NULL OR repo = ?1) AND (?2 IS NULL OR failed_at >= ?2)
+            GROUP BY job_fingerprint
+            ORDER BY COUNT(*) DESC, MAX(failed_at) DESC
+            LIMIT ?3",
+        )?;
+        let fingerprints = stmt
+            .query_map(
+                params![query.repo, query.since, query.limit as i64],
+                |row| {
+                    Ok(FingerprintFailures {
+                        fingerprint: row.get(0)?,
+                        failures: row.get(1)?,
+                        last_failed_at: row.get(2)?,
+                        job_name: row.get(3)?,
+                        failure_label: row.get(4)?,
+                        recipe: row.get(5)?,
+                        issue_url: row.get(6)?,
+                    })
+                },
+            )?
+            .collect::<Result<_, _>>()?;
+        Ok(fingerprints)
+    }
+
+    /// The failures of a job, oldest first
+    ///
+    /// The limit of the query applies to the most recent failures.
+    pub fn timeline(
+        &self,
+        job_name: &str,
+        query: &HistoryQuery,
+    ) -> Result<Vec<FailureRecord>, Box<dyn Error>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT * FROM (
+                SELECT * FROM failures
+                WHERE job_name = ?1 AND (?2 IS NULL OR repo = ?2) AND (?3 IS NULL OR failed_at >= ?3)
+                ORDER BY failed_at DESC
+                LIMIT ?4
+            ) ORDER BY failed_at",
+        )?;
+        let records = stmt
+            .query_map(
+                params![job_name, query.repo, query.since, query.limit as i64],
+                FailureRecord::from_row,
+            )?
+            .collect::<Result<_, _>>()?;
+        Ok(records)
+    }
+
+    /// The mean time to recovery of each failed job, optionally only of one job
+    ///
+    /// Jobs are ordered by the mean time to recovery, longest first. Jobs that haven't recovered
+    /// come first.
+    pub fn mttr(
+        &self,
+        job_name: Option<&str>,
+        query: &HistoryQuery,
+    ) -> Result<Vec<Recovery>, Box<dyn Error>> {
+        type Key = (String, String, String, String);
+        let mut failures: BTreeMap<Key, Vec<String>> = BTreeMap::new();
+        let mut stmt = self.conn.prepare(
+            "SELECT repo, workflow, branch, job_name, failed_at FROM failures
+            WHERE (?1 IS NULL OR job_name = ?1) AND (?2 IS NULL OR repo = ?2)
+                AND (?3 IS NULL OR failed_at >= ?3)
+            ORDER BY failed_at",
+        )?;
+        let mut rows = stmt.query(params![job_name, query.repo, query.since])?;
+        while let Some(row) = rows.next()? {
+            failures
+                .entry((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+                .or_default()
+                .push(row.get(4)?);
+        }
+
+        let mut green_runs = self.conn.prepare(
+            "SELECT created_at FROM runs
+            WHERE repo = ?1 AND workflow = ?2 AND branch = ?3 AND conclusion = 'success'
+            ORDER BY created_at",
+        )?;
+        let mut recoveries = vec![];
+        for ((repo, workflow, branch, job_name), failed_at) in failures {
+            let green_at: Vec<String> = green_runs
+                .query_map(params![repo, workflow, branch], |row| row.get(0))?
+                .collect::<Result<_, _>>()?;
+            let (durations, failing_since) = recovery_durations(&failed_at, &green_at);
+            recoveries.push(Recovery {
+                job_name,
+                workflow,
+                branch,
+                failures: failed_at.len(),
+                recoveries: durations.len(),
+                mean_seconds: (!durations.is_empty())
+                    .then(|| durations.iter().sum::<i64>() / durations.len() as i64),
+                failing_since,
+            });
+        }
+        recoveries.sort_by_key(|recovery| {
+            (
+                recovery.failing_since.is_none(),
+                std::cmp::Reverse(recovery.mean_seconds),
+            )
+        });
+        recoveries.truncate(query.limit);
+        Ok(recoveries)
+    }
+}
+
+/// The durations (in seconds) from the first failure of each failure streak to the first green run
+/// after it, and the start of the current streak if the last failure isn't followed by a green run
+///
+/// Both lists are ISO 8601 timestamps sorted oldest first, unparseable timestamps are ignored.
+///
+/// # Example
+/// ```
+/// # use gh_workflow_parser::history::recovery_durations;
+/// let failed_at = ["2024-02-10T00:00:00Z", "2024-02-10T12:00:00Z", "2024-02-12T00:00:00Z"];
+/// let green_at = ["2024-02-11T00:00:00Z"];
+/// let (durations, failing_since) = recovery_durations(&failed_at, &green_at);
+/// assert_eq!(durations, [24 * 60 * 60]);
+/// assert_eq!(failing_since.as_deref(), Some("2024-02-12T00:00:00Z"));
+/// ```
+pub fn recovery_durations<F: AsRef<str>, G: AsRef<str>>(
+    failed_at: &[F],
+    green_at: &[G],
+) -> (Vec<i64>, Option<String>) {
+    let green_at: Vec<i64> = green_at
+        .iter()
+        .filter_map(|at| unix_seconds_from_iso(at.as_ref()))
+        .collect();
+    let mut durations = vec![];
+    let mut streak_start: Option<(i64, &str)> = None;
+    for failure in failed_at {
+        let Some(failure_secs) = unix_seconds_from_iso(failure.as_ref()) else {
+            continue;
+        };
+        if let Some((start, _)) = streak_start {
+            match green_at.iter().find(|&&green| green > start) {
+                Some(&green) if green <= failure_secs => durations.push(green - start),
+                // Still failing
+                _ => continue,
+            }
+        }
+        streak_start = Some((failure_secs, failure.as_ref()));
+    }
+    let failing_since = match streak_start {
+        Some((start, at)) => match green_at.iter().find(|&&green| green > start) {
+            Some(&green) => {
+                durations.push(green - start);
+                None
+            },
+            None => Some(at.to_string()),
+        },
+        None => None,
+    };
+    (durations, failing_since)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const REPO: &str = "https://github.com/luftkode/distro-template";
+
+    fn record(run_id: &str, job_name: &str, fingerprint: &str, failed_at: &str) -> FailureRecord {
+        FailureRecord {
+            repo: REPO.to_string(),
+            run_id: run_id.to_string(),
+            run_link: format!("{REPO}/actions/runs/{run_id}"),
+            workflow: "Build".to_string(),
+            branch: "main".to_string(),
+            job_id: format!("{run_id}-{job_name}"),
+            job_name: job_name.to_string(),
+            job_fingerprint: fingerprint.to_string(),
+            issue_fingerprint: fingerprint.to_string(),
+            parser: "yocto".to_string(),
+            failure_label: Some("do_fetch".to_string()),
+            recipe: None,
+            labels: vec!["bug".to_string(), "do_fetch".to_string()],
+            issue_url: Some(format!("{REPO}/issues/1")),
+            failed_at: failed_at.to_string(),
+        }
+    }
+
+    fn green_run(run_id: u64, created_at: &str) -> GhRun {
+        GhRun {
+            database_id: run_id,
+            display_title: "Build".to_string(),
+            workflow_name: "Build".to_string(),
+            head_branch: "main".to_string(),
+            conclusion: "success".to_string(),
+            created_at: created_at.to_string(),
+            url: String::new(),
+            head_sha: String::new(),
+            attempt: 1,
+        }
+    }
+
+    fn example_db() -> HistoryDb {
+        let mut db = HistoryDb::open_in_memory().unwrap();
+        db.record_failures(&[
+            record("1", "build", "aaa", "2024-02-10T00:00:00Z"),
+            record("2", "build", "aaa", "2024-02-10T06:00:00Z"),
+            record("2", "test", "bbb", "2024-02-10T06:00:00Z"),
+            record("4", "build", "ccc", "2024-02-12T00:00:00Z"),
+        ])
+        .unwrap();
+        db.record_runs(REPO, &[green_run(3, "2024-02-11T00:00:00Z")])
+            .unwrap();
+        db
+    }
+
+    #[test]
+    fn test_top_jobs() {
+        let db = example_db();
+        let jobs = db.top_jobs(&HistoryQuery::default()).unwrap();
+        assert_eq!(jobs.len(), 2);
+        assert_eq!(jobs[0].job_name, "build");
+        assert_eq!(jobs[0].failures, 3);
+        assert_eq!(jobs[0].first_failed_at, "2024-02-10T00:00:00Z");
+        assert_eq!(jobs[0].last_failed_at, "2024-02-12T00:00:00Z");
+
+        let query = HistoryQuery {
+            since: Some("2024-02-11".to_string()),
+            ..Default::default()
+        };
+        let jobs = db.top_jobs(&query).unwrap();
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].failures, 1);
+    }
+
+    #[test]
+    fn test_top_fingerprints() {
+        let db = example_db();
+        let fingerprints = db.top_fingerprints(&HistoryQuery::default()).unwrap();
+        let counts: Vec<(&str, usize)> = fingerprints
+            .iter()
+            .map(|f| (f.fingerprint.as_str(), f.failures))
+            .collect();
+        assert_eq!(counts, [("aaa", 2), ("ccc", 1), ("bbb", 1)]);
+        assert_eq!(fingerprints[0].last_failed_at, "2024-02-10T06:00:00Z");
+        assert_eq!(fingerprints[0].job_name, "build");
+    }
+
+    #[test]
+    fn test_timeline() {
+        let db = example_db();
+        let timeline = db.timeline("build", &HistoryQuery::default()).unwrap();
+        let runs: Vec<&str> = timeline.iter().map(|r| r.run_id.as_str()).collect();
+        assert_eq!(runs, ["1", "2", "4"]);
+        assert_eq!(
+            timeline[0],
+            record("1", "build", "aaa", "2024-02-10T00:00:00Z")
+        );
+
+        let query = HistoryQuery {
+            limit: 1,
+            ..Default::default()
+        };
+        let timeline = db.timeline("build", &query).unwrap();
+        assert_eq!(timeline[0].run_id, "4");
+    }
+
+    #[test]
+    fn test_mttr() {
+        let db = example_db();
+        let recoveries = db.mttr(None, &HistoryQuery::default()).unwrap();
+        assert_eq!(
+            recoveries,
+            [
+                Recovery {
+                    job_name: "build".to_string(),
+                    workflow: "Build".to_string(),
+                    branch: "main".to_string(),
+                    failures: 3,
+                    recoveries: 1,
+                    mean_seconds: Some(24 * 60 * 60),
+                    failing_since: Some("2024-02-12T00:00:00Z".to_string()),
+                },
+                Recovery {
+                    job_name: "test".to_string(),
+                    workflow: "Build".to_string(),
+                    branch: "main".to_string(),
+                    failures: 1,
+                    recoveries: 1,
+                    mean_seconds: Some(18 * 60 * 60),
+                    failing_since: None,
+                },
+            ]
+        );
+        assert_eq!(
+            db.mttr(Some("test"), &HistoryQuery::default())
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_record_failures_replaces_the_same_job() {
+        let mut db = example_db();
+        let mut updated = record("1", "build", "aaa", "2024-02-10T00:00:00Z");
+        updated.issue_url = Some(format!("{REPO}/issues/2"));
+        db.record_failures(&[updated]).unwrap();
+        assert_eq!(
+            db.top_jobs(&HistoryQuery::default()).unwrap()[0].failures,
+            3
+        );
+        let timeline = db.timeline("build", &HistoryQuery::default()).unwrap();
+        assert_eq!(
+            timeline[0].issue_url.as_deref(),
+            Some("https://github.com/luftkode/distro-template/issues/2")
+        );
+    }
+
+    #[test]
+    fn test_reopen_database() {
+        let dir = temp_dir::TempDir::new().unwrap();
+        let path = dir.path().join("history.db");
+        {
+            let mut db = HistoryDb::open(&path).unwrap();
+            db.record_failures(&[record("1", "build", "aaa", "2024-02-10T00:00:00Z")])
+                .unwrap();
+        }
+        let db = HistoryDb::open(&path).unwrap();
+        assert_eq!(db.top_jobs(&HistoryQuery::default()).unwrap().len(), 1);
+    }
+}
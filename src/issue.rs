@@ -4,21 +4,65 @@
 //! in a repository. It contains a title, label, and body. The body is a
 //! collection of FailedJob structs, which contain information about the failed
 //! jobs in a GitHub Actions workflow run.
-use std::fmt::{self, Display, Formatter, Write};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
 
-use crate::err_msg_parse::ErrorMessageSummary;
+use minijinja::{context, Value};
+use serde::Serialize;
 
-#[derive(Debug)]
+use crate::{
+    err_msg_parse::ErrorMessageSummary,
+    flaky::{Flakiness, FLAKY_LABEL},
+    util,
+};
+
+use self::{metadata::IssueMetadata, template::IssueTemplates};
+
+pub mod metadata;
+pub mod template;
+
+/// A markdown list with an item for each line
+pub(crate) fn markdown_list(items: &[String]) -> String {
+    items
+        .iter()
+        .map(|item| format!("- {item}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Maximum length (in characters) of a GitHub issue title
+pub const ISSUE_TITLE_MAX_LEN: usize = 256;
+
+#[derive(Debug, Serialize)]
 pub struct Issue {
     title: String,
     labels: Vec<String>,
+    #[serde(flatten)]
     body: IssueBody,
+    /// URL of the closed issue this failure is a regression of
+    #[serde(skip_serializing_if = "Option::is_none")]
+    regression_of: Option<String>,
+    /// GitHub users and teams owning the failure
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    owners: Vec<String>,
+    /// Links to all runs with the same failure when triaging several runs
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    affected_runs: Vec<String>,
+    /// Flakiness of the failed jobs if flaky jobs were detected
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    flakiness: Vec<Flakiness>,
+    #[serde(skip)]
+    templates: IssueTemplates,
 }
 
 impl Issue {
+    /// Create a new issue, the title is generated from the failed jobs e.g.
+    /// `[Yocto do_fetch] sqlite3-native failed in "Test template xilinx" (master)`
     pub fn new(
         run_id: String,
         run_link: String,
+        workflow: String,
+        branch: String,
         failed_jobs: Vec<FailedJob>,
         label: String,
     ) -> Self {
@@ -29,13 +73,69 @@ impl Issue {
                 labels.push(failure_label);
             }
         });
+        let body = IssueBody::new(run_id, run_link, workflow, branch, failed_jobs);
         Self {
-            title: "Scheduled run failed".to_string(),
+            title: truncate_title(&body.default_title()),
             labels,
-            body: IssueBody::new(run_id, run_link, failed_jobs),
+            body,
+            regression_of: None,
+            owners: vec![],
+            affected_runs: vec![],
+            flakiness: vec![],
+            templates: IssueTemplates::default(),
         }
     }
 
+    /// Use the given templates to render the issue title and body
+    ///
+    /// The title is rendered with the current labels, apply the templates again after changing the
+    /// labels or flakiness.
+    ///
+    /// # Errors
+    /// Returns an error if the templates fail to render with the issue as context
+    pub fn with_templates(mut self, templates: IssueTemplates) -> Result<Self, Box<dyn Error>> {
+        let ctx = self.template_context();
+        if let Some(title) = templates.render_title(&ctx)? {
+            self.title = truncate_title(&title);
+        }
+        // Render the body once up front to surface template errors
+        templates.render_body(&ctx)?;
+        self.templates = templates;
+        Ok(self)
+    }
+
+    /// Split the issue into one issue per failed job, each with its own title, labels and
+    /// fingerprint, rendered with the same templates
+    ///
+    /// # Errors
+    /// Returns an error if the templates fail to render for a job
+    pub fn into_per_job_issues(self) -> Result<Vec<Issue>, Box<dyn Error>> {
+        // The first label is the one the issue was created with, the rest are failure labels
+        let label = self.labels.into_iter().next().unwrap_or_default();
+        let IssueBody {
+            run_id,
+            run_link,
+            workflow,
+            branch,
+            failed_jobs,
+            ..
+        } = self.body;
+        failed_jobs
+            .into_iter()
+            .map(|job| {
+                Issue::new(
+                    run_id.clone(),
+                    run_link.clone(),
+                    workflow.clone(),
+                    branch.clone(),
+                    vec![job],
+                    label.clone(),
+                )
+                .with_templates(self.templates.clone())
+            })
+            .collect()
+    }
+
     pub fn title(&self) -> &str {
         self.title.as_str()
     }
@@ -44,69 +144,271 @@ impl Issue {
         self.labels.as_slice()
     }
 
-    pub fn body(&self) -> String {
-        self.body.to_string()
+    /// Replace the labels, the first label is expected to be the issue label
+    pub fn with_labels(mut self, labels: Vec<String>) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Fingerprint of the failure that is stable across runs, derived from the fingerprints of the
+    /// failed jobs
+    pub fn fingerprint(&self) -> &str {
+        &self.body.fingerprint
+    }
+
+    pub fn run_id(&self) -> &str {
+        &self.body.run_id
+    }
+
+    pub fn run_link(&self) -> &str {
+        &self.body.run_link
+    }
+
+    pub fn workflow(&self) -> &str {
+        &self.body.workflow
+    }
+
+    pub fn branch(&self) -> &str {
+        &self.body.branch
+    }
+
+    pub fn failed_jobs(&self) -> &[FailedJob] {
+        &self.body.failed_jobs
+    }
+
+    /// Set the owners of the failure, users are assigned and all owners are mentioned in the body
+    pub fn with_owners(mut self, owners: Vec<String>) -> Self {
+        self.owners = owners;
+        self
+    }
+
+    pub fn owners(&self) -> &[String] {
+        &self.owners
+    }
+
+    /// The owners that can be assigned to the issue (users without the `@`)
+    pub fn assignees(&self) -> Vec<String> {
+        crate::owners::assignable_users(&self.owners)
+    }
+
+    /// Set the links to all runs with the same failure, they are listed in the body
+    pub fn with_affected_runs(mut self, run_links: Vec<String>) -> Self {
+        self.affected_runs = run_links;
+        self
+    }
+
+    pub fn affected_runs(&self) -> &[String] {
+        &self.affected_runs
+    }
+
+    /// Set the flakiness of the failed jobs, the issue is labeled `flaky` if every failed job is
+    /// flaky and the body lists the flake rates of the flaky jobs
+    pub fn with_flakiness(mut self, flakiness: Vec<Flakiness>) -> Self {
+        self.flakiness = flakiness;
+        if self.is_flaky() && !self.labels.iter().any(|label| label == FLAKY_LABEL) {
+            self.labels.push(FLAKY_LABEL.to_string());
+        }
+        self
+    }
+
+    pub fn flakiness(&self) -> &[Flakiness] {
+        &self.flakiness
+    }
+
+    /// Whether every failed job is flaky
+    pub fn is_flaky(&self) -> bool {
+        !self.flakiness.is_empty() && self.flakiness.iter().all(Flakiness::is_flaky)
+    }
+
+    pub fn regression_of(&self) -> Option<&str> {
+        self.regression_of.as_deref()
+    }
+
+    /// Mark the issue as a regression of a closed issue, the body links to the closed issue
+    pub fn set_regression_of(&mut self, url: String) {
+        self.regression_of = Some(url);
+    }
+
+    /// The rendered issue body, ending with the hidden [metadata](metadata)
+    ///
+    /// # Errors
+    /// Returns an error if the body template fails to render with the issue as context
+    pub fn body(&self) -> Result<String, Box<dyn Error>> {
+        let mut body = match self.templates.body() {
+            None => self.body.to_string(),
+            Some(_) => self.templates.render_body(&self.template_context())?,
+        };
+        if let Some(url) = &self.regression_of {
+            body.push_str(&format!("\n\n**Regression of:** {url}"));
+        }
+        if !self.owners.is_empty() {
+            body.push_str(&format!("\n\n**Owners:** {}", self.owners.join(" ")));
+        }
+        let flaky_jobs: Vec<String> = self
+            .flakiness
+            .iter()
+            .filter(|flakiness| flakiness.is_flaky())
+            .map(|flakiness| {
+                format!(
+                    "`{}`: flake rate {:.0}% ({} of {} recent results)",
+                    flakiness.job,
+                    flakiness.rate() * 100.0,
+                    flakiness.flaky_failures,
+                    flakiness.results
+                )
+            })
+            .collect();
+        if !flaky_jobs.is_empty() {
+            body.push_str(&format!(
+                "\n\n**Flaky:** failed and passed for the same commit\n{}",
+                markdown_list(&flaky_jobs)
+            ));
+        }
+        if !self.affected_runs.is_empty() {
+            body.push_str(&format!(
+                "\n\n**Affected runs ({}):**\n{}",
+                self.affected_runs.len(),
+                markdown_list(&self.affected_runs)
+            ));
+        }
+        Ok(format!("{body}\n\n{}", self.metadata().to_html_comment()))
+    }
+
+    /// Machine readable metadata embedded in the issue body
+    pub fn metadata(&self) -> IssueMetadata {
+        IssueMetadata::from_issue(self)
+    }
+
+    /// The context available to issue templates
+    pub fn template_context(&self) -> Value {
+        self.body.template_context(&self.labels)
     }
 }
 
-#[derive(Debug)]
+/// What happened to the issue for the analyzed run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IssueOutcome {
+    /// An issue was created at the URL
+    Created(String),
+    /// An existing issue at the URL describes the same failure
+    DuplicateOf(String),
+    /// A closed issue at the URL describes the same failure and was reopened
+    Reopened(String),
+    /// No issue was created (e.g. dry run)
+    NotCreated,
+    /// No issue was created because the failure is flaky and its flake rate is below the
+    /// suppression threshold
+    Suppressed,
+}
+
+impl IssueOutcome {
+    /// The URL of the issue describing the failure, whether it was created, reopened or existed
+    pub fn issue_url(&self) -> Option<&str> {
+        match self {
+            Self::Created(url) | Self::DuplicateOf(url) | Self::Reopened(url) => Some(url),
+            Self::NotCreated | Self::Suppressed => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
 pub struct IssueBody {
     run_id: String,
     run_link: String,
+    workflow: String,
+    branch: String,
     failed_jobs: Vec<FailedJob>,
+    fingerprint: String,
 }
 
 impl IssueBody {
-    pub fn new(run_id: String, run_link: String, failed_jobs: Vec<FailedJob>) -> Self {
+    pub fn new(
+        run_id: String,
+        run_link: String,
+        workflow: String,
+        branch: String,
+        failed_jobs: Vec<FailedJob>,
+    ) -> Self {
+        let mut job_fingerprints: Vec<&str> = failed_jobs
+            .iter()
+            .map(|job| job.fingerprint.as_str())
+            .collect();
+        job_fingerprints.sort_unstable();
+        let fingerprint = util::fingerprint(&job_fingerprints);
         Self {
             run_id,
             run_link,
+            workflow,
+            branch,
             failed_jobs,
+            fingerprint,
+        }
+    }
+
+    /// Generate a title describing the failure
+    fn default_title(&self) -> String {
+        let branch_suffix = if self.branch.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", self.branch)
+        };
+        match self.failed_jobs.as_slice() {
+            [job] => {
+                let subject = match job.error_message.recipe() {
+                    Some(recipe) => recipe,
+                    None => format!("Step \"{}\"", job.failed_step),
+                };
+                let tag = match &job.error_message {
+                    ErrorMessageSummary::Yocto(err) => format!("[Yocto {}] ", err.kind()),
+                    ErrorMessageSummary::Other(_) => String::new(),
+                };
+                format!("{tag}{subject} failed in \"{}\"{branch_suffix}", job.name)
+            },
+            jobs if self.workflow.is_empty() => {
+                format!("{} jobs failed{branch_suffix}", jobs.len())
+            },
+            jobs => format!(
+                "{} jobs failed in \"{}\"{branch_suffix}",
+                jobs.len(),
+                self.workflow
+            ),
+        }
+    }
+
+    fn template_context(&self, labels: &[String]) -> Value {
+        context! {
+            run_id => self.run_id,
+            run_link => self.run_link,
+            workflow => self.workflow,
+            branch => self.branch,
+            labels => labels,
+            fingerprint => self.fingerprint,
+            failed_jobs => self
+                .failed_jobs
+                .iter()
+                .map(FailedJob::template_context)
+                .collect::<Vec<Value>>(),
         }
     }
 }
 
 impl Display for IssueBody {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "**Run ID**: {id} [LINK TO RUN]({run_url})
-
-**{failed_jobs_list_title}**
-{failed_jobs_name_list}",
-            id = self.run_id,
-            run_url = self.run_link,
-            failed_jobs_list_title = format_args!(
-                "{cnt} {job} failed:",
-                cnt = self.failed_jobs.len(),
-                job = if self.failed_jobs.len() == 1 {
-                    "job"
-                } else {
-                    "jobs"
-                }
-            ),
-            failed_jobs_name_list =
-                self.failed_jobs
-                    .iter()
-                    .fold(String::new(), |mut s_out, job| {
-                        let _ = writeln!(s_out, "- **`{}`**", job.name);
-                        s_out
-                    })
-        )?;
-        for job in &self.failed_jobs {
-            write!(f, "{job}")?;
-        }
-        Ok(())
+        let body = IssueTemplates::default()
+            .render_body(&self.template_context(&[]))
+            .map_err(|_| fmt::Error)?;
+        f.write_str(&body)
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct FailedJob {
     name: String,
     id: String,
     url: String,
     failed_step: String,
     error_message: ErrorMessageSummary,
+    fingerprint: String,
 }
 
 impl FailedJob {
@@ -117,57 +419,99 @@ impl FailedJob {
         failed_step: String,
         error_message: ErrorMessageSummary,
     ) -> Self {
+        // IDs and URLs differ between runs and are left out
+        let fingerprint = util::fingerprint(&[
+            name.as_str(),
+            failed_step.as_str(),
+            error_message.failure_label().as_deref().unwrap_or_default(),
+            &util::normalize_for_fingerprint(error_message.summary()),
+        ]);
         Self {
             name,
             id,
             url,
             failed_step,
             error_message,
+            fingerprint,
         }
     }
 
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn failed_step(&self) -> &str {
+        &self.failed_step
+    }
+
+    pub fn error_message(&self) -> &ErrorMessageSummary {
+        &self.error_message
+    }
+
     pub fn failure_label(&self) -> Option<String> {
         self.error_message.failure_label()
     }
+
+    /// Fingerprint of the job failure that is stable across runs
+    pub fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+
+    fn template_context(&self) -> Value {
+        context! {
+            name => self.name,
+            id => self.id,
+            url => self.url,
+            failed_step => self.failed_step,
+            summary => self.error_message.summary(),
+            failure_label => self.failure_label(),
+            recipe => self.error_message.recipe(),
+            fingerprint => self.fingerprint,
+            log_name => self.error_message.logfile_name(),
+            log => self.error_message.log(),
+        }
+    }
 }
 
 impl Display for FailedJob {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        let summary = self.error_message.summary();
-        let optional_log = match (self.error_message.logfile_name(), self.error_message.log()) {
-            (Some(name), Some(contents)) => format!(
-                "
-<details>
-<summary>{name}</summary>
-<br>
-
-```
-{contents}
-```
-</details>"
-            ),
-            _ => String::from(""),
-        };
+        let job = template::render_default_job(&self.template_context()).map_err(|_| fmt::Error)?;
+        f.write_str(&job)
+    }
+}
 
-        write!(
-            f,
-            "
-### `{name}` (ID {id})
-**Step failed:** `{failed_step}`
-\\
-**Log:** {url}
-\\
-*Best effort error summary*:
-```
-{error_message}```{optional_log}",
-            name = self.name,
-            id = self.id,
-            failed_step = self.failed_step,
-            url = self.url,
-            error_message = summary,
-            optional_log = optional_log
-        )
+/// Truncate a title to the maximum length of a GitHub issue title, marking the truncation with
+/// an ellipsis
+///
+/// # Example
+/// ```
+/// # use gh_workflow_parser::issue::{truncate_title, ISSUE_TITLE_MAX_LEN};
+/// assert_eq!(truncate_title("Short title"), "Short title");
+///
+/// let long_title = "x".repeat(300);
+/// let truncated = truncate_title(&long_title);
+/// assert_eq!(truncated.chars().count(), ISSUE_TITLE_MAX_LEN);
+/// assert!(truncated.ends_with("x…"));
+/// ```
+pub fn truncate_title(title: &str) -> String {
+    let title = title.trim();
+    if title.chars().count() <= ISSUE_TITLE_MAX_LEN {
+        return title.to_string();
     }
+    const ELLIPSIS: char = '…';
+    let mut truncated: String = title.chars().take(ISSUE_TITLE_MAX_LEN - 1).collect();
+    // Don't leave dangling whitespace before the ellipsis
+    truncated.truncate(truncated.trim_end().len());
+    truncated.push(ELLIPSIS);
+    truncated
 }
 
 #[cfg(test)]
@@ -224,13 +568,73 @@ Yocto error: ERROR: No recipes available for: ...
             ),
         ];
         let label = "bug".to_string();
-        let issue = Issue::new(run_id, run_link, failed_jobs, label);
-        assert_eq!(issue.title, "Scheduled run failed");
+        let issue = Issue::new(
+            run_id,
+            run_link,
+            "Use template and build image".to_string(),
+            "master".to_string(),
+            failed_jobs,
+            label,
+        );
+        assert_eq!(
+            issue.title,
+            "2 jobs failed in \"Use template and build image\" (master)"
+        );
         assert_eq!(issue.labels, ["bug"]);
         assert_eq!(issue.body.failed_jobs.len(), 2);
         assert_eq!(issue.body.failed_jobs[0].id, "21442749267");
     }
 
+    #[test]
+    fn test_into_per_job_issues() {
+        use crate::err_msg_parse::yocto_err::{util::YoctoFailureKind, YoctoError};
+        let failed_jobs = vec![
+            FailedJob::new(
+                "Test template xilinx".to_string(),
+                "21442749267".to_string(),
+                "https://github.com/luftkode/distro-template/actions/runs/7858139663/job/21442749267".to_string(),
+                "📦 Build yocto image".to_string(),
+                ErrorMessageSummary::Yocto(YoctoError::new(
+                    "ERROR: Logfile of failure stored in: /app/yocto/build/tmp/work/x86_64-linux/sqlite3-native/3.43.2/temp/log.do_fetch.21616\n".to_string(),
+                    YoctoFailureKind::DoFetch,
+                    None,
+                )),
+            ),
+            FailedJob::new(
+                "Test template raspberry".to_string(),
+                "21442749166".to_string(),
+                "https://github.com/luftkode/distro-template/actions/runs/7858139663/job/21442749166".to_string(),
+                "📦 Build yocto image".to_string(),
+                ErrorMessageSummary::Other("Some error\n".to_string()),
+            ),
+        ];
+        let issue = Issue::new(
+            "7858139663".to_string(),
+            "https://github.com/luftkode/distro-template/actions/runs/7858139663".to_string(),
+            "Use template and build image".to_string(),
+            "master".to_string(),
+            failed_jobs,
+            "bug".to_string(),
+        );
+        let run_fingerprint = issue.fingerprint().to_string();
+
+        let issues = issue.into_per_job_issues().unwrap();
+        assert_eq!(issues.len(), 2);
+        assert_eq!(
+            issues[0].title(),
+            r#"[Yocto do_fetch] sqlite3-native failed in "Test template xilinx" (master)"#
+        );
+        assert_eq!(issues[0].labels(), ["bug", "do_fetch"]);
+        assert_eq!(
+            issues[1].title(),
+            r#"Step "📦 Build yocto image" failed in "Test template raspberry" (master)"#
+        );
+        assert_eq!(issues[1].labels(), ["bug"]);
+        assert_eq!(issues[1].run_id(), "7858139663");
+        assert_ne!(issues[0].fingerprint(), run_fingerprint);
+        assert_ne!(issues[0].fingerprint(), issues[1].fingerprint());
+    }
+
     #[test]
     fn test_issue_body_display() {
         let run_id = "7858139663".to_string();
@@ -255,8 +659,170 @@ Yocto error: ERROR: No recipes available for: ...
             ),
             ];
 
-        let issue_body = IssueBody::new(run_id, run_link, failed_jobs);
+        let issue_body = IssueBody::new(
+            run_id,
+            run_link,
+            "Use template and build image".to_string(),
+            "master".to_string(),
+            failed_jobs,
+        );
         assert_eq!(issue_body.to_string(), EXAMPLE_ISSUE_BODY);
         //std::fs::write("test2.md", issue_body.to_string()).unwrap();
     }
+
+    #[test]
+    fn test_failed_job_display_with_logfile() {
+        use crate::err_msg_parse::yocto_err::{
+            util::YoctoFailureKind, YoctoError, YoctoFailureLog,
+        };
+        let failed_job = FailedJob::new(
+            "Test template xilinx".to_string(),
+            "21442749267".to_string(),
+            "https://github.com/luftkode/distro-template/actions/runs/7858139663/job/21442749267"
+                .to_string(),
+            "📦 Build yocto image".to_string(),
+            ErrorMessageSummary::Yocto(YoctoError::new(
+                "ERROR: sqlite3-native-3_3.43.2-r0 do_fetch: Bitbake Fetcher Error\n".to_string(),
+                YoctoFailureKind::DoFetch,
+                Some(YoctoFailureLog {
+                    name: "log.do_fetch.21616".to_string(),
+                    contents: "blabla error".to_string(),
+                }),
+            )),
+        );
+        assert_eq!(
+            failed_job.to_string(),
+            r#"
+### `Test template xilinx` (ID 21442749267)
+**Step failed:** `📦 Build yocto image`
+\
+**Log:** https://github.com/luftkode/distro-template/actions/runs/7858139663/job/21442749267
+\
+*Best effort error summary*:
+```
+ERROR: sqlite3-native-3_3.43.2-r0 do_fetch: Bitbake Fetcher Error
+```
+<details>
+<summary>log.do_fetch.21616</summary>
+<br>
+
+```
+blabla error
+```
+</details>"#
+        );
+    }
+
+    #[test]
+    fn test_issue_with_templates() {
+        let failed_jobs = vec![FailedJob::new(
+            "Test template xilinx".to_string(),
+            "21442749267".to_string(),
+            "https://github.com/luftkode/distro-template/actions/runs/7850874958/job/21442749267"
+                .to_string(),
+            "📦 Build yocto image".to_string(),
+            ErrorMessageSummary::Other("Some error\n".to_string()),
+        )];
+        let issue = Issue::new(
+            "7858139663".to_string(),
+            "https://github.com/luftkode/distro-template/actions/runs/7850874958".to_string(),
+            "Use template and build image".to_string(),
+            "master".to_string(),
+            failed_jobs,
+            "bug".to_string(),
+        )
+        .with_templates(IssueTemplates::new(
+            Some("{{ workflow }} failed on {{ branch }}".to_string()),
+            Some(
+                "{{ labels | join(\",\") }}: {% for job in failed_jobs %}{{ job.failed_step }}{% endfor %}"
+                    .to_string(),
+            ),
+        ))
+        .unwrap();
+        assert_eq!(
+            issue.title(),
+            "Use template and build image failed on master"
+        );
+        assert_eq!(
+            metadata::strip_metadata(&issue.body().unwrap()),
+            "bug: 📦 Build yocto image"
+        );
+    }
+
+    #[test]
+    fn test_issue_title_sees_changed_labels() {
+        let templates = IssueTemplates::new(Some("{{ labels | join(\",\") }}".to_string()), None);
+        let issue = Issue::new(
+            "7858139663".to_string(),
+            "https://github.com/luftkode/distro-template/actions/runs/7850874958".to_string(),
+            "Use template and build image".to_string(),
+            "master".to_string(),
+            vec![],
+            "bug".to_string(),
+        )
+        .with_templates(templates.clone())
+        .unwrap();
+        assert_eq!(issue.title(), "bug");
+        let issue = issue
+            .with_labels(vec!["bug".to_string(), "flaky".to_string()])
+            .with_templates(templates)
+            .unwrap();
+        assert_eq!(issue.title(), "bug,flaky");
+    }
+
+    #[test]
+    fn test_default_title_single_yocto_job() {
+        use crate::err_msg_parse::yocto_err::{util::YoctoFailureKind, YoctoError};
+        let failed_jobs = vec![FailedJob::new(
+            "Test template xilinx".to_string(),
+            "21442749267".to_string(),
+            "https://github.com/luftkode/distro-template/actions/runs/7858139663/job/21442749267".to_string(),
+            "📦 Build yocto image".to_string(),
+            ErrorMessageSummary::Yocto(YoctoError::new(
+                "ERROR: Logfile of failure stored in: /app/yocto/build/tmp/work/x86_64-linux/sqlite3-native/3.43.2/temp/log.do_fetch.21616\n".to_string(),
+                YoctoFailureKind::DoFetch,
+                None,
+            )),
+        )];
+        let issue = Issue::new(
+            "7858139663".to_string(),
+            "https://github.com/luftkode/distro-template/actions/runs/7858139663".to_string(),
+            "Use template and build image".to_string(),
+            "master".to_string(),
+            failed_jobs,
+            "bug".to_string(),
+        );
+        assert_eq!(
+            issue.title(),
+            r#"[Yocto do_fetch] sqlite3-native failed in "Test template xilinx" (master)"#
+        );
+    }
+
+    #[test]
+    fn test_default_title_single_other_job_no_branch() {
+        let failed_jobs = vec![FailedJob::new(
+            "Lint".to_string(),
+            "1".to_string(),
+            "https://github.com/foo/bar/actions/runs/2/job/1".to_string(),
+            "Run clippy".to_string(),
+            ErrorMessageSummary::Other("error: unused variable\n".to_string()),
+        )];
+        let issue = Issue::new(
+            "2".to_string(),
+            "https://github.com/foo/bar/actions/runs/2".to_string(),
+            String::new(),
+            String::new(),
+            failed_jobs,
+            "bug".to_string(),
+        );
+        assert_eq!(issue.title(), r#"Step "Run clippy" failed in "Lint""#);
+    }
+
+    #[test]
+    fn test_truncate_title_multibyte() {
+        let title = "📦".repeat(ISSUE_TITLE_MAX_LEN + 1);
+        let truncated = truncate_title(&title);
+        assert_eq!(truncated.chars().count(), ISSUE_TITLE_MAX_LEN);
+        assert!(truncated.ends_with("📦…"));
+    }
 }
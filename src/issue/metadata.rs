@@ -0,0 +1,167 @@
+//! Machine readable metadata embedded in the body of created issues
+//!
+//! The metadata is a JSON object in an HTML comment at the end of the issue body, so it is hidden
+//! when GitHub renders the issue:
+//!
+//! ```text
+//! <!-- gh-workflow-parser-metadata
+//! {"version":"0.5.4","repo":"https://github.com/luftkode/distro-template","run_id":"7858139663",...}
+//! -->
+//! ```
+//!
+//! It allows later runs to recognize the issues created by this tool by their fingerprints
+//! instead of comparing the rendered markdown.
+use serde::{Deserialize, Serialize};
+
+use super::Issue;
+
+/// Marks the start of the HTML comment with the metadata
+pub const METADATA_MARKER: &str = "<!-- gh-workflow-parser-metadata";
+const COMMENT_END: &str = "-->";
+
+/// Metadata describing the run an issue was created from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IssueMetadata {
+    /// Version of gh-workflow-parser that created the issue
+    pub version: String,
+    pub repo: String,
+    pub workflow: String,
+    pub run_id: String,
+    pub fingerprint: String,
+    pub jobs: Vec<JobMetadata>,
+}
+
+/// Metadata of a failed job
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JobMetadata {
+    pub id: String,
+    pub name: String,
+    pub fingerprint: String,
+    /// The parser that produced the error summary e.g. `yocto`
+    pub parser: String,
+    pub failure_label: Option<String>,
+}
+
+impl IssueMetadata {
+    pub fn from_issue(issue: &Issue) -> Self {
+        let repo = issue
+            .run_link()
+            .trim()
+            .rsplit_once("/actions/runs/")
+            .map_or("", |(repo, _run_id)| repo);
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            repo: repo.to_string(),
+            workflow: issue.workflow().to_string(),
+            run_id: issue.run_id().to_string(),
+            fingerprint: issue.fingerprint().to_string(),
+            jobs: issue
+                .failed_jobs()
+                .iter()
+                .map(|job| JobMetadata {
+                    id: job.id().to_string(),
+                    name: job.name().to_string(),
+                    fingerprint: job.fingerprint().to_string(),
+                    parser: job.error_message().parser().to_string(),
+                    failure_label: job.failure_label(),
+                })
+                .collect(),
+        }
+    }
+
+    /// The metadata as a hidden HTML comment
+    pub fn to_html_comment(&self) -> String {
+        let json = serde_json::to_string(self).expect("Metadata serializes to JSON");
+        // `--` can only occur inside JSON strings, escape it so a value can't end the comment
+        let json = json.replace("--", "-\\u002d");
+        format!("{METADATA_MARKER}\n{json}\n{COMMENT_END}")
+    }
+}
+
+/// Extract the metadata from the body of an issue created by this tool
+///
+/// Returns `None` if the body contains no (valid) metadata, e.g. the issue was created manually
+/// or by a version without metadata.
+///
+/// # Example
+/// ```
+/// # use gh_workflow_parser::issue::metadata::extract_metadata;
+/// let body = r#"**Run ID**: 1337
+/// <!-- gh-workflow-parser-metadata
+/// {"version":"0.5.4","repo":"https://github.com/foo/bar","workflow":"CI","run_id":"1337","fingerprint":"0123456789abcdef","jobs":[]}
+/// -->"#;
+/// let metadata = extract_metadata(body).unwrap();
+/// assert_eq!(metadata.run_id, "1337");
+/// assert!(extract_metadata("A manually created issue").is_none());
+/// ```
+pub fn extract_metadata(body: &str) -> Option<IssueMetadata> {
+    let (_, after_marker) = body.rsplit_once(METADATA_MARKER)?;
+    let (json, _) = after_marker.split_once(COMMENT_END)?;
+    serde_json::from_str(json.trim())
+        .map_err(|e| log::warn!("Invalid issue metadata: {e}"))
+        .ok()
+}
+
+/// Remove the metadata comment from an issue body
+pub fn strip_metadata(body: &str) -> &str {
+    match body.rsplit_once(METADATA_MARKER) {
+        Some((before, _)) => before.trim_end(),
+        None => body,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{err_msg_parse::ErrorMessageSummary, issue::FailedJob};
+    use pretty_assertions::assert_eq;
+
+    fn example_issue() -> Issue {
+        Issue::new(
+            "7858139663".to_string(),
+            "https://github.com/luftkode/distro-template/actions/runs/7858139663".to_string(),
+            "Use template and build image".to_string(),
+            "master".to_string(),
+            vec![FailedJob::new(
+                "Test template <!-- xilinx -->".to_string(),
+                "21442749267".to_string(),
+                "https://github.com/luftkode/distro-template/actions/runs/7858139663/job/21442749267".to_string(),
+                "📦 Build yocto image".to_string(),
+                ErrorMessageSummary::Other("ERROR: something broke\n".to_string()),
+            )],
+            "bug".to_string(),
+        )
+    }
+
+    #[test]
+    fn test_metadata_roundtrip() {
+        let issue = example_issue();
+        let metadata = IssueMetadata::from_issue(&issue);
+        assert_eq!(metadata.repo, "https://github.com/luftkode/distro-template");
+        assert_eq!(metadata.fingerprint, issue.fingerprint());
+        assert_eq!(metadata.jobs[0].parser, "other");
+
+        let body = format!("Some body\n\n{}", metadata.to_html_comment());
+        // The job name must not end the comment early
+        assert_eq!(body.matches(COMMENT_END).count(), 1);
+        assert_eq!(extract_metadata(&body), Some(metadata));
+        assert_eq!(strip_metadata(&body), "Some body");
+    }
+
+    #[test]
+    fn test_issue_body_contains_metadata() {
+        let issue = example_issue();
+        let metadata = extract_metadata(&issue.body().unwrap()).unwrap();
+        assert_eq!(metadata.run_id, "7858139663");
+        assert_eq!(metadata.jobs[0].id, "21442749267");
+    }
+
+    #[test]
+    fn test_invalid_metadata() {
+        assert_eq!(
+            extract_metadata(&format!("{METADATA_MARKER}\n{{}}\n-->")),
+            None
+        );
+        assert_eq!(extract_metadata(METADATA_MARKER), None);
+    }
+}
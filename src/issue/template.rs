@@ -0,0 +1,195 @@
+//! Templates for rendering the title and body of a GitHub issue.
+//!
+//! Templates use the [minijinja](https://docs.rs/minijinja) syntax and are rendered with the
+//! parsed issue model as context:
+//!
+//! - `run_id`, `run_link`, `workflow`, `branch`, `labels` and `fingerprint`
+//! - `failed_jobs`: a list of jobs with the fields `name`, `id`, `url`, `failed_step`,
+//!   `summary`, `fingerprint`, `failure_label`, `recipe`, `log_name` and `log` (the last four may
+//!   be `none`)
+//!
+//! Without a title template the title is generated from the failure (see
+//! [`Issue::new`](crate::issue::Issue::new)).
+//!
+//! The default job section is available to body templates as `{% include "job" %}` (expects the
+//! current job to be bound to `job`).
+use std::{error::Error, path::Path};
+
+use minijinja::{Environment, Value};
+
+/// The default issue body template
+pub const DEFAULT_BODY_TEMPLATE: &str = r#"**Run ID**: {{ run_id }} [LINK TO RUN]({{ run_link }})
+
+**{{ failed_jobs | length }} {% if failed_jobs | length == 1 %}job{% else %}jobs{% endif %} failed:**
+{% for job in failed_jobs %}- **`{{ job.name }}`**
+{% endfor %}{% for job in failed_jobs %}{% include "job" %}{% endfor %}"#;
+
+/// The default template for the section describing a single failed job
+pub const DEFAULT_JOB_TEMPLATE: &str = r#"
+### `{{ job.name }}` (ID {{ job.id }})
+**Step failed:** `{{ job.failed_step }}`
+\
+**Log:** {{ job.url }}
+\
+*Best effort error summary*:
+```
+{{ job.summary }}```{% if job.log_name is not none and job.log is not none %}
+<details>
+<summary>{{ job.log_name }}</summary>
+<br>
+
+```
+{{ job.log }}
+```
+</details>{% endif %}"#;
+
+const TITLE_TEMPLATE_NAME: &str = "title";
+const BODY_TEMPLATE_NAME: &str = "body";
+const JOB_TEMPLATE_NAME: &str = "job";
+
+/// User supplied templates for the issue title and body
+///
+/// Templates that are not set fall back to the defaults.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct IssueTemplates {
+    title: Option<String>,
+    body: Option<String>,
+}
+
+impl IssueTemplates {
+    pub fn new(title: Option<String>, body: Option<String>) -> Self {
+        Self { title, body }
+    }
+
+    /// Read the templates from files, a `None` path means the default template is used
+    ///
+    /// # Errors
+    /// Returns an error if a template file cannot be read or is not a valid template
+    pub fn from_files(title: Option<&Path>, body: Option<&Path>) -> Result<Self, Box<dyn Error>> {
+        let read = |path: &Path| {
+            std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read template file {path:?}: {e}"))
+        };
+        let templates = Self {
+            title: title.map(read).transpose()?,
+            body: body.map(read).transpose()?,
+        };
+        // Fail early instead of when the issue is about to be created
+        templates.environment()?;
+        Ok(templates)
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    pub fn body(&self) -> Option<&str> {
+        self.body.as_deref()
+    }
+
+    /// Render the title template with the given context, returns `None` if no title template is
+    /// set
+    pub fn render_title(&self, ctx: &Value) -> Result<Option<String>, Box<dyn Error>> {
+        if self.title.is_none() {
+            return Ok(None);
+        }
+        let rendered = self
+            .environment()?
+            .get_template(TITLE_TEMPLATE_NAME)?
+            .render(ctx)?;
+        Ok(Some(rendered.trim().to_string()))
+    }
+
+    /// Render the body template with the given context
+    pub fn render_body(&self, ctx: &Value) -> Result<String, Box<dyn Error>> {
+        Ok(self
+            .environment()?
+            .get_template(BODY_TEMPLATE_NAME)?
+            .render(ctx)?)
+    }
+
+    fn environment(&self) -> Result<Environment<'_>, minijinja::Error> {
+        let mut env = Environment::new();
+        if let Some(title) = self.title() {
+            env.add_template(TITLE_TEMPLATE_NAME, title)?;
+        }
+        env.add_template(
+            BODY_TEMPLATE_NAME,
+            self.body().unwrap_or(DEFAULT_BODY_TEMPLATE),
+        )?;
+        env.add_template(JOB_TEMPLATE_NAME, DEFAULT_JOB_TEMPLATE)?;
+        Ok(env)
+    }
+}
+
+/// Render the default section for a single failed job
+pub fn render_default_job(job_ctx: &Value) -> Result<String, minijinja::Error> {
+    let mut env = Environment::new();
+    env.add_template(JOB_TEMPLATE_NAME, DEFAULT_JOB_TEMPLATE)?;
+    env.get_template(JOB_TEMPLATE_NAME)?
+        .render(minijinja::context! { job => job_ctx })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use minijinja::context;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_custom_title_template() {
+        let templates = IssueTemplates::new(
+            Some(
+                "{{ workflow }} failed on {{ branch }} ({{ failed_jobs | length }})\n".to_string(),
+            ),
+            None,
+        );
+        let ctx = context! {
+            workflow => "Nightly",
+            branch => "main",
+            failed_jobs => vec![context! { name => "build" }],
+        };
+        assert_eq!(
+            templates.render_title(&ctx).unwrap().unwrap(),
+            "Nightly failed on main (1)"
+        );
+    }
+
+    #[test]
+    fn test_custom_body_template_can_include_job() {
+        let templates = IssueTemplates::new(
+            None,
+            Some(
+                "Run {{ run_id }}{% for job in failed_jobs %}{% include \"job\" %}{% endfor %}"
+                    .to_string(),
+            ),
+        );
+        let ctx = context! {
+            run_id => "123",
+            failed_jobs => vec![context! {
+                name => "build",
+                id => "1",
+                url => "https://example.com",
+                failed_step => "compile",
+                summary => "error\n",
+                log_name => Value::from(()),
+                log => Value::from(()),
+            }],
+        };
+        let body = templates.render_body(&ctx).unwrap();
+        assert!(body.starts_with("Run 123\n### `build` (ID 1)"), "{body}");
+        assert!(!body.contains("<details>"), "{body}");
+    }
+
+    #[test]
+    fn test_invalid_template_is_error() {
+        let templates = IssueTemplates::new(Some("{% if %}".to_string()), None);
+        assert!(templates.render_title(&context! {}).is_err());
+    }
+
+    #[test]
+    fn test_no_title_template() {
+        let templates = IssueTemplates::default();
+        assert!(templates.render_title(&context! {}).unwrap().is_none());
+    }
+}
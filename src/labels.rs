@@ -0,0 +1,310 @@
+//! The label policy for created issues
+//!
+//! Issues are labeled with the issue label given with `--label` and a failure label for each
+//! failed job (e.g. the failed Yocto task `do_fetch`). The policy decides:
+//!
+//! - The namespace of the failure labels with a prefix e.g. `yocto/do_fetch`
+//! - The color and description of each label category, or of a single label
+//! - Which labels may be created when an issue is created, labels that don't exist and may not be
+//!   created are left out of the issue
+//!
+//! The whole taxonomy can be created or updated up front with the `sync-labels` subcommand.
+use std::error::Error;
+
+use strum::IntoEnumIterator;
+
+use crate::{
+    err_msg_parse::yocto_err::util::YoctoFailureKind,
+    flaky::FLAKY_LABEL,
+    gh::GitHub,
+    issue::Issue,
+    settings::{LabelSettings, LabelStyle},
+};
+
+pub const DEFAULT_ISSUE_COLOR: &str = "D73A4A";
+pub const DEFAULT_ISSUE_DESCRIPTION: &str = "Failed workflow run";
+pub const DEFAULT_FAILURE_COLOR: &str = "FBCA04";
+pub const DEFAULT_FAILURE_DESCRIPTION: &str = "Failed Yocto task";
+pub const DEFAULT_FLAKY_COLOR: &str = "C5DEF5";
+pub const DEFAULT_FLAKY_DESCRIPTION: &str = "Fails and passes for the same commit";
+
+/// The category of a label, each category has its own default color and description
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LabelCategory {
+    /// The label given with `--label`, or any other label that isn't generated
+    Issue,
+    /// A label generated from a failure e.g. `do_fetch`
+    Failure,
+    /// The label of flaky failures
+    Flaky,
+}
+
+/// A label as it should exist in the repository
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LabelSpec {
+    pub name: String,
+    pub color: String,
+    pub description: String,
+}
+
+/// Decides the names, colors and descriptions of labels and which labels may be created
+#[derive(Debug, Default, Clone)]
+pub struct LabelPolicy {
+    settings: LabelSettings,
+}
+
+impl LabelPolicy {
+    pub fn new(settings: LabelSettings) -> Self {
+        Self { settings }
+    }
+
+    /// The name of the label for a failure e.g. `yocto/do_fetch` with the prefix `yocto/`
+    pub fn failure_label(&self, failure: &str) -> String {
+        if failure.starts_with(&self.settings.prefix) {
+            failure.to_string()
+        } else {
+            format!("{}{failure}", self.settings.prefix)
+        }
+    }
+
+    /// The category of a label by its name
+    pub fn category(&self, name: &str) -> LabelCategory {
+        let is_failure = name
+            .strip_prefix(&self.settings.prefix)
+            .is_some_and(|failure| {
+                YoctoFailureKind::iter().any(|kind| kind.to_string() == failure)
+            });
+        if name == FLAKY_LABEL {
+            LabelCategory::Flaky
+        } else if is_failure {
+            LabelCategory::Failure
+        } else {
+            LabelCategory::Issue
+        }
+    }
+
+    /// The label with the color and description from the policy
+    ///
+    /// # Example
+    /// ```
+    /// # use gh_workflow_parser::{labels::LabelPolicy, settings::LabelSettings};
+    /// let policy = LabelPolicy::new(LabelSettings {
+    ///     prefix: "yocto/".to_string(),
+    ///     ..Default::default()
+    /// });
+    /// assert_eq!(policy.spec("yocto/do_fetch").color, "FBCA04");
+    /// assert_eq!(policy.spec("bug").color, "D73A4A");
+    /// ```
+    pub fn spec(&self, name: &str) -> LabelSpec {
+        let (category_style, default_color, default_description) = match self.category(name) {
+            LabelCategory::Issue => (
+                &self.settings.issue,
+                DEFAULT_ISSUE_COLOR,
+                DEFAULT_ISSUE_DESCRIPTION,
+            ),
+            LabelCategory::Failure => (
+                &self.settings.failure,
+                DEFAULT_FAILURE_COLOR,
+                DEFAULT_FAILURE_DESCRIPTION,
+            ),
+            LabelCategory::Flaky => (
+                &self.settings.flaky,
+                DEFAULT_FLAKY_COLOR,
+                DEFAULT_FLAKY_DESCRIPTION,
+            ),
+        };
+        let label_style = self.settings.definitions.get(name);
+        let pick = |f: fn(&LabelStyle) -> &Option<String>, default: &str| {
+            label_style
+                .and_then(|style| f(style).clone())
+                .or_else(|| f(category_style).clone())
+                .unwrap_or_else(|| default.to_string())
+        };
+        LabelSpec {
+            name: name.to_string(),
+            color: pick(|style| &style.color, default_color),
+            description: pick(|style| &style.description, default_description),
+        }
+    }
+
+    /// Whether the label may be created when an issue is created
+    ///
+    /// # Example
+    /// ```
+    /// # use gh_workflow_parser::{labels::LabelPolicy, settings::LabelSettings};
+    /// let policy = LabelPolicy::new(LabelSettings {
+    ///     auto_create: Some(vec!["bug".to_string(), "yocto/*".to_string()]),
+    ///     ..Default::default()
+    /// });
+    /// assert!(policy.may_create("bug"));
+    /// assert!(policy.may_create("yocto/do_fetch"));
+    /// assert!(!policy.may_create("do_fetch"));
+    /// assert!(LabelPolicy::default().may_create("do_fetch"));
+    /// ```
+    pub fn may_create(&self, name: &str) -> bool {
+        let Some(allowed) = &self.settings.auto_create else {
+            return true;
+        };
+        allowed
+            .iter()
+            .any(|pattern| match pattern.strip_suffix('*') {
+                Some(prefix) => name.starts_with(prefix),
+                None => name == pattern,
+            })
+    }
+
+    /// Apply the prefix to the failure labels of the issue
+    pub fn apply(&self, issue: Issue) -> Issue {
+        let mut labels = issue.labels().iter();
+        // The first label is the issue label, the rest are failure labels
+        let labels = labels
+            .next()
+            .cloned()
+            .into_iter()
+            .chain(labels.map(|label| self.failure_label(label)))
+            .collect();
+        issue.with_labels(labels)
+    }
+
+    /// Make sure the labels exist in the repository before they are added to an issue
+    ///
+    /// Missing labels are created if the policy allows it, otherwise they are left out. The issue
+    /// label (`--label`) is always created, duplicates are only found among issues with it.
+    /// Returns the labels that can be added to the issue.
+    pub fn ensure_labels(
+        &self,
+        github_cli: &dyn GitHub,
+        issue_label: &str,
+        labels: &[String],
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let existing_labels = github_cli.all_labels(None)?;
+        let mut usable = vec![];
+        for label in labels {
+            if existing_labels.contains(label) {
+                log::debug!("Label {label} already exists in the repository");
+            } else if label == issue_label || self.may_create(label) {
+                log::info!("Label {label} does not exist in the repository. Creating it...");
+                let spec = self.spec(label);
+                github_cli.create_label(None, &spec.name, &spec.color, &spec.description, false)?;
+            } else {
+                log::warn!("Label {label} does not exist in the repository and may not be created, leaving it out");
+                continue;
+            }
+            usable.push(label.to_owned());
+        }
+        Ok(usable)
+    }
+
+    /// All labels the tool can add to issues labeled with `issue_label`: the issue label, a failure
+    /// label for every known failure, the flaky label and the labels defined in the settings
+    pub fn taxonomy(&self, issue_label: &str) -> Vec<LabelSpec> {
+        let mut names = vec![issue_label.to_string()];
+        names.extend(YoctoFailureKind::iter().map(|kind| self.failure_label(&kind.to_string())));
+        names.push(FLAKY_LABEL.to_string());
+        names.extend(self.settings.definitions.keys().cloned());
+        let mut specs: Vec<LabelSpec> = vec![];
+        for name in names {
+            if !specs.iter().any(|spec| spec.name == name) {
+                specs.push(self.spec(&name));
+            }
+        }
+        specs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gh::gh_cli_fake::GitHubCliFake;
+    use pretty_assertions::assert_eq;
+    use std::collections::BTreeMap;
+
+    fn policy() -> LabelPolicy {
+        LabelPolicy::new(LabelSettings {
+            prefix: "yocto/".to_string(),
+            auto_create: Some(vec!["bug".to_string(), "yocto/*".to_string()]),
+            failure: LabelStyle {
+                color: Some("00FF00".to_string()),
+                description: None,
+            },
+            definitions: BTreeMap::from([(
+                "yocto/do_fetch".to_string(),
+                LabelStyle {
+                    color: None,
+                    description: Some("Fetching sources failed".to_string()),
+                },
+            )]),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_spec() {
+        let policy = policy();
+        assert_eq!(
+            policy.spec("yocto/do_fetch"),
+            LabelSpec {
+                name: "yocto/do_fetch".to_string(),
+                color: "00FF00".to_string(),
+                description: "Fetching sources failed".to_string(),
+            }
+        );
+        assert_eq!(
+            policy.spec("yocto/do_compile").description,
+            DEFAULT_FAILURE_DESCRIPTION
+        );
+        // Without the prefix it's not a generated label
+        assert_eq!(policy.category("do_compile"), LabelCategory::Issue);
+        assert_eq!(policy.spec("bug").color, DEFAULT_ISSUE_COLOR);
+        assert_eq!(policy.spec("flaky").color, DEFAULT_FLAKY_COLOR);
+    }
+
+    #[test]
+    fn test_failure_label() {
+        let policy = policy();
+        assert_eq!(policy.failure_label("do_fetch"), "yocto/do_fetch");
+        assert_eq!(policy.failure_label("yocto/do_fetch"), "yocto/do_fetch");
+        assert_eq!(LabelPolicy::default().failure_label("do_fetch"), "do_fetch");
+    }
+
+    #[test]
+    fn test_ensure_labels() {
+        let github_cli = GitHubCliFake::new("https://github.com/luftkode/distro-template".into());
+        github_cli
+            .create_label(None, "existing", "FFFFFF", "", false)
+            .unwrap();
+        let labels = ["bug", "existing", "yocto/do_fetch", "do_fetch"].map(String::from);
+        let usable = policy().ensure_labels(&github_cli, "bug", &labels).unwrap();
+        assert_eq!(usable, ["bug", "existing", "yocto/do_fetch"]);
+        assert_eq!(
+            github_cli.all_labels(None).unwrap(),
+            ["existing", "bug", "yocto/do_fetch"]
+        );
+    }
+
+    #[test]
+    fn test_ensure_labels_creates_issue_label() {
+        let github_cli = GitHubCliFake::new("https://github.com/luftkode/distro-template".into());
+        // The issue label isn't in the allowlist
+        let labels = ["ci-failure", "yocto/do_fetch", "do_fetch"].map(String::from);
+        let usable = policy()
+            .ensure_labels(&github_cli, "ci-failure", &labels)
+            .unwrap();
+        assert_eq!(usable, ["ci-failure", "yocto/do_fetch"]);
+        assert_eq!(
+            github_cli.all_labels(None).unwrap(),
+            ["ci-failure", "yocto/do_fetch"]
+        );
+    }
+
+    #[test]
+    fn test_taxonomy() {
+        let taxonomy = policy().taxonomy("bug");
+        let names: Vec<&str> = taxonomy.iter().map(|spec| spec.name.as_str()).collect();
+        assert_eq!(names[0], "bug");
+        assert!(names.contains(&"yocto/do_fetch"));
+        assert!(names.contains(&"yocto/misc"));
+        assert!(names.contains(&"flaky"));
+        assert_eq!(names.len(), 2 + YoctoFailureKind::iter().count());
+    }
+}
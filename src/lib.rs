@@ -2,9 +2,19 @@ pub mod commands;
 pub mod config;
 pub mod err_msg_parse;
 pub mod errlog;
+pub mod flaky;
 pub mod gh;
+pub mod history;
 pub mod issue;
+pub mod labels;
+pub mod notify;
+pub mod owners;
+pub mod project;
+pub mod report;
+pub mod serve;
+pub mod settings;
 pub mod util;
+pub mod watch;
 
 /// Module containing macros related to protocol words.
 pub mod macros {
@@ -1,6 +1,26 @@
-use std::{error::Error, process::ExitCode};
+use std::{error::Error, process::ExitCode, time::Duration};
 
-use gh_workflow_parser::{commands, config, gh::init_github_cli, util};
+use gh_workflow_parser::{
+    commands::{
+        self,
+        analyze_log::LocalRun,
+        create_issue_from_run::{CreateIssueOptions, RepoIssueOptions},
+        watch::WatchOptions,
+    },
+    commands::{HistoryCommand, IssuePer},
+    config,
+    flaky::FlakinessDetector,
+    gh::{init_github_cli_with, RunFilter},
+    history::{HistoryDb, HistoryQuery},
+    issue::template::IssueTemplates,
+    labels::LabelPolicy,
+    notify::Notifier,
+    owners::OwnershipResolver,
+    project::ProjectOptions,
+    report::Period,
+    serve::WEBHOOK_SECRET_ENV,
+    util,
+};
 
 fn main() -> ExitCode {
     match run() {
@@ -9,7 +29,7 @@ fn main() -> ExitCode {
         Err(err)
             if err
                 .downcast_ref::<std::io::Error>()
-                .map_or(false, |e| e.kind() == std::io::ErrorKind::BrokenPipe) =>
+                .is_some_and(|e| e.kind() == std::io::ErrorKind::BrokenPipe) =>
         {
             ExitCode::SUCCESS
         },
@@ -36,19 +56,339 @@ fn run() -> Result<(), Box<dyn Error>> {
             label,
             kind,
             no_duplicate,
+            title_template,
+            body_template,
+            github_actions_output,
+            webhook_url,
+            webhook_format,
+            webhook_timeout,
+            no_issue,
+            on_regression,
+            regression_window_days,
+            issue_per,
+            tracking_issue,
+            milestone,
+            project,
+            project_field,
+            detect_flaky,
+            flaky_history,
+            suppress_flaky_below,
         } => {
             log::info!("Targeting GitHub repository: {repo}, run: {run_id}, label: {label}, kind: {kind}, no_duplicate: {no_duplicate}");
-            let canonicalized_repo_url = util::canonicalize_repo_url(repo, "github.com");
-            let github_cli = init_github_cli(canonicalized_repo_url, config.fake_github_cli());
-            commands::create_issue_from_run::create_issue_from_run(
+            let canonicalized_repo_url = util::canonicalize_repo_url(repo, &config.host()?);
+            let github_cli =
+                init_github_cli_with(canonicalized_repo_url, &config.github_cli_settings())?;
+            let templates =
+                IssueTemplates::from_files(title_template.as_deref(), body_template.as_deref())?;
+            let settings = config.settings()?;
+            let owners = OwnershipResolver::from_settings(settings.owners, github_cli.as_ref())?;
+            let options = CreateIssueOptions {
+                templates,
+                dry_run: config.dry_run(),
+                no_duplicate: *no_duplicate,
+                github_actions_output: *github_actions_output,
+                notifiers: webhook_url
+                    .iter()
+                    .map(|url| {
+                        Notifier::new(url.to_owned(), *webhook_format)
+                            .with_timeout(Duration::from_secs(*webhook_timeout))
+                    })
+                    .collect(),
+                skip_issue_creation: *no_issue,
+                on_regression: *on_regression,
+                regression_window_days: *regression_window_days,
+                issue_per: *issue_per,
+                log_parallelism: config.log_parallelism(),
+                tracking_issue: *tracking_issue,
+                owners,
+                labels: LabelPolicy::new(settings.labels),
+                project: ProjectOptions {
+                    milestone: milestone.to_owned(),
+                    project: project.to_owned(),
+                    fields: project_field.to_owned(),
+                },
+                flaky: detect_flaky.then_some(FlakinessDetector {
+                    history: *flaky_history,
+                    suppress_below: *suppress_flaky_below,
+                }),
+                history_db: config.history_db().map(ToOwned::to_owned),
+                ..CreateIssueOptions::new(label.to_owned(), *kind)
+            };
+            commands::create_issue_from_run::create_issue_from_run(github_cli, run_id, &options)?;
+        },
+        AnalyzeRun {
+            repo,
+            run_id,
+            label,
+            kind,
+            skip_duplicate_check,
+            title_template,
+            body_template,
+        } => {
+            log::info!(
+                "Analyzing GitHub repository: {repo}, run: {run_id}, label: {label}, kind: {kind}"
+            );
+            let canonicalized_repo_url = util::canonicalize_repo_url(repo, &config.host()?);
+            let github_cli =
+                init_github_cli_with(canonicalized_repo_url, &config.github_cli_settings())?;
+            let templates =
+                IssueTemplates::from_files(title_template.as_deref(), body_template.as_deref())?;
+            commands::analyze_run::analyze_run(
                 github_cli,
                 run_id,
                 label,
                 *kind,
-                config.dry_run(),
-                *no_duplicate,
+                config.log_parallelism(),
+                templates,
+                *skip_duplicate_check,
             )?;
         },
+        AnalyzeLog {
+            logs,
+            label,
+            kind,
+            repo,
+            run_id,
+            workflow,
+            branch,
+            format,
+            title_template,
+            body_template,
+        } => {
+            log::info!(
+                "Analyzing {} log file(s), label: {label}, kind: {kind}",
+                logs.len()
+            );
+            let host = config.host()?;
+            let run = LocalRun {
+                repo: repo
+                    .as_deref()
+                    .map(|repo| util::canonicalize_repo_url(repo, &host))
+                    .unwrap_or_default(),
+                run_id: run_id.to_owned(),
+                header: util::RunHeader {
+                    branch: branch.to_owned(),
+                    workflow: workflow.to_owned(),
+                },
+            };
+            let templates =
+                IssueTemplates::from_files(title_template.as_deref(), body_template.as_deref())?;
+            commands::analyze_log::analyze_log(logs, run, label, *kind, templates, *format)?;
+        },
+        TriageRuns {
+            repo,
+            label,
+            kind,
+            workflow,
+            branch,
+            since,
+            until,
+            limit,
+            title_template,
+            body_template,
+        } => {
+            log::info!("Triaging runs of GitHub repository: {repo}, label: {label}, kind: {kind}");
+            let canonicalized_repo_url = util::canonicalize_repo_url(repo, &config.host()?);
+            let github_cli =
+                init_github_cli_with(canonicalized_repo_url, &config.github_cli_settings())?;
+            let templates =
+                IssueTemplates::from_files(title_template.as_deref(), body_template.as_deref())?;
+            let settings = config.settings()?;
+            let owners = OwnershipResolver::from_settings(settings.owners, github_cli.as_ref())?;
+            let filter = RunFilter {
+                workflow: workflow.to_owned(),
+                branch: branch.to_owned(),
+                status: Some("failure".to_string()),
+                created_since: since.to_owned(),
+                created_until: until.to_owned(),
+                limit: *limit,
+            };
+            let options = CreateIssueOptions {
+                templates,
+                dry_run: config.dry_run(),
+                no_duplicate: true,
+                issue_per: IssuePer::Job,
+                log_parallelism: config.log_parallelism(),
+                owners,
+                labels: LabelPolicy::new(settings.labels),
+                history_db: config.history_db().map(ToOwned::to_owned),
+                ..CreateIssueOptions::new(label.to_owned(), *kind)
+            };
+            commands::triage_runs::triage_runs(github_cli, &filter, &options)?;
+        },
+        SyncLabels { repo, label } => {
+            log::info!("Syncing labels of GitHub repository: {repo}, label: {label}");
+            let canonicalized_repo_url = util::canonicalize_repo_url(repo, &config.host()?);
+            let github_cli =
+                init_github_cli_with(canonicalized_repo_url, &config.github_cli_settings())?;
+            let policy = LabelPolicy::new(config.settings()?.labels);
+            commands::sync_labels::sync_labels(github_cli, label, &policy, config.dry_run())?;
+        },
+        Serve {
+            bind,
+            label,
+            kind,
+            issue_per,
+            title_template,
+            body_template,
+        } => {
+            let secret = std::env::var(WEBHOOK_SECRET_ENV)
+                .ok()
+                .filter(|secret| !secret.is_empty())
+                .ok_or_else(|| format!("{WEBHOOK_SECRET_ENV} must be set to the webhook secret"))?;
+            let templates =
+                IssueTemplates::from_files(title_template.as_deref(), body_template.as_deref())?;
+            let settings = config.settings()?;
+            let create_issue = CreateIssueOptions {
+                templates,
+                dry_run: config.dry_run(),
+                no_duplicate: true,
+                issue_per: *issue_per,
+                log_parallelism: config.log_parallelism(),
+                labels: LabelPolicy::new(settings.labels),
+                history_db: config.history_db().map(ToOwned::to_owned),
+                ..CreateIssueOptions::new(label.to_owned(), *kind)
+            };
+            let options = RepoIssueOptions {
+                github_cli: config.github_cli_settings(),
+                owners: settings.owners,
+                create_issue,
+            };
+            commands::serve::serve(bind, secret.into_bytes(), settings.serve, options)?;
+        },
+        Watch {
+            repo,
+            label,
+            kind,
+            issue_per,
+            interval,
+            state_file,
+            once,
+            title_template,
+            body_template,
+        } => {
+            let templates =
+                IssueTemplates::from_files(title_template.as_deref(), body_template.as_deref())?;
+            let settings = config.settings()?;
+            let host = config.host()?;
+            let mut repos: Vec<String> = settings
+                .watch
+                .repos
+                .iter()
+                .chain(repo)
+                .map(|repo| util::canonicalize_repo_url(repo, &host))
+                .collect();
+            repos.sort();
+            repos.dedup();
+            if repos.is_empty() {
+                return Err("No repositories to watch, pass --repo or set [watch] repos".into());
+            }
+            let create_issue = CreateIssueOptions {
+                templates,
+                dry_run: config.dry_run(),
+                no_duplicate: true,
+                issue_per: *issue_per,
+                log_parallelism: config.log_parallelism(),
+                labels: LabelPolicy::new(settings.labels),
+                history_db: config.history_db().map(ToOwned::to_owned),
+                ..CreateIssueOptions::new(label.to_owned(), *kind)
+            };
+            let options = WatchOptions {
+                repos,
+                interval: Duration::from_secs(*interval),
+                state_file: state_file.to_owned(),
+                once: *once,
+                issue: RepoIssueOptions {
+                    github_cli: config.github_cli_settings(),
+                    owners: settings.owners,
+                    create_issue,
+                },
+            };
+            commands::watch::watch(&settings.watch, &options)?;
+        },
+        Report {
+            repo,
+            kind,
+            workflow,
+            branch,
+            until,
+            days,
+            limit,
+            top,
+            format,
+        } => {
+            log::info!("Reporting on GitHub repository: {repo}, kind: {kind}");
+            let canonicalized_repo_url = util::canonicalize_repo_url(repo, &config.host()?);
+            let github_cli =
+                init_github_cli_with(canonicalized_repo_url, &config.github_cli_settings())?;
+            let until = until
+                .to_owned()
+                .unwrap_or_else(|| util::iso_date_days_ago(0));
+            let filter = RunFilter {
+                workflow: workflow.to_owned(),
+                branch: branch.to_owned(),
+                limit: *limit,
+                ..Default::default()
+            };
+            commands::report::report(
+                github_cli,
+                &filter,
+                Period::ending(&until, *days)?,
+                *kind,
+                config.log_parallelism(),
+                *top,
+                *format,
+            )?;
+        },
+        History { command } => {
+            let path = config
+                .history_db()
+                .ok_or("The history subcommands require --history-db")?;
+            let mut db = HistoryDb::open(path)?;
+            let host = config.host()?;
+            let history_query = |query: &commands::HistoryQueryArgs| HistoryQuery {
+                repo: query
+                    .repo
+                    .as_deref()
+                    .map(|repo| util::canonicalize_repo_url(repo, &host)),
+                since: query.since.to_owned(),
+                limit: query.limit,
+            };
+            match command {
+                HistoryCommand::Sync {
+                    repo,
+                    workflow,
+                    branch,
+                    limit,
+                } => {
+                    log::info!("Recording runs of GitHub repository: {repo}");
+                    let canonicalized_repo_url = util::canonicalize_repo_url(repo, &config.host()?);
+                    let github_cli = init_github_cli_with(
+                        canonicalized_repo_url,
+                        &config.github_cli_settings(),
+                    )?;
+                    let filter = RunFilter {
+                        workflow: workflow.to_owned(),
+                        branch: branch.to_owned(),
+                        limit: *limit,
+                        ..Default::default()
+                    };
+                    commands::history::sync(github_cli, &mut db, &filter, config.dry_run())?;
+                },
+                HistoryCommand::TopJobs { query } => {
+                    commands::history::top_jobs(&db, &history_query(query))?;
+                },
+                HistoryCommand::TopFingerprints { query } => {
+                    commands::history::top_fingerprints(&db, &history_query(query))?;
+                },
+                HistoryCommand::Timeline { job, query } => {
+                    commands::history::timeline(&db, job, &history_query(query))?;
+                },
+                HistoryCommand::Mttr { job, query } => {
+                    commands::history::mttr(&db, job.as_deref(), &history_query(query))?;
+                },
+            }
+        },
         LocateFailureLog { kind, input_file } => {
             log::info!("Locating failure log for kind: {kind}");
             commands::locate_failure_log::locate_failure_log(*kind, input_file.as_ref())?;
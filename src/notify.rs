@@ -0,0 +1,434 @@
+//! Notify chat services about failed runs via webhooks
+//!
+//! Supports Slack ([Block Kit](https://api.slack.com/block-kit)), Microsoft Teams
+//! ([Adaptive Cards](https://adaptivecards.io/)) and a generic JSON payload containing the parsed
+//! [Issue].
+use std::{error::Error, time::Duration};
+
+use clap::ValueEnum;
+use serde_json::{json, Value};
+use strum::Display;
+
+use crate::issue::{Issue, IssueOutcome};
+
+/// Maximum length of the text in a Slack section block
+const SLACK_SECTION_MAX_LEN: usize = 3000;
+/// Maximum length of the text in a Slack header block
+const SLACK_HEADER_MAX_LEN: usize = 150;
+/// How long to wait for a webhook to connect and to respond if no other timeout is given
+pub const DEFAULT_WEBHOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The payload format of a webhook
+#[derive(ValueEnum, Display, Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[strum(serialize_all = "lowercase")]
+pub enum WebhookFormat {
+    /// Slack incoming webhook with Block Kit blocks
+    Slack,
+    /// Microsoft Teams incoming webhook with an Adaptive Card
+    Teams,
+    /// The parsed issue as JSON
+    #[default]
+    Json,
+}
+
+/// Sends a notification about a failed run to a webhook
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notifier {
+    url: String,
+    format: WebhookFormat,
+    timeout: Duration,
+}
+
+impl Notifier {
+    pub fn new(url: String, format: WebhookFormat) -> Self {
+        Self {
+            url,
+            format,
+            timeout: DEFAULT_WEBHOOK_TIMEOUT,
+        }
+    }
+
+    /// Set how long to wait for the webhook to connect and to respond
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn format(&self) -> WebhookFormat {
+        self.format
+    }
+
+    /// The JSON payload for the failed run in the format of the webhook
+    pub fn payload(&self, issue: &Issue, outcome: &IssueOutcome) -> Value {
+        let issue_url = match outcome {
+            IssueOutcome::Created(url) | IssueOutcome::Reopened(url) => Some(url.as_str()),
+            IssueOutcome::DuplicateOf(_) | IssueOutcome::NotCreated | IssueOutcome::Suppressed => {
+                None
+            },
+        };
+        match self.format {
+            WebhookFormat::Slack => slack_payload(issue, issue_url),
+            WebhookFormat::Teams => teams_payload(issue, issue_url),
+            WebhookFormat::Json => json_payload(issue, issue_url),
+        }
+    }
+
+    /// Post the notification to the webhook
+    ///
+    /// # Errors
+    /// Returns an error if the request fails or the webhook responds with an error status
+    pub fn notify(&self, issue: &Issue, outcome: &IssueOutcome) -> Result<(), Box<dyn Error>> {
+        log::info!("Sending {} notification to webhook", self.format);
+        let payload = self.payload(issue, outcome);
+        log::trace!("Webhook payload: {payload}");
+        let agent = ureq::AgentBuilder::new()
+            .timeout_connect(self.timeout)
+            .timeout_read(self.timeout)
+            .build();
+        match agent.post(&self.url).send_json(payload) {
+            Ok(response) => {
+                log::debug!("Webhook responded with status {}", response.status());
+                Ok(())
+            },
+            Err(ureq::Error::Status(code, response)) => Err(format!(
+                "Webhook responded with status {code}: {}",
+                response.into_string().unwrap_or_default()
+            )
+            .into()),
+            Err(e) => Err(format!("Failed to send webhook notification: {e}").into()),
+        }
+    }
+}
+
+/// Send notifications unless the failure is a duplicate of an existing issue or a suppressed flaky
+/// failure
+///
+/// Notifications are a side channel, so a webhook that fails is logged and the other webhooks are
+/// still notified.
+pub fn notify_all(notifiers: &[Notifier], issue: &Issue, outcome: &IssueOutcome, dry_run: bool) {
+    if notifiers.is_empty() {
+        return;
+    }
+    match outcome {
+        IssueOutcome::DuplicateOf(url) => {
+            log::info!("Not sending notifications for a known failure (duplicate of {url})");
+            return;
+        },
+        IssueOutcome::Suppressed => {
+            log::info!("Not sending notifications for a suppressed flaky failure");
+            return;
+        },
+        IssueOutcome::Created(_) | IssueOutcome::Reopened(_) | IssueOutcome::NotCreated => (),
+    }
+    for notifier in notifiers {
+        if dry_run {
+            log::info!(
+                "DRY RUN MODE! Would send {} notification: {}",
+                notifier.format(),
+                notifier.payload(issue, outcome)
+            );
+        } else if let Err(e) = notifier.notify(issue, outcome) {
+            log::warn!("Failed to send {} notification: {e}", notifier.format());
+        }
+    }
+}
+
+fn slack_payload(issue: &Issue, issue_url: Option<&str>) -> Value {
+    let mut run_text = format!("*Run:* <{}|{}>", issue.run_link(), issue.run_id());
+    if !issue.workflow().is_empty() {
+        run_text.push_str(&format!(" of *{}*", issue.workflow()));
+    }
+    if !issue.branch().is_empty() {
+        run_text.push_str(&format!(" on `{}`", issue.branch()));
+    }
+    if let Some(url) = issue_url {
+        run_text.push_str(&format!("\n*Issue:* {url}"));
+    }
+    let mut blocks = vec![
+        json!({
+            "type": "header",
+            "text": { "type": "plain_text", "text": truncate(issue.title(), SLACK_HEADER_MAX_LEN) },
+        }),
+        json!({ "type": "section", "text": { "type": "mrkdwn", "text": run_text } }),
+    ];
+    for job in issue.failed_jobs() {
+        let header = format!(
+            "*<{}|{}>* failed at `{}`\n",
+            job.url(),
+            job.name(),
+            job.failed_step()
+        );
+        // Leave room for the header and the code block delimiters
+        let header = truncate(&header, SLACK_SECTION_MAX_LEN - 6);
+        let max_summary_len = (SLACK_SECTION_MAX_LEN - 6).saturating_sub(header.chars().count());
+        let text = format!(
+            "{header}```{}```",
+            truncate(job.error_message().summary().trim(), max_summary_len)
+        );
+        blocks.push(json!({ "type": "section", "text": { "type": "mrkdwn", "text": text } }));
+    }
+    json!({ "text": issue.title(), "blocks": blocks })
+}
+
+fn teams_payload(issue: &Issue, issue_url: Option<&str>) -> Value {
+    let mut body = vec![
+        json!({
+            "type": "TextBlock",
+            "size": "Large",
+            "weight": "Bolder",
+            "text": issue.title(),
+            "wrap": true,
+        }),
+        json!({
+            "type": "FactSet",
+            "facts": [
+                { "title": "Run", "value": issue.run_id() },
+                { "title": "Workflow", "value": issue.workflow() },
+                { "title": "Branch", "value": issue.branch() },
+            ],
+        }),
+    ];
+    for job in issue.failed_jobs() {
+        body.push(json!({
+            "type": "TextBlock",
+            "weight": "Bolder",
+            "text": format!("{} failed at {}", job.name(), job.failed_step()),
+            "wrap": true,
+        }));
+        body.push(json!({
+            "type": "TextBlock",
+            "fontType": "Monospace",
+            "text": job.error_message().summary().trim(),
+            "wrap": true,
+        }));
+    }
+    let mut actions = vec![json!({
+        "type": "Action.OpenUrl",
+        "title": "View run",
+        "url": issue.run_link(),
+    })];
+    if let Some(url) = issue_url {
+        actions.push(json!({ "type": "Action.OpenUrl", "title": "View issue", "url": url }));
+    }
+    json!({
+        "type": "message",
+        "attachments": [{
+            "contentType": "application/vnd.microsoft.card.adaptive",
+            "contentUrl": null,
+            "content": {
+                "$schema": "http://adaptivecards.io/schemas/adaptive-card.json",
+                "type": "AdaptiveCard",
+                "version": "1.4",
+                "body": body,
+                "actions": actions,
+            },
+        }],
+    })
+}
+
+fn json_payload(issue: &Issue, issue_url: Option<&str>) -> Value {
+    json!({
+        "event": "workflow_run_failed",
+        "issue_url": issue_url,
+        "issue": issue,
+    })
+}
+
+/// Truncate to at most `max_len` characters, marking the truncation with an ellipsis
+fn truncate(s: &str, max_len: usize) -> String {
+    if s.chars().count() <= max_len {
+        return s.to_string();
+    }
+    if max_len == 0 {
+        return String::new();
+    }
+    let mut truncated: String = s.chars().take(max_len - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{err_msg_parse::ErrorMessageSummary, issue::FailedJob};
+    use pretty_assertions::assert_eq;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    fn example_issue() -> Issue {
+        Issue::new(
+            "7858139663".to_string(),
+            "https://github.com/luftkode/distro-template/actions/runs/7858139663".to_string(),
+            "Use template and build image".to_string(),
+            "master".to_string(),
+            vec![FailedJob::new(
+                "Test template xilinx".to_string(),
+                "21442749267".to_string(),
+                "https://github.com/luftkode/distro-template/actions/runs/7858139663/job/21442749267".to_string(),
+                "📦 Build yocto image".to_string(),
+                ErrorMessageSummary::Other("ERROR: something broke\n".to_string()),
+            )],
+            "bug".to_string(),
+        )
+    }
+
+    /// Serve a single HTTP request with the given status and return the request body
+    fn serve_one_request(status: u16) -> (String, std::thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+                if let Some(len) = line.to_lowercase().strip_prefix("content-length:") {
+                    content_length = len.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 {status} Whatever\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            )
+            .unwrap();
+            String::from_utf8(body).unwrap()
+        });
+        (url, handle)
+    }
+
+    #[test]
+    fn test_notify_posts_payload() {
+        let (url, handle) = serve_one_request(200);
+        let issue = example_issue();
+        let outcome = IssueOutcome::Created("https://github.com/foo/bar/issues/1".to_string());
+        let notifier = Notifier::new(url, WebhookFormat::Json);
+        notifier.notify(&issue, &outcome).unwrap();
+
+        let body: Value = serde_json::from_str(&handle.join().unwrap()).unwrap();
+        assert_eq!(body["issue_url"], "https://github.com/foo/bar/issues/1");
+        assert_eq!(body["issue"]["run_id"], "7858139663");
+        assert_eq!(body["issue"]["fingerprint"], issue.fingerprint());
+    }
+
+    #[test]
+    fn test_notify_error_status() {
+        let (url, handle) = serve_one_request(500);
+        let notifier = Notifier::new(url, WebhookFormat::Slack);
+        let res = notifier.notify(&example_issue(), &IssueOutcome::NotCreated);
+        handle.join().unwrap();
+        assert!(res.unwrap_err().to_string().contains("status 500"));
+    }
+
+    #[test]
+    fn test_notify_timeout() {
+        // Accepts the connection but never responds
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let notifier =
+            Notifier::new(url, WebhookFormat::Json).with_timeout(Duration::from_millis(100));
+        let res = notifier.notify(&example_issue(), &IssueOutcome::NotCreated);
+        assert!(res.is_err());
+    }
+
+    /// Assert that no notification is sent for the outcome
+    fn assert_not_notified(outcome: &IssueOutcome) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        notify_all(
+            &[Notifier::new(url, WebhookFormat::Json)],
+            &example_issue(),
+            outcome,
+            false,
+        );
+        listener.set_nonblocking(true).unwrap();
+        let err = listener.accept().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::WouldBlock);
+    }
+
+    #[test]
+    fn test_duplicates_are_not_notified() {
+        assert_not_notified(&IssueOutcome::DuplicateOf(
+            "https://github.com/foo/bar/issues/1".to_string(),
+        ));
+    }
+
+    #[test]
+    fn test_suppressed_are_not_notified() {
+        assert_not_notified(&IssueOutcome::Suppressed);
+    }
+
+    #[test]
+    fn test_slack_payload() {
+        let notifier = Notifier::new(String::new(), WebhookFormat::Slack);
+        let payload = notifier.payload(&example_issue(), &IssueOutcome::NotCreated);
+        assert_eq!(payload["blocks"][0]["type"], "header");
+        assert_eq!(
+            payload["blocks"][1]["text"]["text"],
+            "*Run:* <https://github.com/luftkode/distro-template/actions/runs/7858139663|7858139663> of *Use template and build image* on `master`"
+        );
+        assert_eq!(
+            payload["blocks"][2]["text"]["text"],
+            "*<https://github.com/luftkode/distro-template/actions/runs/7858139663/job/21442749267|Test template xilinx>* failed at `📦 Build yocto image`\n```ERROR: something broke```"
+        );
+    }
+
+    #[test]
+    fn test_teams_payload() {
+        let notifier = Notifier::new(String::new(), WebhookFormat::Teams);
+        let payload = notifier.payload(
+            &example_issue(),
+            &IssueOutcome::Created("https://github.com/foo/bar/issues/1".to_string()),
+        );
+        let card = &payload["attachments"][0]["content"];
+        assert_eq!(card["type"], "AdaptiveCard");
+        assert_eq!(
+            card["body"][2]["text"],
+            "Test template xilinx failed at 📦 Build yocto image"
+        );
+        assert_eq!(
+            card["actions"][1]["url"],
+            "https://github.com/foo/bar/issues/1"
+        );
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("abc", 3), "abc");
+        assert_eq!(truncate("abcd", 3), "ab…");
+        assert_eq!(truncate("abcd", 1), "…");
+        assert_eq!(truncate("abcd", 0), "");
+        assert_eq!(truncate("", 0), "");
+    }
+
+    #[test]
+    fn test_slack_payload_long_job_name() {
+        let issue = Issue::new(
+            "7858139663".to_string(),
+            "https://github.com/luftkode/distro-template/actions/runs/7858139663".to_string(),
+            "Use template and build image".to_string(),
+            "master".to_string(),
+            vec![FailedJob::new(
+                "x".repeat(2 * SLACK_SECTION_MAX_LEN),
+                "21442749267".to_string(),
+                "https://github.com/luftkode/distro-template/actions/runs/7858139663/job/21442749267".to_string(),
+                "📦 Build yocto image".to_string(),
+                ErrorMessageSummary::Other("ERROR: something broke\n".to_string()),
+            )],
+            "bug".to_string(),
+        );
+        let notifier = Notifier::new(String::new(), WebhookFormat::Slack);
+        let payload = notifier.payload(&issue, &IssueOutcome::NotCreated);
+        let text = payload["blocks"][2]["text"]["text"].as_str().unwrap();
+        assert!(text.chars().count() <= SLACK_SECTION_MAX_LEN);
+    }
+}
@@ -0,0 +1,341 @@
+//! Resolve the owners of a failure to assign issues to
+//!
+//! Owners are looked up in order:
+//!
+//! 1. The owners of the failed job in the `[owners.jobs]` config table
+//! 2. The owners of the failed recipe in the `[owners.recipes]` config table
+//! 3. The CODEOWNERS of the paths found in the error message, e.g. the `.bb` recipe in a Yocto
+//!    `Task (...) failed` line or the source file of a compiler error
+//!
+//! Owners are GitHub users (`@alice`) or teams (`@org/team`). Only users can be assigned to an
+//! issue, teams are mentioned in the issue body instead.
+use std::path::{Component, Path, PathBuf};
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::{
+    gh::GitHub,
+    issue::{FailedJob, Issue},
+    settings::OwnersSettings,
+};
+
+/// Locations GitHub looks for a CODEOWNERS file, in order
+pub const CODEOWNERS_LOCATIONS: [&str; 3] = [".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"];
+
+/// The rules of a CODEOWNERS file
+///
+/// See <https://docs.github.com/en/repositories/managing-your-repositorys-settings-and-features/customizing-your-repository/about-code-owners>
+#[derive(Debug, Default, Clone)]
+pub struct CodeOwners {
+    rules: Vec<(Regex, Vec<String>)>,
+}
+
+impl CodeOwners {
+    /// Parse a CODEOWNERS file, invalid patterns are skipped with a warning
+    pub fn parse(contents: &str) -> Self {
+        let rules = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| {
+                let mut parts = line.split_whitespace();
+                let pattern = parts.next()?;
+                let owners = parts
+                    .take_while(|part| !part.starts_with('#'))
+                    .map(str::to_string)
+                    .collect();
+                match pattern_to_regex(pattern) {
+                    Ok(regex) => Some((regex, owners)),
+                    Err(e) => {
+                        log::warn!("Skipping invalid CODEOWNERS pattern {pattern}: {e}");
+                        None
+                    },
+                }
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// The owners of a path, the last matching rule takes precedence
+    ///
+    /// The path does not have to be relative to the repository root (e.g. a path in the build
+    /// container), it matches if any of its suffixes matches a rule.
+    ///
+    /// # Example
+    /// ```
+    /// # use gh_workflow_parser::owners::CodeOwners;
+    /// let codeowners = CodeOwners::parse("*.bb @yocto\n/meta-custom/ @alice @org/bsp\n");
+    /// assert_eq!(codeowners.owners_of("/app/meta-custom/recipes/foo.bb".as_ref()), ["@alice", "@org/bsp"]);
+    /// assert_eq!(codeowners.owners_of("/app/poky/meta/sqlite3.bb".as_ref()), ["@yocto"]);
+    /// assert!(codeowners.owners_of("/app/README.md".as_ref()).is_empty());
+    /// ```
+    pub fn owners_of(&self, path: &Path) -> &[String] {
+        let components = normalize(path);
+        let suffixes: Vec<String> = (0..components.len())
+            .map(|start| components[start..].join("/"))
+            .collect();
+        self.rules
+            .iter()
+            .rev()
+            .find(|(regex, _)| suffixes.iter().any(|suffix| regex.is_match(suffix)))
+            .map_or(&[], |(_, owners)| owners.as_slice())
+    }
+}
+
+/// Resolves the owners of failed jobs from the config and CODEOWNERS
+#[derive(Debug, Default, Clone)]
+pub struct OwnershipResolver {
+    settings: OwnersSettings,
+    codeowners: Option<CodeOwners>,
+}
+
+impl OwnershipResolver {
+    pub fn new(settings: OwnersSettings, codeowners: Option<CodeOwners>) -> Self {
+        Self {
+            settings,
+            codeowners,
+        }
+    }
+
+    /// Create a resolver from the settings, reading the CODEOWNERS file from the local file or
+    /// the repository if enabled
+    pub fn from_settings(
+        settings: OwnersSettings,
+        github_cli: &dyn GitHub,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let codeowners = match &settings.codeowners_file {
+            Some(path) => Some(
+                std::fs::read_to_string(path)
+                    .map_err(|e| format!("Failed to read CODEOWNERS file {path:?}: {e}"))?,
+            ),
+            None if settings.codeowners => {
+                let mut found = None;
+                for location in CODEOWNERS_LOCATIONS {
+                    if let Some(contents) = github_cli.repo_file(None, location)? {
+                        log::debug!("Using the CODEOWNERS file at {location}");
+                        found = Some(contents);
+                        break;
+                    }
+                }
+                if found.is_none() {
+                    log::warn!("No CODEOWNERS file found in the repository");
+                }
+                found
+            },
+            None => None,
+        };
+        Ok(Self::new(
+            settings,
+            codeowners.as_deref().map(CodeOwners::parse),
+        ))
+    }
+
+    /// The owners of a failed job without duplicates
+    pub fn owners_of(&self, job: &FailedJob) -> Vec<String> {
+        let mut owners: Vec<String> = vec![];
+        let mut add = |new: &[String]| {
+            for owner in new {
+                if !owners.contains(owner) {
+                    owners.push(owner.to_owned());
+                }
+            }
+        };
+        if let Some(job_owners) = self.settings.jobs.get(job.name()) {
+            add(job_owners);
+        }
+        if let Some(recipe_owners) = job
+            .error_message()
+            .recipe()
+            .and_then(|recipe| self.settings.recipes.get(&recipe))
+        {
+            add(recipe_owners);
+        }
+        if let Some(codeowners) = &self.codeowners {
+            for path in paths_from_error(job.error_message().summary()) {
+                add(codeowners.owners_of(&path));
+            }
+        }
+        owners
+    }
+
+    /// Set the owners of all failed jobs of the issue
+    pub fn assign(&self, issue: Issue) -> Issue {
+        let owners: Vec<String> = issue
+            .failed_jobs()
+            .iter()
+            .flat_map(|job| self.owners_of(job))
+            .fold(vec![], |mut owners, owner| {
+                if !owners.contains(&owner) {
+                    owners.push(owner);
+                }
+                owners
+            });
+        if !owners.is_empty() {
+            log::info!("Owners of the failure: {}", owners.join(", "));
+        }
+        issue.with_owners(owners)
+    }
+}
+
+/// The GitHub users among the owners (without the `@`), teams and email addresses can't be
+/// assigned
+///
+/// # Example
+/// ```
+/// # use gh_workflow_parser::owners::assignable_users;
+/// let owners = ["@alice".to_string(), "@org/team".to_string(), "bob@example.com".to_string()];
+/// assert_eq!(assignable_users(&owners), ["alice"]);
+/// ```
+pub fn assignable_users(owners: &[String]) -> Vec<String> {
+    owners
+        .iter()
+        .filter_map(|owner| owner.strip_prefix('@'))
+        .filter(|user| !user.contains('/'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Paths mentioned in an error message that can be looked up in CODEOWNERS
+///
+/// # Example
+/// ```
+/// # use gh_workflow_parser::owners::paths_from_error;
+/// # use std::path::PathBuf;
+/// let error = r#"ERROR: Task (virtual:native:/app/yocto/build/../poky/meta/recipes-support/sqlite/sqlite3_3.43.2.bb:do_fetch) failed with exit code '1'
+/// src/main.c:12:5: error: expected ';' before '}' token"#;
+/// assert_eq!(
+///     paths_from_error(error),
+///     [
+///         PathBuf::from("/app/yocto/build/../poky/meta/recipes-support/sqlite/sqlite3_3.43.2.bb"),
+///         PathBuf::from("src/main.c"),
+///     ]
+/// );
+/// ```
+pub fn paths_from_error(error: &str) -> Vec<PathBuf> {
+    static YOCTO_TASK_RECIPE: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r"Task \((?:[\w-]+:)*(?P<path>/?[^\s:()]+\.bb(?:append)?):do_\w+\)").unwrap()
+    });
+    static COMPILER_ERROR_FILE: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r"(?m)(?:^|\s)(?P<path>[\w./+-]+\.\w+):\d+(?::\d+)?: (?:fatal )?error").unwrap()
+    });
+    let mut paths: Vec<PathBuf> = vec![];
+    for regex in [&*YOCTO_TASK_RECIPE, &*COMPILER_ERROR_FILE] {
+        for captures in regex.captures_iter(error) {
+            let path = PathBuf::from(&captures["path"]);
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+    paths
+}
+
+/// The normal components of a path with `..` resolved
+fn normalize(path: &Path) -> Vec<String> {
+    let mut components: Vec<String> = vec![];
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => components.push(part.to_string_lossy().to_string()),
+            Component::ParentDir => {
+                components.pop();
+            },
+            Component::RootDir | Component::CurDir | Component::Prefix(_) => (),
+        }
+    }
+    components
+}
+
+/// Convert a CODEOWNERS (gitignore style) pattern to a regex matching relative paths
+fn pattern_to_regex(pattern: &str) -> Result<Regex, regex::Error> {
+    let directory_only = pattern.ends_with('/');
+    let trimmed = pattern.trim_end_matches('/');
+    // A pattern with a slash at the start or in the middle is relative to the root
+    let anchored = trimmed.contains('/');
+    let trimmed = trimmed.trim_start_matches('/');
+
+    let mut regex = String::from(if anchored { "^" } else { "^(?:.*/)?" });
+    let mut chars = trimmed.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            },
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push_str(if directory_only { "/.*$" } else { "(?:/.*)?$" });
+    Regex::new(&regex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::err_msg_parse::ErrorMessageSummary;
+    use pretty_assertions::assert_eq;
+
+    const CODEOWNERS: &str = r#"
+# Default owners
+*       @luftkode/maintainers
+*.bb    @yocto-team # inline comment
+/meta-custom/ @alice
+docs/**/*.md @bob
+"#;
+
+    fn job(name: &str, summary: &str) -> FailedJob {
+        FailedJob::new(
+            name.to_string(),
+            "1".to_string(),
+            "https://example.com".to_string(),
+            "build".to_string(),
+            ErrorMessageSummary::Other(summary.to_string()),
+        )
+    }
+
+    #[test]
+    fn test_codeowners_precedence() {
+        let codeowners = CodeOwners::parse(CODEOWNERS);
+        let owners = |path: &str| codeowners.owners_of(Path::new(path)).to_vec();
+        assert_eq!(owners("src/main.rs"), ["@luftkode/maintainers"]);
+        assert_eq!(owners("/app/poky/meta/foo.bb"), ["@yocto-team"]);
+        assert_eq!(owners("/app/yocto/meta-custom/recipes/foo.bb"), ["@alice"]);
+        assert_eq!(owners("/app/yocto/build/../meta-custom/x.bb"), ["@alice"]);
+        assert_eq!(owners("docs/a/b/c.md"), ["@bob"]);
+        assert_eq!(owners("docs/c.md"), ["@bob"]);
+    }
+
+    #[test]
+    fn test_pattern_to_regex() {
+        let matches = |pattern: &str, path: &str| pattern_to_regex(pattern).unwrap().is_match(path);
+        assert!(matches("build/", "build/logs/a.log"));
+        assert!(!matches("build/", "build"));
+        assert!(matches("apps", "src/apps/main.rs"));
+        assert!(!matches("/apps", "src/apps/main.rs"));
+        assert!(matches("src/*.rs", "src/main.rs"));
+        assert!(!matches("src/*.rs", "src/bin/main.rs"));
+    }
+
+    #[test]
+    fn test_resolver_order_and_dedup() {
+        let mut settings = OwnersSettings::default();
+        settings
+            .jobs
+            .insert("xilinx".to_string(), vec!["@carol".to_string()]);
+        let resolver = OwnershipResolver::new(settings, Some(CodeOwners::parse(CODEOWNERS)));
+
+        let failed_job = job(
+            "xilinx",
+            "ERROR: Task (/app/yocto/meta-custom/recipes/foo_1.0.bb:do_compile) failed with exit code '1'\nERROR: Task (/app/yocto/meta-custom/recipes/bar_1.0.bb:do_compile) failed with exit code '1'\n",
+        );
+        assert_eq!(resolver.owners_of(&failed_job), ["@carol", "@alice"]);
+        assert!(resolver.owners_of(&job("other", "no paths")).is_empty());
+    }
+}
@@ -0,0 +1,171 @@
+//! Milestones and GitHub Projects (v2) for created issues
+//!
+//! Created issues can be attached to a milestone and added to a project board with field values,
+//! e.g. `--project luftkode/3 --project-field Status=Triage`. Field values are
+//! [minijinja](https://docs.rs/minijinja) templates rendered with the same context as the
+//! [issue templates](crate::issue::template), e.g.
+//! `--project-field 'Component={{ failed_jobs[0].recipe }}'`.
+use std::{error::Error, str::FromStr};
+
+use minijinja::Environment;
+
+use crate::{
+    gh::{util::issue_number_from_url, GitHub, ProjectRef},
+    issue::Issue,
+};
+
+/// A project field and the template of its value given as `<name>=<value>`
+///
+/// # Example
+/// ```
+/// # use gh_workflow_parser::project::ProjectField;
+/// let field: ProjectField = "Severity=High".parse().unwrap();
+/// assert_eq!(field.name, "Severity");
+/// assert_eq!(field.value, "High");
+/// assert!("Severity".parse::<ProjectField>().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectField {
+    pub name: String,
+    pub value: String,
+}
+
+impl FromStr for ProjectField {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((name, value)) if !name.trim().is_empty() => Ok(Self {
+                name: name.trim().to_string(),
+                value: value.to_string(),
+            }),
+            _ => Err(format!("Expected <name>=<value>, got: {s}")),
+        }
+    }
+}
+
+/// The milestone and project board created issues are added to
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ProjectOptions {
+    /// Title of the milestone
+    pub milestone: Option<String>,
+    pub project: Option<ProjectRef>,
+    /// Fields to set on the project item
+    pub fields: Vec<ProjectField>,
+}
+
+impl ProjectOptions {
+    /// The project fields with the values rendered for the issue
+    ///
+    /// # Errors
+    /// Returns an error if a value is not a valid template
+    pub fn render_fields(&self, issue: &Issue) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+        let mut env = Environment::new();
+        // Render `none` (e.g. an unknown recipe) as empty so the field is left unset
+        env.set_formatter(|out, state, value| {
+            if value.is_none() {
+                Ok(())
+            } else {
+                minijinja::escape_formatter(out, state, value)
+            }
+        });
+        let ctx = issue.template_context();
+        self.fields
+            .iter()
+            .map(|field| {
+                let value = env
+                    .render_str(&field.value, &ctx)
+                    .map_err(|e| format!("Invalid value of project field {}: {e}", field.name))?;
+                Ok((field.name.clone(), value.trim().to_string()))
+            })
+            .collect()
+    }
+
+    /// Set the milestone of the created issue and add it to the project with the field values
+    pub fn apply(
+        &self,
+        github_cli: &dyn GitHub,
+        issue: &Issue,
+        issue_url: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        if let Some(milestone) = &self.milestone {
+            let number = issue_number_from_url(issue_url)
+                .ok_or_else(|| format!("Could not get the issue number from {issue_url}"))?;
+            github_cli.set_issue_milestone(None, number, milestone)?;
+            log::info!("Set milestone of {issue_url} to {milestone}");
+        }
+        if let Some(project) = &self.project {
+            let item_id = github_cli.add_issue_to_project(project, issue_url)?;
+            log::info!("Added {issue_url} to project {project}");
+            for (field, value) in self.render_fields(issue)? {
+                if value.is_empty() {
+                    log::warn!("Project field {field} is empty for {issue_url}, leaving it unset");
+                    continue;
+                }
+                github_cli.set_project_item_field(project, &item_id, &field, &value)?;
+                log::debug!("Set project field {field}={value} of {issue_url}");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        commands::{create_issue_from_run::issue_from_run, WorkflowKind},
+        gh::gh_cli_fake::GitHubCliFake,
+    };
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_apply_project_options() {
+        let github_cli = GitHubCliFake::new("https://github.com/luftkode/distro-template".into());
+        github_cli
+            .create_label(None, "bug", "FF0000", "", false)
+            .unwrap();
+        let issue =
+            issue_from_run(&github_cli, "7858139663", "bug", WorkflowKind::Yocto, 1).unwrap();
+        let url = github_cli
+            .create_issue(
+                None,
+                issue.title(),
+                &issue.body().unwrap(),
+                &["bug".to_string()],
+                &[],
+            )
+            .unwrap();
+        let options = ProjectOptions {
+            milestone: Some("v1.0".to_string()),
+            project: Some("luftkode/3".parse().unwrap()),
+            fields: vec![
+                "Status=Triage".parse().unwrap(),
+                "Component={{ failed_jobs[0].name }}".parse().unwrap(),
+                "Recipe={{ failed_jobs[0].recipe }}".parse().unwrap(),
+            ],
+        };
+        options.apply(&github_cli, &issue, &url).unwrap();
+
+        assert_eq!(github_cli.issues()[0].milestone.as_deref(), Some("v1.0"));
+        let item = &github_cli.project_items()[0];
+        assert_eq!(item.project, "luftkode/3");
+        assert_eq!(item.issue_url, url);
+        assert_eq!(item.fields["Status"], "Triage");
+        assert_eq!(item.fields["Component"], "Test template xilinx");
+        // The recipe of the hardcoded fake log can't be determined so the field is left unset
+        assert!(!item.fields.contains_key("Recipe"));
+    }
+
+    #[test]
+    fn test_invalid_field_template() {
+        let github_cli = GitHubCliFake::new("https://github.com/luftkode/distro-template".into());
+        let issue =
+            issue_from_run(&github_cli, "7858139663", "bug", WorkflowKind::Yocto, 1).unwrap();
+        let options = ProjectOptions {
+            fields: vec!["Status={% if %}".parse().unwrap()],
+            ..Default::default()
+        };
+        assert!(options.render_fields(&issue).is_err());
+    }
+}
@@ -0,0 +1,526 @@
+//! Failure statistics of the workflow runs of a repository over a period
+//!
+//! The runs of the period are aggregated by job, failed step, parser, Yocto failure kind and recipe,
+//! and compared to the previous period of the same length. The report is rendered as markdown (e.g.
+//! to post as an issue) or JSON.
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+    fmt::Write as _,
+};
+
+use clap::ValueEnum;
+use serde::Serialize;
+use strum::Display;
+
+use crate::{
+    commands::{create_issue_from_run::issue_from_run, WorkflowKind},
+    gh::{GitHub, RunFilter},
+    util::{human_duration, iso_date_from_unix_days, unix_seconds_from_iso},
+};
+
+/// The output format of a report
+#[derive(ValueEnum, Display, Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[strum(serialize_all = "lowercase")]
+pub enum ReportFormat {
+    /// Markdown tables, e.g. for the body of an issue
+    #[default]
+    Markdown,
+    Json,
+}
+
+/// A range of days (inclusive)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Period {
+    /// ISO 8601 date of the first day
+    pub since: String,
+    /// ISO 8601 date of the last day
+    pub until: String,
+}
+
+impl Period {
+    /// The `days` days ending on `until` (YYYY-MM-DD)
+    ///
+    /// # Example
+    /// ```
+    /// # use gh_workflow_parser::report::Period;
+    /// let period = Period::ending("2024-03-01", 7).unwrap();
+    /// assert_eq!(period.since, "2024-02-24");
+    /// assert_eq!(period.previous().since, "2024-02-17");
+    /// assert_eq!(period.previous().until, "2024-02-23");
+    /// assert!(Period::ending("last week", 7).is_err());
+    /// ```
+    pub fn ending(until: &str, days: u64) -> Result<Self, Box<dyn Error>> {
+        let until_day = unix_seconds_from_iso(until)
+            .ok_or_else(|| format!("Expected a date (YYYY-MM-DD), got: {until}"))?
+            .div_euclid(86400);
+        Ok(Self::from_days(until_day - days as i64 + 1, until_day))
+    }
+
+    fn from_days(first_day: i64, last_day: i64) -> Self {
+        Self {
+            since: iso_date_from_unix_days(first_day),
+            until: iso_date_from_unix_days(last_day),
+        }
+    }
+
+    /// The period of the same length right before this one
+    pub fn previous(&self) -> Self {
+        let day = |date: &str| {
+            unix_seconds_from_iso(date)
+                .unwrap_or_default()
+                .div_euclid(86400)
+        };
+        let (first_day, last_day) = (day(&self.since), day(&self.until));
+        Self::from_days(2 * first_day - last_day - 1, first_day - 1)
+    }
+}
+
+/// How often something occurred
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Count {
+    pub name: String,
+    pub count: usize,
+}
+
+/// Counts occurrences by name, most common first
+#[derive(Debug, Default)]
+struct Counter(BTreeMap<String, usize>);
+
+impl Counter {
+    fn add(&mut self, name: impl Into<String>) {
+        *self.0.entry(name.into()).or_default() += 1;
+    }
+
+    fn into_counts(self) -> Vec<Count> {
+        let mut counts: Vec<Count> = self
+            .0
+            .into_iter()
+            .map(|(name, count)| Count { name, count })
+            .collect();
+        // Stable sort keeps names with the same count in alphabetical order
+        counts.sort_by_key(|count| std::cmp::Reverse(count.count));
+        counts
+    }
+}
+
+/// The results of a job over a period
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JobStats {
+    pub name: String,
+    /// Runs the job passed or failed in
+    pub runs: usize,
+    pub failures: usize,
+    pub failure_rate: f64,
+    /// Mean time from the start of the job until it failed, from the step timings
+    pub mean_time_to_failure_seconds: Option<i64>,
+}
+
+/// A failure that occurred in the period, identified by the fingerprint of the failed job
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FingerprintStats {
+    pub fingerprint: String,
+    pub count: usize,
+    pub job: String,
+    pub failed_step: String,
+    pub parser: String,
+    pub failure_label: Option<String>,
+    pub recipe: Option<String>,
+}
+
+/// The failure statistics of a period
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PeriodStats {
+    #[serde(flatten)]
+    pub period: Period,
+    /// Completed runs (passed or failed)
+    pub runs: usize,
+    pub failed_runs: usize,
+    pub failure_rate: f64,
+    /// Jobs with the highest failure rate first
+    pub jobs: Vec<JobStats>,
+    pub failed_steps: Vec<Count>,
+    pub parsers: Vec<Count>,
+    /// Yocto failure kinds e.g. `do_fetch`
+    pub failure_kinds: Vec<Count>,
+    pub recipes: Vec<Count>,
+    /// Most common first
+    pub fingerprints: Vec<FingerprintStats>,
+}
+
+fn rate(part: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 / total as f64
+    }
+}
+
+impl PeriodStats {
+    /// Fetch the runs of the period that match the filter and aggregate them
+    ///
+    /// The failed jobs are analyzed like `create-issue-from-run` does, runs that can't be analyzed
+    /// are only counted. Runs that didn't pass or fail (e.g. cancelled) are left out.
+    pub fn collect(
+        github_cli: &dyn GitHub,
+        filter: &RunFilter,
+        period: Period,
+        kind: WorkflowKind,
+        log_parallelism: usize,
+    ) -> Result<Self, Box<dyn Error>> {
+        let filter = RunFilter {
+            status: None,
+            created_since: Some(period.since.clone()),
+            created_until: Some(period.until.clone()),
+            ..filter.clone()
+        };
+        let runs: Vec<_> = github_cli
+            .list_runs(None, &filter)?
+            .into_iter()
+            .filter(|run| matches!(run.conclusion.as_str(), "success" | "failure"))
+            .collect();
+        log::info!(
+            "Aggregating {} run(s) from {} to {}",
+            runs.len(),
+            period.since,
+            period.until
+        );
+
+        #[derive(Default)]
+        struct JobResults {
+            runs: usize,
+            failures: usize,
+            times_to_failure: Vec<i64>,
+        }
+        let mut jobs: BTreeMap<String, JobResults> = BTreeMap::new();
+        let mut failed_steps = Counter::default();
+        let mut parsers = Counter::default();
+        let mut failure_kinds = Counter::default();
+        let mut recipes = Counter::default();
+        let mut fingerprints: Vec<FingerprintStats> = vec![];
+        let mut failed_runs = 0;
+        for run in &runs {
+            for job in github_cli.run_jobs(None, run.database_id, run.attempt)? {
+                let results = jobs.entry(job.name.clone()).or_default();
+                match job.conclusion.as_str() {
+                    "success" => results.runs += 1,
+                    "failure" => {
+                        results.runs += 1;
+                        results.failures += 1;
+                        results.times_to_failure.extend(job.time_to_failure());
+                    },
+                    _ => (),
+                }
+            }
+            if run.conclusion != "failure" {
+                continue;
+            }
+            failed_runs += 1;
+            // The label is only used for the labels of the issue
+            let run_id = run.database_id.to_string();
+            let issue = match issue_from_run(github_cli, &run_id, "", kind, log_parallelism) {
+                Ok(issue) => issue,
+                Err(e) => {
+                    log::warn!("Could not analyze run {}: {e}", run.database_id);
+                    continue;
+                },
+            };
+            for job in issue.failed_jobs() {
+                failed_steps.add(job.failed_step());
+                parsers.add(job.error_message().parser());
+                if let Some(label) = job.failure_label() {
+                    failure_kinds.add(label);
+                }
+                if let Some(recipe) = job.error_message().recipe() {
+                    recipes.add(recipe);
+                }
+                match fingerprints
+                    .iter_mut()
+                    .find(|f| f.fingerprint == job.fingerprint())
+                {
+                    Some(stats) => stats.count += 1,
+                    None => fingerprints.push(FingerprintStats {
+                        fingerprint: job.fingerprint().to_string(),
+                        count: 1,
+                        job: job.name().to_string(),
+                        failed_step: job.failed_step().to_string(),
+                        parser: job.error_message().parser().to_string(),
+                        failure_label: job.failure_label(),
+                        recipe: job.error_message().recipe(),
+                    }),
+                }
+            }
+        }
+
+        let mut jobs: Vec<JobStats> = jobs
+            .into_iter()
+            .filter(|(_, results)| results.runs > 0)
+            .map(|(name, results)| JobStats {
+                name,
+                runs: results.runs,
+                failures: results.failures,
+                failure_rate: rate(results.failures, results.runs),
+                mean_time_to_failure_seconds: (!results.times_to_failure.is_empty()).then(|| {
+                    results.times_to_failure.iter().sum::<i64>()
+                        / results.times_to_failure.len() as i64
+                }),
+            })
+            .collect();
+        jobs.sort_by(|a, b| b.failure_rate.total_cmp(&a.failure_rate));
+        fingerprints.sort_by_key(|stats| std::cmp::Reverse(stats.count));
+        Ok(Self {
+            period,
+            runs: runs.len(),
+            failed_runs,
+            failure_rate: rate(failed_runs, runs.len()),
+            jobs,
+            failed_steps: Counter::into_counts(failed_steps),
+            parsers: Counter::into_counts(parsers),
+            failure_kinds: Counter::into_counts(failure_kinds),
+            recipes: Counter::into_counts(recipes),
+            fingerprints,
+        })
+    }
+}
+
+/// The change from the previous period
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Trend {
+    /// Change of the failure rate (-1 to 1), `None` if there were no runs in the previous period
+    pub failure_rate_change: Option<f64>,
+    pub failed_runs_change: i64,
+    /// Fingerprints of failures that didn't occur in the previous period
+    pub new_fingerprints: Vec<String>,
+}
+
+impl Trend {
+    pub fn new(current: &PeriodStats, previous: &PeriodStats) -> Self {
+        let previous_fingerprints: BTreeSet<&str> = previous
+            .fingerprints
+            .iter()
+            .map(|f| f.fingerprint.as_str())
+            .collect();
+        Self {
+            failure_rate_change: (previous.runs > 0)
+                .then_some(current.failure_rate - previous.failure_rate),
+            failed_runs_change: current.failed_runs as i64 - previous.failed_runs as i64,
+            new_fingerprints: current
+                .fingerprints
+                .iter()
+                .filter(|f| !previous_fingerprints.contains(f.fingerprint.as_str()))
+                .map(|f| f.fingerprint.clone())
+                .collect(),
+        }
+    }
+}
+
+/// Failure statistics of a period compared to the previous period
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Report {
+    pub repo: String,
+    pub workflow: Option<String>,
+    pub branch: Option<String>,
+    pub current: PeriodStats,
+    pub previous: PeriodStats,
+    pub trend: Trend,
+}
+
+impl Report {
+    /// Collect the statistics of the period and the previous period
+    ///
+    /// Only the `top` most common failures, failed steps, failure kinds and recipes are kept.
+    pub fn collect(
+        github_cli: &dyn GitHub,
+        filter: &RunFilter,
+        period: Period,
+        kind: WorkflowKind,
+        log_parallelism: usize,
+        top: usize,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut previous =
+            PeriodStats::collect(github_cli, filter, period.previous(), kind, log_parallelism)?;
+        let mut current = PeriodStats::collect(github_cli, filter, period, kind, log_parallelism)?;
+        let trend = Trend::new(&current, &previous);
+        for stats in [&mut current, &mut previous] {
+            stats.failed_steps.truncate(top);
+            stats.failure_kinds.truncate(top);
+            stats.recipes.truncate(top);
+            stats.fingerprints.truncate(top);
+        }
+        Ok(Self {
+            repo: github_cli.default_repo().to_string(),
+            workflow: filter.workflow.clone(),
+            branch: filter.branch.clone(),
+            current,
+            previous,
+            trend,
+        })
+    }
+
+    /// The report as markdown
+    pub fn to_markdown(&self) -> String {
+        let (current, previous) = (&self.current, &self.previous);
+        let mut md = format!(
+            "# CI health: {} ({} to {})\n\n",
+            self.workflow.as_deref().unwrap_or("all workflows"),
+            current.period.since,
+            current.period.until
+        );
+        let _ = writeln!(md, "Repository: {}", self.repo);
+        if let Some(branch) = &self.branch {
+            let _ = writeln!(md, "Branch: `{branch}`");
+        }
+        let _ = writeln!(
+            md,
+            "\n| | This period | Previous period ({} to {}) | Trend |\n|---|---|---|---|",
+            previous.period.since, previous.period.until
+        );
+        let _ = writeln!(
+            md,
+            "| Runs | {} | {} | {:+} |",
+            current.runs,
+            previous.runs,
+            current.runs as i64 - previous.runs as i64
+        );
+        let _ = writeln!(
+            md,
+            "| Failed runs | {} | {} | {:+} |",
+            current.failed_runs, previous.failed_runs, self.trend.failed_runs_change
+        );
+        let _ = writeln!(
+            md,
+            "| Failure rate | {} | {} | {} |",
+            percent(current.failure_rate),
+            percent(previous.failure_rate),
+            rate_trend(self.trend.failure_rate_change)
+        );
+
+        if !current.jobs.is_empty() {
+            md.push_str("\n## Jobs\n\n| Job | Runs | Failures | Failure rate | Trend | Mean time to failure |\n|---|---|---|---|---|---|\n");
+            for job in &current.jobs {
+                let change = previous
+                    .jobs
+                    .iter()
+                    .find(|previous_job| previous_job.name == job.name)
+                    .map(|previous_job| job.failure_rate - previous_job.failure_rate);
+                let _ = writeln!(
+                    md,
+                    "| {} | {} | {} | {} | {} | {} |",
+                    cell(&job.name),
+                    job.runs,
+                    job.failures,
+                    percent(job.failure_rate),
+                    rate_trend(change),
+                    job.mean_time_to_failure_seconds
+                        .map_or("-".to_string(), human_duration)
+                );
+            }
+        }
+
+        if !current.fingerprints.is_empty() {
+            md.push_str("\n## Most common failures\n\n| Failures | Job | Failed step | Failure | Recipe | Fingerprint |\n|---|---|---|---|---|---|\n");
+            for failure in &current.fingerprints {
+                let new = if self.trend.new_fingerprints.contains(&failure.fingerprint) {
+                    " (new)"
+                } else {
+                    ""
+                };
+                let _ = writeln!(
+                    md,
+                    "| {} | {} | {} | {} | {} | `{}`{new} |",
+                    failure.count,
+                    cell(&failure.job),
+                    cell(&failure.failed_step),
+                    cell(failure.failure_label.as_deref().unwrap_or(&failure.parser)),
+                    cell(failure.recipe.as_deref().unwrap_or("-")),
+                    failure.fingerprint
+                );
+            }
+        }
+
+        for (title, counts) in [
+            ("Failed steps", &current.failed_steps),
+            ("Parsers", &current.parsers),
+            ("Yocto failures", &current.failure_kinds),
+            ("Recipes", &current.recipes),
+        ] {
+            if counts.is_empty() {
+                continue;
+            }
+            let _ = writeln!(md, "\n## {title}\n");
+            for Count { name, count } in counts {
+                let _ = writeln!(md, "- {name}: {count}");
+            }
+        }
+        md
+    }
+}
+
+/// A rate as a percentage e.g. `12.5%`
+fn percent(rate: f64) -> String {
+    format!("{:.1}%", rate * 100.0)
+}
+
+/// A change of a rate in percentage points e.g. `▲ +12.5 pp`
+fn rate_trend(change: Option<f64>) -> String {
+    match change {
+        None => "-".to_string(),
+        Some(change) if change.abs() < 0.0005 => "=".to_string(),
+        Some(change) if change > 0.0 => format!("▲ +{:.1} pp", change * 100.0),
+        Some(change) => format!("▼ {:.1} pp", change * 100.0),
+    }
+}
+
+/// Escape a value for a markdown table cell
+fn cell(value: &str) -> String {
+    value.replace('|', "\\|").replace('\n', " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_period() {
+        let period = Period::ending("2024-03-01", 1).unwrap();
+        assert_eq!(period.since, "2024-03-01");
+        assert_eq!(period.until, "2024-03-01");
+        assert_eq!(
+            period.previous(),
+            Period {
+                since: "2024-02-29".to_string(),
+                until: "2024-02-29".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_rate_trend() {
+        assert_eq!(rate_trend(Some(0.125)), "▲ +12.5 pp");
+        assert_eq!(rate_trend(Some(-0.5)), "▼ -50.0 pp");
+        assert_eq!(rate_trend(Some(0.0)), "=");
+        assert_eq!(rate_trend(None), "-");
+    }
+
+    #[test]
+    fn test_counter() {
+        let mut counter = Counter::default();
+        for name in ["b", "a", "b", "c"] {
+            counter.add(name);
+        }
+        let counts: Vec<(String, usize)> = counter
+            .into_counts()
+            .into_iter()
+            .map(|c| (c.name, c.count))
+            .collect();
+        assert_eq!(
+            counts,
+            [
+                ("b".to_string(), 2),
+                ("a".to_string(), 1),
+                ("c".to_string(), 1)
+            ]
+        );
+    }
+}
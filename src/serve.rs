@@ -0,0 +1,305 @@
+//! Receive GitHub `workflow_run` webhooks
+//!
+//! The `serve` subcommand is an HTTP server for a repository or organization webhook with the
+//! `Workflow runs` event. When a run completes with a failure, an issue is created for it like
+//! `create-issue-from-run` does, so failures are handled even if the workflow was cancelled or its
+//! runner died before a final step could run.
+//!
+//! Every request must be signed with the webhook secret in the `X-Hub-Signature-256` header, the
+//! secret is read from the `GITHUB_WEBHOOK_SECRET` environment variable. A payload can be posted
+//! locally by signing it with [signature], e.g. with `openssl`:
+//!
+//! ```text
+//! SIG=$(openssl dgst -sha256 -hmac "$GITHUB_WEBHOOK_SECRET" < payload.json | cut -d' ' -f2)
+//! curl -H 'X-GitHub-Event: workflow_run' -H "X-Hub-Signature-256: sha256=$SIG" \
+//!     --data-binary @payload.json http://127.0.0.1:8080/
+//! ```
+//!
+//! See <https://docs.github.com/en/webhooks/webhook-events-and-payloads#workflow_run>
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+
+use crate::settings::ServeSettings;
+
+/// Environment variable with the secret of the webhook
+pub const WEBHOOK_SECRET_ENV: &str = "GITHUB_WEBHOOK_SECRET";
+/// Header with the name of the event e.g. `workflow_run`
+pub const EVENT_HEADER: &str = "X-GitHub-Event";
+/// Header with the HMAC-SHA256 signature of the payload
+pub const SIGNATURE_HEADER: &str = "X-Hub-Signature-256";
+
+/// The `X-Hub-Signature-256` header value of a payload signed with the secret
+///
+/// # Example
+/// ```
+/// # use gh_workflow_parser::serve::{signature, verify_signature};
+/// let sig = signature(b"It's a Secret to Everybody", b"Hello, World!");
+/// assert_eq!(
+///     sig,
+///     "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17"
+/// );
+/// assert!(verify_signature(b"It's a Secret to Everybody", b"Hello, World!", &sig));
+/// assert!(!verify_signature(b"wrong secret", b"Hello, World!", &sig));
+/// ```
+pub fn signature(secret: &[u8], payload: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(payload);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Whether the `X-Hub-Signature-256` header value is the signature of the payload, compared in
+/// constant time
+pub fn verify_signature(secret: &[u8], payload: &[u8], header: &str) -> bool {
+    let Some(Ok(signature)) = header.trim().strip_prefix("sha256=").map(hex::decode) else {
+        return false;
+    };
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(payload);
+    mac.verify_slice(&signature).is_ok()
+}
+
+/// The fields of a `workflow_run` event that are used
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct WorkflowRunEvent {
+    /// e.g. `requested`, `in_progress` or `completed`
+    pub action: String,
+    pub workflow_run: WorkflowRun,
+    pub repository: Repository,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct WorkflowRun {
+    pub id: u64,
+    /// The name of the workflow
+    pub name: String,
+    /// The path of the workflow file e.g. `.github/workflows/build.yml`
+    pub path: String,
+    pub head_branch: String,
+    /// `None` until the run completes
+    pub conclusion: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Repository {
+    /// e.g. `luftkode/distro-template`
+    pub full_name: String,
+    pub html_url: String,
+}
+
+/// A failed run to create an issue for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailedRun {
+    /// The URL of the repository
+    pub repo: String,
+    pub run_id: u64,
+}
+
+/// How a webhook request was handled
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebhookResponse {
+    /// The HTTP status code
+    pub status: u16,
+    pub message: String,
+    /// The failed run to create an issue for, if the event is accepted
+    pub failed_run: Option<FailedRun>,
+}
+
+impl WebhookResponse {
+    fn new(status: u16, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+            failed_run: None,
+        }
+    }
+}
+
+/// The `<owner>/<repo>` part of a repository given as `<owner>/<repo>` or URL
+fn repo_full_name(repo: &str) -> &str {
+    let repo = match repo.split_once("://") {
+        Some((_scheme, rest)) => rest.split_once('/').map_or("", |(_host, path)| path),
+        None => repo,
+    };
+    repo.trim_matches('/')
+}
+
+impl ServeSettings {
+    /// Why the event is ignored according to the settings, `None` if it is handled
+    pub fn ignores(&self, event: &WorkflowRunEvent) -> Option<String> {
+        let run = &event.workflow_run;
+        let workflow_file = run.path.rsplit('/').next().unwrap_or_default();
+        if !self.repos.is_empty()
+            && !self
+                .repos
+                .iter()
+                .any(|repo| repo_full_name(repo) == event.repository.full_name)
+        {
+            Some(format!(
+                "repository {} is not handled",
+                event.repository.full_name
+            ))
+        } else if !self.workflows.is_empty()
+            && !self
+                .workflows
+                .iter()
+                .any(|workflow| workflow == &run.name || workflow == workflow_file)
+        {
+            Some(format!("workflow {} is not handled", run.name))
+        } else if !self.branches.is_empty() && !self.branches.contains(&run.head_branch) {
+            Some(format!("branch {} is not handled", run.head_branch))
+        } else {
+            None
+        }
+    }
+}
+
+/// Verify and handle a webhook request
+///
+/// Only `completed` `workflow_run` events of failed runs that aren't ignored by the settings are
+/// accepted, other events are acknowledged and ignored.
+pub fn handle_webhook(
+    secret: &[u8],
+    settings: &ServeSettings,
+    event: Option<&str>,
+    signature: Option<&str>,
+    payload: &[u8],
+) -> WebhookResponse {
+    if !signature.is_some_and(|signature| verify_signature(secret, payload, signature)) {
+        return WebhookResponse::new(401, format!("Missing or invalid {SIGNATURE_HEADER}"));
+    }
+    match event {
+        Some("ping") => return WebhookResponse::new(200, "pong"),
+        Some("workflow_run") => (),
+        Some(event) => return WebhookResponse::new(200, format!("Ignored {event} event")),
+        None => return WebhookResponse::new(400, format!("Missing {EVENT_HEADER}")),
+    }
+    let event: WorkflowRunEvent = match serde_json::from_slice(payload) {
+        Ok(event) => event,
+        Err(e) => return WebhookResponse::new(400, format!("Invalid workflow_run payload: {e}")),
+    };
+    let run = &event.workflow_run;
+    if event.action != "completed" {
+        return WebhookResponse::new(200, format!("Ignored {} run {}", event.action, run.id));
+    }
+    if run.conclusion.as_deref() != Some("failure") {
+        return WebhookResponse::new(
+            200,
+            format!(
+                "Ignored run {} with conclusion {}",
+                run.id,
+                run.conclusion.as_deref().unwrap_or("none")
+            ),
+        );
+    }
+    if let Some(reason) = settings.ignores(&event) {
+        return WebhookResponse::new(200, format!("Ignored run {}: {reason}", run.id));
+    }
+    WebhookResponse {
+        status: 202,
+        message: format!("Creating an issue for run {}", run.id),
+        failed_run: Some(FailedRun {
+            repo: event.repository.html_url.clone(),
+            run_id: run.id,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const SECRET: &[u8] = b"s3cret";
+    const PAYLOAD: &str = include_str!("../tests/fixtures/webhooks/workflow_run_failure.json");
+
+    fn handle(settings: &ServeSettings, event: &str, payload: &str) -> WebhookResponse {
+        let sig = signature(SECRET, payload.as_bytes());
+        handle_webhook(
+            SECRET,
+            settings,
+            Some(event),
+            Some(&sig),
+            payload.as_bytes(),
+        )
+    }
+
+    #[test]
+    fn test_accepts_failed_run() {
+        let response = handle(&ServeSettings::default(), "workflow_run", PAYLOAD);
+        assert_eq!(response.status, 202);
+        assert_eq!(
+            response.failed_run,
+            Some(FailedRun {
+                repo: "https://github.com/luftkode/distro-template".to_string(),
+                run_id: 7858139663,
+            })
+        );
+    }
+
+    #[test]
+    fn test_rejects_invalid_signature() {
+        let settings = ServeSettings::default();
+        let response = handle_webhook(
+            SECRET,
+            &settings,
+            Some("workflow_run"),
+            Some("sha256=00"),
+            PAYLOAD.as_bytes(),
+        );
+        assert_eq!(response.status, 401);
+        let response = handle_webhook(
+            SECRET,
+            &settings,
+            Some("workflow_run"),
+            None,
+            PAYLOAD.as_bytes(),
+        );
+        assert_eq!(response.status, 401);
+    }
+
+    #[test]
+    fn test_ignores_other_events() {
+        let settings = ServeSettings::default();
+        assert_eq!(handle(&settings, "ping", "{}").message, "pong");
+        assert_eq!(handle(&settings, "push", "{}").failed_run, None);
+        assert_eq!(handle(&settings, "workflow_run", "{}").status, 400);
+
+        let in_progress = PAYLOAD.replace(r#""action": "completed""#, r#""action": "in_progress""#);
+        let response = handle(&settings, "workflow_run", &in_progress);
+        assert_eq!(response.message, "Ignored in_progress run 7858139663");
+        let success = PAYLOAD.replace(r#""conclusion": "failure""#, r#""conclusion": "success""#);
+        let response = handle(&settings, "workflow_run", &success);
+        assert_eq!(response.failed_run, None);
+    }
+
+    #[test]
+    fn test_settings_filter() {
+        let handled = |settings: ServeSettings| {
+            handle(&settings, "workflow_run", PAYLOAD)
+                .failed_run
+                .is_some()
+        };
+        assert!(handled(ServeSettings {
+            repos: vec!["https://github.com/luftkode/distro-template/".to_string()],
+            workflows: vec!["use-template.yml".to_string()],
+            branches: vec!["master".to_string()],
+        }));
+        assert!(handled(ServeSettings {
+            workflows: vec!["Use template and build image".to_string()],
+            ..Default::default()
+        }));
+        assert!(!handled(ServeSettings {
+            repos: vec!["luftkode/other".to_string()],
+            ..Default::default()
+        }));
+        assert!(!handled(ServeSettings {
+            workflows: vec!["build.yml".to_string()],
+            ..Default::default()
+        }));
+        assert!(!handled(ServeSettings {
+            branches: vec!["main".to_string()],
+            ..Default::default()
+        }));
+    }
+}
@@ -0,0 +1,227 @@
+//! Settings loaded from a TOML configuration file given with `--config`
+//!
+//! Every section and key is optional, e.g.
+//!
+//! ```toml
+//! [github]
+//! # Host of repositories given as `<owner>/<repo>` e.g. a GitHub Enterprise Server
+//! host = "github.example.com"
+//!
+//! [owners]
+//! # Look up owners of the failing paths in the repository's CODEOWNERS file
+//! codeowners = true
+//! # Owners of Yocto recipes and jobs, matched by name
+//! recipes = { "sqlite3-native" = ["@alice"] }
+//! jobs = { "Test template xilinx" = ["@luftkode/xilinx"] }
+//!
+//! [labels]
+//! # Namespace of the generated failure labels e.g. `yocto/do_fetch`
+//! prefix = "yocto/"
+//! # Labels that may be created when an issue is created (`*` matches any suffix)
+//! auto_create = ["bug", "yocto/*"]
+//! failure = { color = "FBCA04", description = "Failed Yocto task" }
+//! definitions = { "CI scheduled build" = { color = "0E8A16" } }
+//!
+//! [serve]
+//! # Only handle `workflow_run` webhooks of these repositories, workflows and branches
+//! repos = ["luftkode/distro-template"]
+//! workflows = ["build.yml"]
+//! branches = ["main"]
+//!
+//! [watch]
+//! # Repositories to poll for failed runs, and the workflows (by name) and branches to handle
+//! repos = ["luftkode/distro-template"]
+//! workflows = ["Use template and build image"]
+//! branches = ["main"]
+//! ```
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub github: GitHubSettings,
+    pub owners: OwnersSettings,
+    pub labels: LabelSettings,
+    pub serve: ServeSettings,
+    pub watch: WatchSettings,
+}
+
+impl Settings {
+    /// Read the settings from a TOML file
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read or is not valid
+    pub fn from_file(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read config file {path:?}: {e}"))?;
+        Self::from_toml(&contents).map_err(|e| format!("Invalid config file {path:?}: {e}").into())
+    }
+
+    pub fn from_toml(toml: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(toml)
+    }
+}
+
+/// Settings for accessing GitHub
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GitHubSettings {
+    /// Host of repositories given as `<owner>/<repo>` and of requests that aren't for a repository,
+    /// e.g. of a GitHub Enterprise Server
+    pub host: Option<String>,
+}
+
+/// Settings for resolving the owners of a failure, see [owners](crate::owners)
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OwnersSettings {
+    /// Look up owners of the paths in the error messages in the repository's CODEOWNERS file
+    pub codeowners: bool,
+    /// A local CODEOWNERS file to use instead of the one in the repository
+    pub codeowners_file: Option<PathBuf>,
+    /// Owners by recipe name
+    pub recipes: BTreeMap<String, Vec<String>>,
+    /// Owners by job name
+    pub jobs: BTreeMap<String, Vec<String>>,
+}
+
+/// The label policy, see [labels](crate::labels)
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LabelSettings {
+    /// Prefix of the generated failure labels
+    pub prefix: String,
+    /// Labels that may be created when an issue is created, all labels if not set, the issue label
+    /// is always created
+    pub auto_create: Option<Vec<String>>,
+    /// Style of the issue label (`--label`)
+    pub issue: LabelStyle,
+    /// Style of the failure labels
+    pub failure: LabelStyle,
+    /// Style of the `flaky` label
+    pub flaky: LabelStyle,
+    /// Style by label name, overrides the category styles, also synced by `sync-labels`
+    pub definitions: BTreeMap<String, LabelStyle>,
+}
+
+/// Color and description of a label, unset values fall back to the defaults of the category
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LabelStyle {
+    /// 6 character hex code e.g. `FF0000`
+    pub color: Option<String>,
+    pub description: Option<String>,
+}
+
+/// Which `workflow_run` webhooks the `serve` subcommand handles, see [serve](crate::serve)
+///
+/// An empty list matches everything.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServeSettings {
+    /// Repositories as `<owner>/<repo>` or URL
+    pub repos: Vec<String>,
+    /// Workflow names or file names e.g. `build.yml`
+    pub workflows: Vec<String>,
+    pub branches: Vec<String>,
+}
+
+/// Which repositories the `watch` subcommand polls and which of their runs it handles, see
+/// [watch](crate::watch)
+///
+/// Empty `workflows` and `branches` match every run.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WatchSettings {
+    /// Repositories as `<owner>/<repo>` or URL
+    pub repos: Vec<String>,
+    /// Workflow names
+    pub workflows: Vec<String>,
+    pub branches: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse_settings() {
+        let settings = Settings::from_toml(
+            r#"
+[github]
+host = "github.example.com"
+
+[owners]
+codeowners = true
+recipes = { "sqlite3-native" = ["@alice"] }
+
+[owners.jobs]
+"Test template xilinx" = ["@luftkode/xilinx", "@bob"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(settings.github.host.as_deref(), Some("github.example.com"));
+        assert!(settings.owners.codeowners);
+        assert_eq!(settings.owners.recipes["sqlite3-native"], ["@alice"]);
+        assert_eq!(
+            settings.owners.jobs["Test template xilinx"],
+            ["@luftkode/xilinx", "@bob"]
+        );
+    }
+
+    #[test]
+    fn test_parse_label_settings() {
+        let settings = Settings::from_toml(
+            r#"
+[labels]
+prefix = "yocto/"
+auto_create = ["bug", "yocto/*"]
+failure = { color = "FBCA04" }
+
+[labels.definitions."CI scheduled build"]
+color = "0E8A16"
+description = "Scheduled CI"
+"#,
+        )
+        .unwrap();
+        let labels = settings.labels;
+        assert_eq!(labels.prefix, "yocto/");
+        assert_eq!(labels.auto_create.unwrap(), ["bug", "yocto/*"]);
+        assert_eq!(labels.failure.color.as_deref(), Some("FBCA04"));
+        assert_eq!(labels.failure.description, None);
+        assert_eq!(
+            labels.definitions["CI scheduled build"]
+                .description
+                .as_deref(),
+            Some("Scheduled CI")
+        );
+    }
+
+    #[test]
+    fn test_parse_serve_settings() {
+        let settings = Settings::from_toml(
+            r#"
+[serve]
+repos = ["luftkode/distro-template"]
+branches = ["main", "master"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(settings.serve.repos, ["luftkode/distro-template"]);
+        assert!(settings.serve.workflows.is_empty());
+        assert_eq!(settings.serve.branches, ["main", "master"]);
+    }
+
+    #[test]
+    fn test_empty_and_unknown_settings() {
+        assert_eq!(Settings::from_toml("").unwrap(), Settings::default());
+        assert!(Settings::from_toml("[owner]").is_err());
+    }
+}
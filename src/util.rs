@@ -55,6 +55,42 @@ pub fn take_lines_with_failed_jobs(output: String) -> Vec<String> {
         .collect()
 }
 
+/// The branch and workflow name of a run, parsed from the output of `gh run view`
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RunHeader {
+    pub branch: String,
+    pub workflow: String,
+}
+
+/// Parse the branch and workflow name from the header line of the output of `gh run view`
+///
+/// # Example
+/// ```
+/// # use gh_workflow_parser::util::run_header_from_summary;
+/// let summary = r#"
+///     X master Use template and build image · 7858139663
+///     Triggered via schedule about 10 hours ago
+/// "#;
+/// let header = run_header_from_summary(summary).unwrap();
+/// assert_eq!(header.branch, "master");
+/// assert_eq!(header.workflow, "Use template and build image");
+///
+/// // No header line is `None`
+/// assert!(run_header_from_summary("foo bar").is_none());
+/// ```
+pub fn run_header_from_summary(summary: &str) -> Option<RunHeader> {
+    static RE: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r"^\s*\S+\s+(?P<branch>\S+)\s+(?P<workflow>.+?)\s+·\s+[0-9]+\s*$").unwrap()
+    });
+
+    let first_line = summary.lines().find(|line| !line.trim().is_empty())?;
+    let caps = RE.captures(first_line)?;
+    Some(RunHeader {
+        branch: caps["branch"].to_string(),
+        workflow: caps["workflow"].to_string(),
+    })
+}
+
 /// Extract the job IDs from the lines with job information
 pub fn id_from_job_lines(lines: &[String]) -> Vec<String> {
     static RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"ID (?<JOB_ID>[0-9]*)").unwrap());
@@ -95,7 +131,7 @@ pub fn id_from_job_lines(lines: &[String]) -> Vec<String> {
 /// let modified = remove_timestamps(test_str);
 /// assert_eq!(modified, "IDdate: \nother text");
 /// ```
-pub fn remove_timestamps(text: &str) -> std::borrow::Cow<str> {
+pub fn remove_timestamps(text: &str) -> std::borrow::Cow<'_, str> {
     static RE: Lazy<Regex> = Lazy::new(|| {
         Regex::new(
             r"(?x)
@@ -112,6 +148,176 @@ pub fn remove_timestamps(text: &str) -> std::borrow::Cow<str> {
     RE.replace_all(text, "")
 }
 
+/// Normalize text before fingerprinting by removing the parts that vary between otherwise identical
+/// failures such as timestamps, IDs, PIDs and whitespace.
+///
+/// # Example
+/// ```
+/// # use gh_workflow_parser::util::normalize_for_fingerprint;
+/// let a = "ERROR: Logfile of failure stored in: /app/temp/log.do_fetch.21616";
+/// let b = "ERROR: Logfile of failure stored in: /app/temp/log.do_fetch.31337";
+/// assert_eq!(normalize_for_fingerprint(a), normalize_for_fingerprint(b));
+///
+/// let a = "2024-02-10T00:03:45.5797561Z  some   error";
+/// assert_eq!(normalize_for_fingerprint(a), "some error");
+/// ```
+pub fn normalize_for_fingerprint(text: &str) -> String {
+    static TIMESTAMP_RE: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r"[0-9]{4}-[0-9]{2}-[0-9]{2}[T ][0-9]{2}:[0-9]{2}:[0-9]{2}(\.[0-9]+)?Z?")
+            .unwrap()
+    });
+    static NUMBER_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"[0-9]{4,}").unwrap());
+
+    let text = TIMESTAMP_RE.replace_all(text, "");
+    let text = NUMBER_RE.replace_all(&text, "N");
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/// Compute a stable fingerprint of the given parts, parts are separated so that `["ab", "c"]` and
+/// `["a", "bc"]` have different fingerprints.
+///
+/// Uses the 64-bit FNV-1a hash which is stable across platforms and Rust versions, the fingerprint
+/// is formatted as 16 hex characters.
+///
+/// # Example
+/// ```
+/// # use gh_workflow_parser::util::fingerprint;
+/// assert_eq!(fingerprint(&["foo"]), fingerprint(&["foo"]));
+/// assert_ne!(fingerprint(&["ab", "c"]), fingerprint(&["a", "bc"]));
+/// assert_eq!(fingerprint(&["foo"]).len(), 16);
+/// ```
+pub fn fingerprint<S: AsRef<str>>(parts: &[S]) -> String {
+    const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const FNV_PRIME: u64 = 0x100000001b3;
+
+    let hash = parts.iter().fold(FNV_OFFSET_BASIS, |hash, part| {
+        part.as_ref()
+            .bytes()
+            // Separate the parts with a byte that can't appear in UTF-8
+            .chain(std::iter::once(0xFF))
+            .fold(hash, |hash, byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+            })
+    });
+    format!("{hash:016x}")
+}
+
+/// Format a number of days since the Unix epoch as an ISO 8601 date (`YYYY-MM-DD`)
+///
+/// # Example
+/// ```
+/// # use gh_workflow_parser::util::iso_date_from_unix_days;
+/// assert_eq!(iso_date_from_unix_days(0), "1970-01-01");
+/// assert_eq!(iso_date_from_unix_days(19764), "2024-02-11");
+/// assert_eq!(iso_date_from_unix_days(-1), "1969-12-31");
+/// ```
+pub fn iso_date_from_unix_days(days: i64) -> String {
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Parse an ISO 8601 timestamp (`YYYY-MM-DDTHH:MM:SSZ`) or date (`YYYY-MM-DD`, midnight) in UTC
+/// as seconds since the Unix epoch
+///
+/// # Example
+/// ```
+/// # use gh_workflow_parser::util::unix_seconds_from_iso;
+/// assert_eq!(unix_seconds_from_iso("1970-01-02"), Some(86400));
+/// assert_eq!(unix_seconds_from_iso("2024-02-11T00:09:04Z"), Some(1707610144));
+/// assert_eq!(unix_seconds_from_iso("yesterday"), None);
+/// ```
+pub fn unix_seconds_from_iso(timestamp: &str) -> Option<i64> {
+    let (date, time) = timestamp
+        .trim()
+        .trim_end_matches('Z')
+        .split_once('T')
+        .unwrap_or((timestamp.trim(), "00:00:00"));
+    let mut date_parts = date.splitn(3, '-').map(str::parse::<i64>);
+    let (year, month, day) = (
+        date_parts.next()?.ok()?,
+        date_parts.next()?.ok()?,
+        date_parts.next()?.ok()?,
+    );
+    let mut time_parts = time.splitn(3, ':').map(|part| {
+        // Ignore fractional seconds
+        part.split('.').next().unwrap_or_default().parse::<i64>()
+    });
+    let (hours, minutes, seconds) = (
+        time_parts.next()?.ok()?,
+        time_parts.next()?.ok()?,
+        time_parts.next().unwrap_or(Ok(0)).ok()?,
+    );
+    // http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    Some(days * 86400 + hours * 3600 + minutes * 60 + seconds)
+}
+
+/// The current time as an ISO 8601 timestamp in UTC (`YYYY-MM-DDTHH:MM:SSZ`)
+pub fn iso_timestamp_now() -> String {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+    let secs_of_day = now.rem_euclid(86400);
+    format!(
+        "{}T{:02}:{:02}:{:02}Z",
+        iso_date_from_unix_days(now.div_euclid(86400)),
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60
+    )
+}
+
+/// A duration in seconds as days, hours, minutes and seconds e.g. `1d 2h 5m` or `5m 19s`, seconds
+/// are left out of durations longer than an hour
+///
+/// # Example
+/// ```
+/// # use gh_workflow_parser::util::human_duration;
+/// assert_eq!(human_duration(93_900), "1d 2h 5m");
+/// assert_eq!(human_duration(3_600), "1h 0m");
+/// assert_eq!(human_duration(319), "5m 19s");
+/// assert_eq!(human_duration(59), "59s");
+/// ```
+pub fn human_duration(seconds: i64) -> String {
+    let (days, hours, minutes) = (seconds / 86400, seconds % 86400 / 3600, seconds % 3600 / 60);
+    if days > 0 {
+        format!("{days}d {hours}h {minutes}m")
+    } else if hours > 0 {
+        format!("{hours}h {minutes}m")
+    } else if minutes > 0 {
+        format!("{minutes}m {}s", seconds % 60)
+    } else {
+        format!("{seconds}s")
+    }
+}
+
+/// The ISO 8601 date (UTC) of the day `days` days ago
+pub fn iso_date_days_ago(days: u64) -> String {
+    const SECS_PER_DAY: u64 = 24 * 60 * 60;
+    let today = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        / SECS_PER_DAY;
+    iso_date_from_unix_days(today as i64 - days as i64)
+}
+
 /// Parse an absolute path from a string. This assumes that the the first '/' found in the string is the start
 /// of the path.
 /// # Example
@@ -207,6 +413,9 @@ pub fn bzip2_compress(input: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
 
 /// Canonicalize a repository URL to the form `https://{host}/{repo}`
 ///
+/// The host of a repository given with a host, e.g. as URL, is kept, so `host` is only used for
+/// repositories given as `<owner>/<repo>`
+///
 /// # Arguments
 /// * `repo` - The repository URL e.g. `user1/user1-repo`
 /// * `host` - The host for the repository e.g. `github.com`
@@ -227,19 +436,23 @@ pub fn bzip2_compress(input: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
 /// let repo = "https://gitlab.com/foo-org/foo-repo";
 /// let canonicalized = canonicalize_repo_url(repo, "gitlab.com");
 /// assert_eq!(canonicalized, repo);
+///
+/// // The host in the URL takes precedence, e.g. for a GitHub Enterprise Server
+/// let repo = "https://github.example.com/foo-org/foo-repo/";
+/// let canonicalized = canonicalize_repo_url(repo, "github.com");
+/// assert_eq!(canonicalized, "https://github.example.com/foo-org/foo-repo");
+/// let canonicalized = canonicalize_repo_url("github.example.com/foo-org/foo-repo", "github.com");
+/// assert_eq!(canonicalized, "https://github.example.com/foo-org/foo-repo");
 /// ```
 pub fn canonicalize_repo_url(repo: &str, host: &str) -> String {
-    let canonical_prefix: String = format!("https://{host}/");
-    if repo.starts_with("https://") {
-        if repo.starts_with(&canonical_prefix) {
-            repo.to_string()
-        } else {
-            repo.replace("https://", &canonical_prefix)
-        }
-    } else if repo.starts_with(&format!("{host}/")) {
-        repo.replace(&format!("{host}/"), &canonical_prefix)
+    let repo = repo.trim_end_matches('/');
+    if repo.contains("://") {
+        repo.to_string()
+    } else if repo.split('/').count() > 2 {
+        // `<host>/<owner>/<repo>`
+        format!("https://{repo}")
     } else {
-        format!("{canonical_prefix}{repo}")
+        format!("https://{host}/{repo}")
     }
 }
 
@@ -391,7 +604,7 @@ https://github.com/cli/cli/releases/tag/v2.4.0"#;
 
     #[test]
     pub fn test_remove_timestamps_log_text() {
-        const LOG_TEXT: &'static str = r#"**Run ID**: 8072883145 [LINK TO RUN](https://github.com/luftkode/distro-template/actions/runs/8072883145)
+        const LOG_TEXT: &str = r#"**Run ID**: 8072883145 [LINK TO RUN](https://github.com/luftkode/distro-template/actions/runs/8072883145)
 
         **1 job failed:**
         - **`Test template xilinx`**
@@ -402,7 +615,7 @@ https://github.com/cli/cli/releases/tag/v2.4.0"#;
         **Log:** https://github.com/luftkode/distro-template/actions/runs/8072883145/job/22055505284
         "#;
 
-        const EXPECTED_MODIFIED: &'static str = r#"**Run ID**:[LINK TO RUN](https://github.com/luftkode/distro-template/actions/runs
+        const EXPECTED_MODIFIED: &str = r#"**Run ID**:[LINK TO RUN](https://github.com/luftkode/distro-template/actions/runs
 
         **1 job failed:**
         - **`Test template xilinx`**
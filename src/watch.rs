@@ -0,0 +1,344 @@
+//! Poll repositories for newly failed runs
+//!
+//! The `watch` subcommand is an alternative to [serve](crate::serve) for repositories where a
+//! webhook can't be installed. It lists the recent runs of each repository at an interval and
+//! creates issues for the runs that failed since the last poll.
+//!
+//! The newest seen run of each repository and the seen runs that weren't processed yet are kept in
+//! a state file, so a restarted watcher continues where it stopped. Completed runs are processed
+//! oldest first, a run that is still in progress is processed once it completes.
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{gh::GhRun, settings::WatchSettings};
+
+/// The longest delay between polls after repeated errors
+pub const MAX_BACKOFF: Duration = Duration::from_secs(60 * 60);
+
+/// The state of the watcher that is kept between restarts
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WatchState {
+    /// The state of each repository by URL
+    #[serde(default)]
+    pub repos: BTreeMap<String, RepoState>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepoState {
+    /// The newest run that was seen, every older run was processed or is pending
+    pub last_run_id: u64,
+    /// Runs that were seen but not processed yet, e.g. because they were in progress
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub pending: BTreeSet<u64>,
+}
+
+impl RepoState {
+    /// The state when starting to watch a repository: completed runs were handled before the
+    /// repository was watched, runs in progress are processed once they complete
+    pub fn starting_at(runs: &[GhRun]) -> Self {
+        Self {
+            last_run_id: runs
+                .iter()
+                .map(|run| run.database_id)
+                .max()
+                .unwrap_or_default(),
+            pending: runs
+                .iter()
+                .filter(|run| run.conclusion.is_empty())
+                .map(|run| run.database_id)
+                .collect(),
+        }
+    }
+
+    /// Add the runs newer than the last seen run to the pending runs
+    ///
+    /// Pending runs that are no longer listed (e.g. deleted or pushed out by newer runs) are
+    /// dropped, as they can't be seen completing.
+    pub fn track(&mut self, runs: &[GhRun]) {
+        if runs.is_empty() {
+            return;
+        }
+        let listed: BTreeSet<u64> = runs.iter().map(|run| run.database_id).collect();
+        self.pending.retain(|run_id| {
+            let is_listed = listed.contains(run_id);
+            if !is_listed {
+                log::warn!("Pending run {run_id} is no longer listed, it won't be processed");
+            }
+            is_listed
+        });
+        self.pending
+            .extend(listed.iter().filter(|&&run_id| run_id > self.last_run_id));
+        if let Some(&newest) = listed.last() {
+            self.last_run_id = self.last_run_id.max(newest);
+        }
+    }
+
+    /// The pending runs that completed, oldest first
+    ///
+    /// # Example
+    /// ```
+    /// # use gh_workflow_parser::{gh::GhRun, watch::RepoState};
+    /// let run = |id, conclusion: &str| GhRun {
+    ///     database_id: id,
+    ///     conclusion: conclusion.to_string(),
+    ///     ..Default::default()
+    /// };
+    /// let mut state = RepoState::starting_at(&[run(2, "failure"), run(1, "")]);
+    /// let runs = vec![run(5, "failure"), run(4, ""), run(3, "success"), run(2, "failure"), run(1, "failure")];
+    /// state.track(&runs);
+    /// let ids: Vec<u64> = state.completed(runs).iter().map(|run| run.database_id).collect();
+    /// // Run 4 is still in progress and doesn't hold back run 5, run 2 was handled before watching
+    /// assert_eq!(ids, [1, 3, 5]);
+    /// ```
+    pub fn completed(&self, mut runs: Vec<GhRun>) -> Vec<GhRun> {
+        runs.sort_by_key(|run| run.database_id);
+        runs.into_iter()
+            .filter(|run| self.pending.contains(&run.database_id) && !run.conclusion.is_empty())
+            .collect()
+    }
+}
+
+impl WatchState {
+    /// Load the state, or the empty state if the file doesn't exist
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read state file {path:?}: {e}"))?;
+        serde_json::from_str(&contents)
+            .map_err(|e| format!("Invalid state file {path:?}: {e}").into())
+    }
+
+    /// Save the state, replacing the file at once so it's never left half written
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(&tmp_path, path)
+            .map_err(|e| format!("Failed to write state file {path:?}: {e}"))?;
+        Ok(())
+    }
+
+    pub fn last_run_id(&self, repo: &str) -> Option<u64> {
+        self.repos.get(repo).map(|state| state.last_run_id)
+    }
+
+    /// Mark a run of the repository as processed
+    pub fn processed(&mut self, repo: &str, run_id: u64) {
+        if let Some(state) = self.repos.get_mut(repo) {
+            state.pending.remove(&run_id);
+        }
+    }
+}
+
+impl WatchSettings {
+    /// Why the run is ignored according to the settings, `None` if it is handled
+    pub fn ignores(&self, run: &GhRun) -> Option<String> {
+        if !self.workflows.is_empty() && !self.workflows.contains(&run.workflow_name) {
+            Some(format!("workflow {} is not handled", run.workflow_name))
+        } else if !self.branches.is_empty() && !self.branches.contains(&run.head_branch) {
+            Some(format!("branch {} is not handled", run.head_branch))
+        } else {
+            None
+        }
+    }
+}
+
+/// Exponential backoff of the poll interval after errors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backoff {
+    interval: Duration,
+    errors: u32,
+}
+
+impl Backoff {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            errors: 0,
+        }
+    }
+
+    /// The delay until the next poll: the interval doubled for every consecutive error, at most
+    /// [MAX_BACKOFF] (or the interval if it's longer)
+    ///
+    /// # Example
+    /// ```
+    /// # use gh_workflow_parser::watch::Backoff;
+    /// # use std::time::Duration;
+    /// let mut backoff = Backoff::new(Duration::from_secs(60));
+    /// backoff.failed();
+    /// backoff.failed();
+    /// assert_eq!(backoff.delay(), Duration::from_secs(240));
+    /// backoff.succeeded();
+    /// assert_eq!(backoff.delay(), Duration::from_secs(60));
+    /// ```
+    pub fn delay(&self) -> Duration {
+        let factor = 2u32.saturating_pow(self.errors);
+        self.interval
+            .saturating_mul(factor)
+            .min(MAX_BACKOFF.max(self.interval))
+    }
+
+    pub fn failed(&mut self) {
+        self.errors = self.errors.saturating_add(1);
+    }
+
+    pub fn succeeded(&mut self) {
+        self.errors = 0;
+    }
+}
+
+/// Set when SIGINT or SIGTERM is received
+#[derive(Debug, Clone)]
+pub struct Shutdown {
+    requested: Arc<AtomicBool>,
+}
+
+impl Shutdown {
+    /// Register the handlers for SIGINT and SIGTERM
+    pub fn register() -> Result<Self, Box<dyn Error>> {
+        let requested = Arc::new(AtomicBool::new(false));
+        for signal in [signal_hook::consts::SIGINT, signal_hook::consts::SIGTERM] {
+            signal_hook::flag::register(signal, Arc::clone(&requested))?;
+        }
+        Ok(Self { requested })
+    }
+
+    pub fn requested(&self) -> bool {
+        self.requested.load(Ordering::Relaxed)
+    }
+
+    /// Sleep for the duration unless a shutdown is requested, returns `false` if it was
+    pub fn sleep(&self, duration: Duration) -> bool {
+        let until = Instant::now() + duration;
+        while !self.requested() {
+            let now = Instant::now();
+            if now >= until {
+                return true;
+            }
+            std::thread::sleep((until - now).min(Duration::from_millis(100)));
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_state_roundtrip() {
+        let dir = temp_dir::TempDir::new().unwrap();
+        let path = dir.path().join("watch.json");
+        assert_eq!(WatchState::load(&path).unwrap(), WatchState::default());
+
+        let mut state = WatchState::default();
+        state.repos.insert(
+            "https://github.com/luftkode/distro-template".to_string(),
+            RepoState {
+                last_run_id: 7858139663,
+                pending: BTreeSet::from([7858139600, 7858139663]),
+            },
+        );
+        state.processed("https://github.com/luftkode/distro-template", 7858139663);
+        state.save(&path).unwrap();
+        let loaded = WatchState::load(&path).unwrap();
+        assert_eq!(loaded, state);
+        assert_eq!(
+            loaded.repos["https://github.com/luftkode/distro-template"].pending,
+            BTreeSet::from([7858139600])
+        );
+        assert_eq!(
+            loaded.last_run_id("https://github.com/luftkode/other"),
+            None
+        );
+    }
+
+    fn run(database_id: u64, conclusion: &str) -> GhRun {
+        GhRun {
+            database_id,
+            conclusion: conclusion.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn completed_ids(state: &RepoState, runs: &[GhRun]) -> Vec<u64> {
+        state
+            .completed(runs.to_vec())
+            .iter()
+            .map(|run| run.database_id)
+            .collect()
+    }
+
+    #[test]
+    fn test_run_in_progress_is_processed_when_completed() {
+        let mut state = RepoState::starting_at(&[run(1, "success")]);
+        let runs = [run(3, "failure"), run(2, ""), run(1, "success")];
+        state.track(&runs);
+        assert_eq!(completed_ids(&state, &runs), [3]);
+        state.pending.remove(&3);
+
+        let runs = [
+            run(4, ""),
+            run(3, "failure"),
+            run(2, "failure"),
+            run(1, "success"),
+        ];
+        state.track(&runs);
+        assert_eq!(completed_ids(&state, &runs), [2]);
+        assert_eq!(state.last_run_id, 4);
+        assert_eq!(state.pending, BTreeSet::from([2, 4]));
+    }
+
+    #[test]
+    fn test_unlisted_pending_runs_are_dropped() {
+        let mut state = RepoState::starting_at(&[run(2, ""), run(1, "")]);
+        state.track(&[]);
+        assert_eq!(state.pending, BTreeSet::from([1, 2]));
+        state.track(&[run(3, ""), run(2, "")]);
+        assert_eq!(state.pending, BTreeSet::from([2, 3]));
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let mut backoff = Backoff::new(Duration::from_secs(60));
+        for _ in 0..40 {
+            backoff.failed();
+        }
+        assert_eq!(backoff.delay(), MAX_BACKOFF);
+        let mut backoff = Backoff::new(2 * MAX_BACKOFF);
+        backoff.failed();
+        assert_eq!(backoff.delay(), 2 * MAX_BACKOFF);
+    }
+
+    #[test]
+    fn test_settings_filter() {
+        let run = GhRun {
+            workflow_name: "Build".to_string(),
+            head_branch: "main".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(WatchSettings::default().ignores(&run), None);
+        let settings = WatchSettings {
+            workflows: vec!["Build".to_string()],
+            branches: vec!["dev".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            settings.ignores(&run).as_deref(),
+            Some("branch main is not handled")
+        );
+    }
+}
@@ -61,13 +61,497 @@ fn fake_github_cli_create_issue() -> Result<(), Box<dyn Error>> {
         "Command failed with status: {status}\n - stdout: {stdout}\n - stderr: {stderr}"
     );
 
-    let stderr_contains_fn = predicate::str::contains("Fake create_issue for repo=https://github.com/fake-repo.com, title=Scheduled run failed, body=**Run ID**: 1337 [LINK TO RUN](https://github.com/fake-repo.com/actions/runs/1337)");
+    let stderr_contains_fn = predicate::str::contains("Fake create_issue for repo=https://github.com/fake-repo.com, title=[Yocto misc] Step \"📦 Build yocto image\" failed in \"Test template xilinx\" (master), body=**Run ID**: 1337 [LINK TO RUN](https://github.com/fake-repo.com/actions/runs/1337)");
 
     assert!(stderr_contains_fn.eval(&stderr), "stderr: {stderr}");
 
     Ok(())
 }
 
+#[test]
+fn fake_github_cli_continues_without_missing_job_log() -> Result<(), Box<dyn Error>> {
+    let fixtures = TempDir::new()?;
+    fixtures.copy_from(
+        format!("{}/tests/fixtures", env!("CARGO_MANIFEST_DIR")),
+        &["**/*"],
+    )?;
+    fs::remove_file(
+        fixtures
+            .child("github.com/luftkode/distro-template/jobs/21460523393.log")
+            .path(),
+    )?;
+
+    let mut cmd = Command::cargo_bin("gh-workflow-parser")?;
+    cmd.arg("analyze-run")
+        .arg("--repo=luftkode/distro-template")
+        .arg("--run-id=7865472546")
+        .arg("--label=bug")
+        .arg("--kind=yocto")
+        .arg("--log-parallelism=2")
+        .arg("--fake-github-cli")
+        .arg(format!("--fixtures={}", fixtures.path().display()));
+
+    let output = cmd.assert().success().get_output().clone();
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    let failed_jobs = json["failed_jobs"].as_array().unwrap();
+    pretty_assert_eq!(failed_jobs.len(), 1);
+    pretty_assert_eq!(failed_jobs[0]["id"], "21460523544");
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(
+        stderr.contains("Continuing without 1 of 2 failed job(s)"),
+        "{stderr}"
+    );
+    Ok(())
+}
+
+#[test]
+fn fake_github_cli_analyze_run_json() -> Result<(), Box<dyn Error>> {
+    let mut cmd = Command::cargo_bin("gh-workflow-parser")?;
+
+    cmd.arg("analyze-run")
+        .arg("--repo=fake-repo.com")
+        .arg("--run-id=1337")
+        .arg("--label=bug")
+        .arg("--kind=yocto")
+        .arg("--fake-github-cli");
+
+    let Output {
+        status,
+        stdout,
+        stderr,
+    } = cmd.output()?;
+
+    let stderr = String::from_utf8(stderr)?;
+    let stdout = String::from_utf8(stdout)?;
+
+    assert!(
+        status.success(),
+        "Command failed with status: {status}\n - stdout: {stdout}\n - stderr: {stderr}"
+    );
+
+    let json: serde_json::Value = serde_json::from_str(&stdout)?;
+    pretty_assert_eq!(json["run_id"], "1337");
+    pretty_assert_eq!(
+        json["run_link"],
+        "https://github.com/fake-repo.com/actions/runs/1337"
+    );
+    pretty_assert_eq!(json["failed_jobs"][0]["id"], "21442749267");
+    pretty_assert_eq!(json["duplicate"]["status"], "new");
+    assert_eq!(json["fingerprint"].as_str().unwrap().len(), 16);
+
+    Ok(())
+}
+
+#[test]
+fn analyze_log_markdown() -> Result<(), Box<dyn Error>> {
+    let mut cmd = Command::cargo_bin("gh-workflow-parser")?;
+    cmd.arg("analyze-log")
+        .arg(format!(
+            "{}/tests/fixtures/github.com/luftkode/distro-template/jobs/21442749267.log",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .arg("--label=bug")
+        .arg("--kind=yocto")
+        .arg("--repo=luftkode/distro-template")
+        .arg("--run-id=7858139663")
+        .arg("--branch=master");
+
+    let stdout = String::from_utf8(cmd.assert().success().get_output().stdout.clone())?;
+    assert!(
+        stdout.starts_with(
+            "# [Yocto do_fetch] sqlite3-native failed in \"Test template xilinx\" (master)\n\n\
+             **Run ID**: 7858139663 [LINK TO RUN](https://github.com/luftkode/distro-template/actions/runs/7858139663)"
+        ),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains("**Step failed:** `📦 Build yocto image`"),
+        "{stdout}"
+    );
+    Ok(())
+}
+
+#[test]
+fn analyze_log_without_prefix_json() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let log = fs::read_to_string(format!(
+        "{}/tests/fixtures/github.com/luftkode/distro-template/jobs/21442749267.log",
+        env!("CARGO_MANIFEST_DIR")
+    ))?;
+    let log: String = log
+        .lines()
+        .map(|line| line.splitn(3, '\t').nth(2).unwrap_or(line))
+        .map(|line| {
+            line.split_once("Z ")
+                .map_or(line, |(_timestamp, line)| line)
+        })
+        .map(|line| format!("{line}\n"))
+        .collect();
+    let log_file = dir.child("xilinx.log");
+    log_file.write_str(&log)?;
+
+    let mut cmd = Command::cargo_bin("gh-workflow-parser")?;
+    cmd.arg("analyze-log")
+        .arg(log_file.path())
+        .arg("--label=bug")
+        .arg("--kind=yocto")
+        .arg("--format=json");
+
+    let stdout = String::from_utf8(cmd.assert().success().get_output().stdout.clone())?;
+    let json: serde_json::Value = serde_json::from_str(&stdout)?;
+    pretty_assert_eq!(json["failed_jobs"][0]["name"], "xilinx");
+    pretty_assert_eq!(json["failed_jobs"][0]["error_message"]["parser"], "yocto");
+    pretty_assert_eq!(json["labels"], serde_json::json!(["bug", "do_fetch"]));
+    pretty_assert_eq!(json["duplicate"], serde_json::Value::Null);
+    Ok(())
+}
+
+#[test]
+fn analyze_log_links_to_host() -> Result<(), Box<dyn Error>> {
+    let log_file = format!(
+        "{}/tests/fixtures/github.com/luftkode/distro-template/jobs/21442749267.log",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let run_link = |repo: &str| -> Result<serde_json::Value, Box<dyn Error>> {
+        let mut cmd = Command::cargo_bin("gh-workflow-parser")?;
+        cmd.arg("analyze-log")
+            .arg(&log_file)
+            .arg("--label=bug")
+            .arg("--kind=yocto")
+            .arg("--format=json")
+            .arg(format!("--repo={repo}"))
+            .arg("--run-id=42")
+            .arg("--host=github.example.com");
+        let stdout = String::from_utf8(cmd.assert().success().get_output().stdout.clone())?;
+        let json: serde_json::Value = serde_json::from_str(&stdout)?;
+        Ok(json["run_link"].clone())
+    };
+
+    pretty_assert_eq!(
+        run_link("luftkode/distro-template")?,
+        "https://github.example.com/luftkode/distro-template/actions/runs/42"
+    );
+    // The host of a repository URL takes precedence
+    pretty_assert_eq!(
+        run_link("https://github.com/luftkode/distro-template")?,
+        "https://github.com/luftkode/distro-template/actions/runs/42"
+    );
+    Ok(())
+}
+
+#[test]
+fn fake_github_cli_replays_fixtures() -> Result<(), Box<dyn Error>> {
+    let mut cmd = Command::cargo_bin("gh-workflow-parser")?;
+
+    cmd.arg("analyze-run")
+        .arg("--repo=luftkode/distro-template")
+        .arg("--run-id=7858139663")
+        .arg("--label=bug")
+        .arg("--kind=yocto")
+        .arg("--fake-github-cli")
+        .arg(format!(
+            "--fixtures={}/tests/fixtures",
+            env!("CARGO_MANIFEST_DIR")
+        ));
+
+    let Output {
+        status,
+        stdout,
+        stderr,
+    } = cmd.output()?;
+
+    let stderr = String::from_utf8(stderr)?;
+    let stdout = String::from_utf8(stdout)?;
+
+    assert!(
+        status.success(),
+        "Command failed with status: {status}\n - stdout: {stdout}\n - stderr: {stderr}"
+    );
+
+    let json: serde_json::Value = serde_json::from_str(&stdout)?;
+    pretty_assert_eq!(
+        json["title"],
+        r#"[Yocto do_fetch] sqlite3-native failed in "Test template xilinx" (master)"#
+    );
+    pretty_assert_eq!(json["labels"], serde_json::json!(["bug", "do_fetch"]));
+    pretty_assert_eq!(
+        json["failed_jobs"][0]["error_message"]["error"]["kind"],
+        "do_fetch"
+    );
+    // No issues are recorded for the label
+    pretty_assert_eq!(json["duplicate"]["status"], "new");
+
+    Ok(())
+}
+
+#[test]
+fn fixtures_require_fake_github_cli() -> Result<(), Box<dyn Error>> {
+    let mut cmd = Command::cargo_bin("gh-workflow-parser")?;
+
+    cmd.arg("analyze-run")
+        .arg("--repo=luftkode/distro-template")
+        .arg("--run-id=7858139663")
+        .arg("--label=bug")
+        .arg("--fixtures=tests/fixtures");
+
+    cmd.assert().failure();
+
+    Ok(())
+}
+
+#[test]
+fn fake_github_cli_state_detects_duplicate() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let state_file = dir.child("state.json");
+
+    let run = || -> Result<String, Box<dyn Error>> {
+        let mut cmd = Command::cargo_bin("gh-workflow-parser")?;
+        cmd.arg("create-issue-from-run")
+            .arg("--repo=luftkode/distro-template")
+            .arg("--run-id=7858139663")
+            .arg("--label=bug")
+            .arg("--kind=yocto")
+            .arg("--fake-github-cli")
+            .arg(format!("--fake-state={}", state_file.path().display()));
+
+        let Output {
+            status,
+            stdout,
+            stderr,
+        } = cmd.output()?;
+        let stderr = String::from_utf8(stderr)?;
+        let stdout = String::from_utf8(stdout)?;
+        assert!(
+            status.success(),
+            "Command failed with status: {status}\n - stdout: {stdout}\n - stderr: {stderr}"
+        );
+        Ok(stderr)
+    };
+
+    let first = run()?;
+    assert!(
+        first.contains("Created issue: https://github.com/luftkode/distro-template/issues/1"),
+        "{first}"
+    );
+    let second = run()?;
+    assert!(
+        second.contains("An issue for the exact same failure already exists: https://github.com/luftkode/distro-template/issues/1"),
+        "{second}"
+    );
+
+    let state: serde_json::Value = serde_json::from_str(&fs::read_to_string(state_file.path())?)?;
+    pretty_assert_eq!(state["issues"].as_array().unwrap().len(), 1);
+    pretty_assert_eq!(
+        state["issues"][0]["labels"],
+        serde_json::json!(["bug", "misc"])
+    );
+    pretty_assert_eq!(state["labels"].as_array().unwrap().len(), 2);
+
+    Ok(())
+}
+
+/// Run `create-issue-from-run` against the fake GitHub CLI with the given state file and return stderr
+fn create_issue_with_fake_state(
+    state_file: &ChildPath,
+    extra_args: &[&str],
+) -> Result<String, Box<dyn Error>> {
+    let mut cmd = Command::cargo_bin("gh-workflow-parser")?;
+    cmd.arg("create-issue-from-run")
+        .arg("--repo=luftkode/distro-template")
+        .arg("--run-id=7858139663")
+        .arg("--label=bug")
+        .arg("--kind=yocto")
+        .arg("--fake-github-cli")
+        .arg(format!("--fake-state={}", state_file.path().display()))
+        .args(extra_args);
+
+    let Output {
+        status,
+        stdout,
+        stderr,
+    } = cmd.output()?;
+    let stderr = String::from_utf8(stderr)?;
+    let stdout = String::from_utf8(stdout)?;
+    assert!(
+        status.success(),
+        "Command failed with status: {status}\n - stdout: {stdout}\n - stderr: {stderr}"
+    );
+    Ok(stderr)
+}
+
+/// Close all issues in the state file of the fake GitHub CLI
+fn close_fake_issues(state_file: &ChildPath) -> Result<(), Box<dyn Error>> {
+    let mut state: serde_json::Value = serde_json::from_str(&fs::read_to_string(state_file)?)?;
+    for issue in state["issues"].as_array_mut().unwrap() {
+        issue["state"] = "closed".into();
+    }
+    fs::write(state_file, state.to_string())?;
+    Ok(())
+}
+
+#[test]
+fn fake_github_cli_reopens_regression() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let state_file = dir.child("state.json");
+
+    create_issue_with_fake_state(&state_file, &[])?;
+    close_fake_issues(&state_file)?;
+    let stderr = create_issue_with_fake_state(&state_file, &[])?;
+    assert!(
+        stderr.contains("Reopened issue: https://github.com/luftkode/distro-template/issues/1"),
+        "{stderr}"
+    );
+
+    let state: serde_json::Value = serde_json::from_str(&fs::read_to_string(state_file.path())?)?;
+    pretty_assert_eq!(state["issues"].as_array().unwrap().len(), 1);
+    pretty_assert_eq!(state["issues"][0]["state"], "open");
+    pretty_assert_eq!(
+        state["issues"][0]["comments"],
+        serde_json::json!([
+            "Regressed in run [7858139663](https://github.com/luftkode/distro-template/actions/runs/7858139663)"
+        ])
+    );
+
+    Ok(())
+}
+
+#[test]
+fn fake_github_cli_links_regression() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let state_file = dir.child("state.json");
+
+    create_issue_with_fake_state(&state_file, &[])?;
+    close_fake_issues(&state_file)?;
+    create_issue_with_fake_state(&state_file, &["--on-regression=link"])?;
+
+    let state: serde_json::Value = serde_json::from_str(&fs::read_to_string(state_file.path())?)?;
+    let issues = state["issues"].as_array().unwrap();
+    pretty_assert_eq!(issues.len(), 2);
+    pretty_assert_eq!(issues[0]["state"], "closed");
+    let body = issues[1]["body"].as_str().unwrap();
+    assert!(
+        body.contains("**Regression of:** https://github.com/luftkode/distro-template/issues/1"),
+        "{body}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn fake_github_cli_issue_per_job() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let state_file = dir.child("state.json");
+
+    let run = || -> Result<(), Box<dyn Error>> {
+        let mut cmd = Command::cargo_bin("gh-workflow-parser")?;
+        cmd.arg("create-issue-from-run")
+            .arg("--repo=luftkode/distro-template")
+            .arg("--run-id=7865472546")
+            .arg("--label=bug")
+            .arg("--kind=yocto")
+            .arg("--issue-per=job")
+            .arg("--tracking-issue")
+            .arg("--fake-github-cli")
+            .arg(format!(
+                "--fixtures={}/tests/fixtures",
+                env!("CARGO_MANIFEST_DIR")
+            ))
+            .arg(format!("--fake-state={}", state_file.path().display()));
+        let Output {
+            status,
+            stdout,
+            stderr,
+        } = cmd.output()?;
+        let stderr = String::from_utf8(stderr)?;
+        let stdout = String::from_utf8(stdout)?;
+        assert!(
+            status.success(),
+            "Command failed with status: {status}\n - stdout: {stdout}\n - stderr: {stderr}"
+        );
+        Ok(())
+    };
+
+    // The second run only finds duplicates and doesn't create a tracking issue
+    run()?;
+    run()?;
+
+    let state: serde_json::Value = serde_json::from_str(&fs::read_to_string(state_file.path())?)?;
+    let issues = state["issues"].as_array().unwrap();
+    pretty_assert_eq!(issues.len(), 3);
+    pretty_assert_eq!(
+        issues[0]["title"],
+        r#"[Yocto do_compile] linux-raspberrypi failed in "Test template raspberry" (master)"#
+    );
+    pretty_assert_eq!(
+        issues[0]["labels"],
+        serde_json::json!(["bug", "do_compile"])
+    );
+    pretty_assert_eq!(issues[1]["labels"], serde_json::json!(["bug", "do_fetch"]));
+    pretty_assert_eq!(
+        issues[2]["title"],
+        r#"Tracking: 2 jobs failed in "Use template and build image" (master)"#
+    );
+    let tracking_body = issues[2]["body"].as_str().unwrap();
+    assert!(
+        tracking_body.contains(
+            "- [ ] https://github.com/luftkode/distro-template/issues/1\n- [ ] https://github.com/luftkode/distro-template/issues/2\n"
+        ),
+        "{tracking_body}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn fake_github_cli_github_actions_output() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let step_summary = dir.child("step_summary.md");
+    let github_output = dir.child("github_output");
+    let mut cmd = Command::cargo_bin("gh-workflow-parser")?;
+
+    cmd.env("GITHUB_STEP_SUMMARY", step_summary.path())
+        .env("GITHUB_OUTPUT", github_output.path())
+        .arg("create-issue-from-run")
+        .arg("--repo=fake-repo.com")
+        .arg("--run-id=1337")
+        .arg("--label=bug")
+        .arg("--kind=yocto")
+        .arg("--github-actions-output")
+        .arg("--fake-github-cli");
+
+    let Output {
+        status,
+        stdout,
+        stderr,
+    } = cmd.output()?;
+
+    let stderr = String::from_utf8(stderr)?;
+    let stdout = String::from_utf8(stdout)?;
+
+    assert!(
+        status.success(),
+        "Command failed with status: {status}\n - stdout: {stdout}\n - stderr: {stderr}"
+    );
+
+    assert!(
+        stdout.starts_with(
+            "::error title=Test template xilinx failed at 📦 Build yocto image (misc)::"
+        ),
+        "stdout: {stdout}"
+    );
+    step_summary.assert(predicate::str::contains(
+        "**Created issue:** https://github.com/fake-repo.com/issues/1",
+    ));
+    step_summary.assert(predicate::str::contains(
+        "### `Test template xilinx` (ID 21442749267)",
+    ));
+    github_output.assert(predicate::str::is_match(
+        "^fingerprint=[0-9a-f]{16}\nissue-url=https://github.com/fake-repo.com/issues/1\n$",
+    )?);
+
+    Ok(())
+}
+
 const EXPECT_FAILURE_LOG_CONTENTS: &str = "foobar";
 const REL_PATH_TO_FAILURE_LOG: &str =
     r#"yocto/build/tmp/work/x86_64-linux/sqlite3-native/3.43.2/temp/log.do_fetch.21616"#;
@@ -168,3 +652,794 @@ other contents",
 
     Ok(())
 }
+
+#[test]
+fn fake_github_cli_assigns_owners() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let state_file = dir.child("state.json");
+    let config_file = dir.child("config.toml");
+    config_file.write_str(
+        r#"
+[owners]
+codeowners = true
+jobs = { "Test template xilinx" = ["@xilinx-maintainer"] }
+"#,
+    )?;
+
+    let mut cmd = Command::cargo_bin("gh-workflow-parser")?;
+    cmd.arg("create-issue-from-run")
+        .arg("--repo=luftkode/distro-template")
+        .arg("--run-id=7865472546")
+        .arg("--label=bug")
+        .arg("--kind=yocto")
+        .arg("--fake-github-cli")
+        .arg(format!(
+            "--fixtures={}/tests/fixtures",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .arg(format!("--fake-state={}", state_file.path().display()))
+        .arg(format!("--config={}", config_file.path().display()));
+    let Output {
+        status,
+        stdout,
+        stderr,
+    } = cmd.output()?;
+    let stderr = String::from_utf8(stderr)?;
+    let stdout = String::from_utf8(stdout)?;
+    assert!(
+        status.success(),
+        "Command failed with status: {status}\n - stdout: {stdout}\n - stderr: {stderr}"
+    );
+
+    let state: serde_json::Value = serde_json::from_str(&fs::read_to_string(state_file.path())?)?;
+    let issue = &state["issues"][0];
+    // Teams are mentioned in the body but can't be assigned
+    pretty_assert_eq!(
+        issue["assignees"],
+        serde_json::json!(["rpi-maintainer", "xilinx-maintainer", "yocto-maintainer"])
+    );
+    assert!(issue["body"].as_str().unwrap().contains(
+        "**Owners:** @luftkode/rpi @rpi-maintainer @xilinx-maintainer @yocto-maintainer"
+    ));
+    Ok(())
+}
+
+#[test]
+fn fake_github_cli_label_policy() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let state_file = dir.child("state.json");
+    let config_file = dir.child("config.toml");
+    config_file.write_str(
+        r#"
+[labels]
+prefix = "yocto/"
+auto_create = ["yocto/do_fetch"]
+failure = { color = "00FF00" }
+"#,
+    )?;
+
+    let mut cmd = Command::cargo_bin("gh-workflow-parser")?;
+    cmd.arg("create-issue-from-run")
+        .arg("--repo=luftkode/distro-template")
+        .arg("--run-id=7865472546")
+        .arg("--label=bug")
+        .arg("--kind=yocto")
+        .arg("--fake-github-cli")
+        .arg(format!(
+            "--fixtures={}/tests/fixtures",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .arg(format!("--fake-state={}", state_file.path().display()))
+        .arg(format!("--config={}", config_file.path().display()));
+    cmd.assert().success();
+
+    let state: serde_json::Value = serde_json::from_str(&fs::read_to_string(state_file.path())?)?;
+    // `yocto/do_compile` may not be created and is left out
+    pretty_assert_eq!(
+        state["issues"][0]["labels"],
+        serde_json::json!(["bug", "yocto/do_fetch"])
+    );
+    let labels = state["labels"].as_array().unwrap();
+    pretty_assert_eq!(labels.len(), 1);
+    pretty_assert_eq!(labels[0]["name"], "yocto/do_fetch");
+    pretty_assert_eq!(labels[0]["color"], "00FF00");
+    Ok(())
+}
+
+#[test]
+fn fake_github_cli_sync_labels() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let state_file = dir.child("state.json");
+    let config_file = dir.child("config.toml");
+    config_file.write_str(
+        r#"
+[labels]
+prefix = "yocto/"
+definitions = { "bug" = { color = "B60205", description = "Something is broken" } }
+"#,
+    )?;
+
+    let mut cmd = Command::cargo_bin("gh-workflow-parser")?;
+    cmd.arg("sync-labels")
+        .arg("--repo=luftkode/distro-template")
+        .arg("--label=bug")
+        .arg("--fake-github-cli")
+        .arg(format!("--fake-state={}", state_file.path().display()))
+        .arg(format!("--config={}", config_file.path().display()));
+    // Syncing twice overwrites the labels instead of failing
+    cmd.assert().success();
+    cmd.assert().success();
+
+    let state: serde_json::Value = serde_json::from_str(&fs::read_to_string(state_file.path())?)?;
+    let labels = state["labels"].as_array().unwrap();
+    pretty_assert_eq!(labels[0]["name"], "bug");
+    pretty_assert_eq!(labels[0]["color"], "B60205");
+    pretty_assert_eq!(labels[0]["description"], "Something is broken");
+    assert!(labels
+        .iter()
+        .any(|label| label["name"] == "yocto/do_fetch" && label["color"] == "FBCA04"));
+    assert!(labels.iter().all(|label| label["name"] != "do_fetch"));
+    Ok(())
+}
+
+#[test]
+fn fake_github_cli_adds_issue_to_project() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let state_file = dir.child("state.json");
+
+    create_issue_with_fake_state(
+        &state_file,
+        &[
+            &format!("--fixtures={}/tests/fixtures", env!("CARGO_MANIFEST_DIR")),
+            "--milestone=Sprint 7",
+            "--project=luftkode/3",
+            "--project-field=Status=Triage",
+            "--project-field=Component={{ failed_jobs[0].recipe }}",
+            "--project-field=Severity=High",
+        ],
+    )?;
+
+    let state: serde_json::Value = serde_json::from_str(&fs::read_to_string(state_file.path())?)?;
+    let issue = &state["issues"][0];
+    pretty_assert_eq!(issue["milestone"], "Sprint 7");
+    let items = state["project_items"].as_array().unwrap();
+    pretty_assert_eq!(items.len(), 1);
+    pretty_assert_eq!(items[0]["project"], "luftkode/3");
+    pretty_assert_eq!(items[0]["issue_url"], issue["url"]);
+    pretty_assert_eq!(
+        items[0]["fields"],
+        serde_json::json!({"Component": "sqlite3-native", "Severity": "High", "Status": "Triage"})
+    );
+    Ok(())
+}
+
+#[test]
+fn project_field_requires_project() -> Result<(), Box<dyn Error>> {
+    let mut cmd = Command::cargo_bin("gh-workflow-parser")?;
+    cmd.arg("create-issue-from-run")
+        .arg("--repo=luftkode/distro-template")
+        .arg("--run-id=7858139663")
+        .arg("--label=bug")
+        .arg("--kind=yocto")
+        .arg("--fake-github-cli")
+        .arg("--project-field=Status=Triage");
+    cmd.assert().failure();
+    Ok(())
+}
+
+/// Run `triage-runs` on the fixture runs with the fake GitHub CLI and return stdout
+fn triage_runs_with_fake_state(
+    state_file: &ChildPath,
+    extra_args: &[&str],
+) -> Result<String, Box<dyn Error>> {
+    let mut cmd = Command::cargo_bin("gh-workflow-parser")?;
+    cmd.arg("triage-runs")
+        .arg("--repo=luftkode/distro-template")
+        .arg("--label=bug")
+        .arg("--kind=yocto")
+        .arg("--fake-github-cli")
+        .arg(format!(
+            "--fixtures={}/tests/fixtures",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .arg(format!("--fake-state={}", state_file.path().display()))
+        .args(extra_args);
+    let Output {
+        status,
+        stdout,
+        stderr,
+    } = cmd.output()?;
+    let stderr = String::from_utf8(stderr)?;
+    let stdout = String::from_utf8(stdout)?;
+    assert!(
+        status.success(),
+        "Command failed with status: {status}\n - stdout: {stdout}\n - stderr: {stderr}"
+    );
+    Ok(stdout)
+}
+
+#[test]
+fn fake_github_cli_triage_runs() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let state_file = dir.child("state.json");
+
+    let stdout = triage_runs_with_fake_state(&state_file, &[])?;
+    assert!(stdout.contains("2 run(s)\tcreated"), "{stdout}");
+    assert!(stdout.contains("1 run(s)\tcreated"), "{stdout}");
+
+    let state: serde_json::Value = serde_json::from_str(&fs::read_to_string(state_file.path())?)?;
+    let issues = state["issues"].as_array().unwrap();
+    // The xilinx do_fetch failure occurred in both runs, the raspberry failure in the newest
+    pretty_assert_eq!(issues.len(), 2);
+    pretty_assert_eq!(issues[1]["labels"], serde_json::json!(["bug", "do_fetch"]));
+    let body = issues[1]["body"].as_str().unwrap();
+    assert!(body.contains("**Affected runs (2):**\n- https://github.com/luftkode/distro-template/actions/runs/7865472546\n- https://github.com/luftkode/distro-template/actions/runs/7858139663"), "{body}");
+
+    // The affected runs are already listed in the issues
+    let stdout = triage_runs_with_fake_state(&state_file, &["--since=2024-02-12"])?;
+    assert!(stdout.contains("1 run(s)\talready listed in"), "{stdout}");
+    assert!(!stdout.contains("created"), "{stdout}");
+    let state: serde_json::Value = serde_json::from_str(&fs::read_to_string(state_file.path())?)?;
+    let issues = state["issues"].as_array().unwrap();
+    pretty_assert_eq!(issues.len(), 2);
+    pretty_assert_eq!(issues[1]["comments"], serde_json::json!([]));
+    Ok(())
+}
+
+#[test]
+fn fake_github_cli_triage_runs_comments_new_runs_once() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let state_file = dir.child("state.json");
+
+    // The issues only list the newest run
+    triage_runs_with_fake_state(&state_file, &["--since=2024-02-12"])?;
+
+    // The older run is added as a comment once
+    let stdout = triage_runs_with_fake_state(&state_file, &[])?;
+    assert!(stdout.contains("2 run(s)\tupdated"), "{stdout}");
+    let stdout = triage_runs_with_fake_state(&state_file, &[])?;
+    assert!(stdout.contains("2 run(s)\talready listed in"), "{stdout}");
+    let state: serde_json::Value = serde_json::from_str(&fs::read_to_string(state_file.path())?)?;
+    let comments: Vec<&serde_json::Value> = state["issues"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .flat_map(|issue| issue["comments"].as_array().unwrap())
+        .collect();
+    pretty_assert_eq!(
+        comments,
+        [&serde_json::json!(
+            "Also failed in:\n- https://github.com/luftkode/distro-template/actions/runs/7858139663"
+        )]
+    );
+    Ok(())
+}
+
+fn history(history_db: &ChildPath, args: &[&str]) -> Result<String, Box<dyn Error>> {
+    let mut cmd = Command::cargo_bin("gh-workflow-parser")?;
+    cmd.arg("history")
+        .args(args)
+        .arg(format!("--history-db={}", history_db.path().display()));
+    let Output {
+        status,
+        stdout,
+        stderr,
+    } = cmd.output()?;
+    let stdout = String::from_utf8(stdout)?;
+    assert!(
+        status.success(),
+        "Command failed with status: {status}\n - stdout: {stdout}\n - stderr: {}",
+        String::from_utf8(stderr)?
+    );
+    Ok(stdout)
+}
+
+#[test]
+fn fake_github_cli_failure_history() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let state_file = dir.child("state.json");
+    let history_db = dir.child("history.db");
+    let history_db_arg = format!("--history-db={}", history_db.path().display());
+
+    triage_runs_with_fake_state(&state_file, &[&history_db_arg])?;
+
+    let stdout = history(&history_db, &["top-jobs"])?;
+    pretty_assert_eq!(
+        stdout,
+        "2\tTest template xilinx\tUse template and build image\tlast failed 2024-02-12T00:03:21Z\n\
+         1\tTest template raspberry\tUse template and build image\tlast failed 2024-02-12T00:03:21Z\n"
+    );
+    let stdout = history(
+        &history_db,
+        &["top-jobs", "--since=2024-02-12", "--limit=1"],
+    )?;
+    assert_eq!(stdout.lines().count(), 1, "{stdout}");
+
+    let stdout = history(&history_db, &["top-fingerprints"])?;
+    let first = stdout.lines().next().unwrap();
+    assert!(first.starts_with("2\t"), "{stdout}");
+    assert!(
+        first.ends_with("\tTest template xilinx\tdo_fetch\tsqlite3-native\thttps://github.com/luftkode/distro-template/issues/2"),
+        "{stdout}"
+    );
+
+    let stdout = history(
+        &history_db,
+        &[
+            "timeline",
+            "--job=Test template xilinx",
+            "--repo=luftkode/distro-template",
+        ],
+    )?;
+    let runs: Vec<&str> = stdout
+        .lines()
+        .map(|line| line.split('\t').nth(1).unwrap())
+        .collect();
+    pretty_assert_eq!(
+        runs,
+        [
+            "https://github.com/luftkode/distro-template/actions/runs/7858139663",
+            "https://github.com/luftkode/distro-template/actions/runs/7865472546"
+        ]
+    );
+
+    // The green run was before the failures, the jobs haven't recovered
+    history(
+        &history_db,
+        &[
+            "sync",
+            "--repo=luftkode/distro-template",
+            "--fake-github-cli",
+            &format!("--fixtures={}/tests/fixtures", env!("CARGO_MANIFEST_DIR")),
+        ],
+    )?;
+    let stdout = history(&history_db, &["mttr", "--job=Test template xilinx"])?;
+    pretty_assert_eq!(
+        stdout,
+        "Test template xilinx\tUse template and build image\tmaster\tmttr -\tfailing since 2024-02-11T00:03:19Z\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn history_requires_history_db() -> Result<(), Box<dyn Error>> {
+    let mut cmd = Command::cargo_bin("gh-workflow-parser")?;
+    cmd.arg("history").arg("top-jobs");
+    cmd.assert()
+        .failure()
+        .stderr(predicates::str::contains("require --history-db"));
+    Ok(())
+}
+
+fn report(extra_args: &[&str]) -> Result<String, Box<dyn Error>> {
+    let mut cmd = Command::cargo_bin("gh-workflow-parser")?;
+    cmd.arg("report")
+        .arg("--repo=luftkode/distro-template")
+        .arg("--kind=yocto")
+        .arg("--until=2024-02-12")
+        .arg("--days=2")
+        .arg("--fake-github-cli")
+        .arg(format!(
+            "--fixtures={}/tests/fixtures",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .args(extra_args);
+    let Output {
+        status,
+        stdout,
+        stderr,
+    } = cmd.output()?;
+    let stdout = String::from_utf8(stdout)?;
+    assert!(
+        status.success(),
+        "Command failed with status: {status}\n - stdout: {stdout}\n - stderr: {}",
+        String::from_utf8(stderr)?
+    );
+    Ok(stdout)
+}
+
+#[test]
+fn fake_github_cli_report_markdown() -> Result<(), Box<dyn Error>> {
+    let stdout = report(&[])?;
+    for expected in [
+        "# CI health: all workflows (2024-02-11 to 2024-02-12)",
+        "| | This period | Previous period (2024-02-09 to 2024-02-10) | Trend |",
+        "| Failure rate | 100.0% | 0.0% | ▲ +100.0 pp |",
+        "| Test template xilinx | 2 | 2 | 100.0% | ▲ +100.0 pp | 5m 19s |",
+        "| Test template raspberry | 2 | 1 | 50.0% | ▲ +50.0 pp | 20m 43s |",
+        "| enable-ssh-agent | 2 | 0 | 0.0% | = | - |",
+        "- 📦 Build yocto image: 3",
+        "- do_fetch: 2\n- do_compile: 1",
+        "- sqlite3-native: 2\n- linux-raspberrypi: 1",
+    ] {
+        assert!(
+            stdout.contains(expected),
+            "Missing {expected:?} in:\n{stdout}"
+        );
+    }
+    assert!(
+        stdout.contains(
+            "| 2 | Test template xilinx | 📦 Build yocto image | do_fetch | sqlite3-native |"
+        ),
+        "{stdout}"
+    );
+    Ok(())
+}
+
+#[test]
+fn fake_github_cli_report_json() -> Result<(), Box<dyn Error>> {
+    let stdout = report(&["--format=json", "--top=1"])?;
+    let report: serde_json::Value = serde_json::from_str(&stdout)?;
+    pretty_assert_eq!(report["current"]["runs"], 2);
+    pretty_assert_eq!(report["current"]["failed_runs"], 2);
+    pretty_assert_eq!(report["previous"]["since"], "2024-02-09");
+    pretty_assert_eq!(report["previous"]["failure_rate"], 0.0);
+    pretty_assert_eq!(report["trend"]["failure_rate_change"], 1.0);
+    pretty_assert_eq!(
+        report["trend"]["new_fingerprints"]
+            .as_array()
+            .unwrap()
+            .len(),
+        2
+    );
+    let fingerprints = report["current"]["fingerprints"].as_array().unwrap();
+    pretty_assert_eq!(fingerprints.len(), 1);
+    pretty_assert_eq!(fingerprints[0]["count"], 2);
+    pretty_assert_eq!(fingerprints[0]["failure_label"], "do_fetch");
+    pretty_assert_eq!(
+        report["current"]["jobs"][0]["mean_time_to_failure_seconds"],
+        319
+    );
+    Ok(())
+}
+
+/// Kills the server when dropped so a failing test doesn't leave it running
+struct Server(std::process::Child);
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+#[test]
+fn fake_github_cli_serve_webhooks() -> Result<(), Box<dyn Error>> {
+    use std::io::BufRead;
+
+    const SECRET: &[u8] = b"s3cret";
+    let dir = TempDir::new()?;
+    let state_file = dir.child("state.json");
+    let config = dir.child("config.toml");
+    config
+        .write_str("[serve]\nrepos = [\"luftkode/distro-template\"]\nbranches = [\"master\"]\n")?;
+
+    let mut server = Server(
+        std::process::Command::new(assert_cmd::cargo::cargo_bin("gh-workflow-parser"))
+            .arg("serve")
+            .arg("--bind=127.0.0.1:0")
+            .arg("--label=bug")
+            .arg("--kind=yocto")
+            .arg("--fake-github-cli")
+            .arg(format!(
+                "--fixtures={}/tests/fixtures",
+                env!("CARGO_MANIFEST_DIR")
+            ))
+            .arg(format!("--fake-state={}", state_file.path().display()))
+            .arg(format!("--config={}", config.path().display()))
+            .env("GITHUB_WEBHOOK_SECRET", "s3cret")
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::null())
+            .spawn()?,
+    );
+    let mut line = String::new();
+    std::io::BufReader::new(server.0.stdout.take().unwrap()).read_line(&mut line)?;
+    let url = line
+        .trim()
+        .strip_prefix("Listening on ")
+        .ok_or_else(|| format!("Unexpected output: {line}"))?
+        .to_string();
+
+    let post = |payload: &str, signature: &str| -> Result<(u16, String), Box<dyn Error>> {
+        let response = ureq::post(&url)
+            .set("X-GitHub-Event", "workflow_run")
+            .set("X-Hub-Signature-256", signature)
+            .send_string(payload);
+        let response = match response {
+            Ok(response) => response,
+            Err(ureq::Error::Status(_, response)) => response,
+            Err(e) => return Err(e.into()),
+        };
+        Ok((response.status(), response.into_string()?))
+    };
+    let payload = fs::read_to_string(format!(
+        "{}/tests/fixtures/webhooks/workflow_run_failure.json",
+        env!("CARGO_MANIFEST_DIR")
+    ))?;
+    let sign = |payload: &str| gh_workflow_parser::serve::signature(SECRET, payload.as_bytes());
+
+    let (status, _) = post(&payload, "sha256=0123")?;
+    pretty_assert_eq!(status, 401);
+    let other_branch = payload.replace(r#""head_branch": "master""#, r#""head_branch": "dev""#);
+    let (status, message) = post(&other_branch, &sign(&other_branch))?;
+    pretty_assert_eq!(
+        (status, message.as_str()),
+        (200, "Ignored run 7858139663: branch dev is not handled")
+    );
+    let (status, _) = post(&payload, &sign(&payload))?;
+    pretty_assert_eq!(status, 202);
+
+    // The issue is created in the background
+    for _ in 0..100 {
+        if let Ok(state) = fs::read_to_string(state_file.path()) {
+            let state: serde_json::Value = serde_json::from_str(&state)?;
+            if let Some(issues) = state["issues"].as_array().filter(|i| !i.is_empty()) {
+                pretty_assert_eq!(issues.len(), 1);
+                assert!(issues[0]["title"]
+                    .as_str()
+                    .unwrap()
+                    .contains("Test template xilinx"));
+                return Ok(());
+            }
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    Err("No issue was created for the failed run".into())
+}
+
+#[test]
+fn serve_requires_webhook_secret() -> Result<(), Box<dyn Error>> {
+    let mut cmd = Command::cargo_bin("gh-workflow-parser")?;
+    cmd.arg("serve")
+        .arg("--label=bug")
+        .arg("--kind=yocto")
+        .arg("--fake-github-cli")
+        .env_remove("GITHUB_WEBHOOK_SECRET");
+    cmd.assert().failure().stderr(predicates::str::contains(
+        "GITHUB_WEBHOOK_SECRET must be set",
+    ));
+    Ok(())
+}
+
+fn watch_command(dir: &TempDir) -> std::process::Command {
+    watch_command_with(
+        dir,
+        &["luftkode/distro-template"],
+        std::path::Path::new(&format!("{}/tests/fixtures", env!("CARGO_MANIFEST_DIR"))),
+    )
+}
+
+fn watch_command_with(
+    dir: &TempDir,
+    repos: &[&str],
+    fixtures: &std::path::Path,
+) -> std::process::Command {
+    let mut cmd = std::process::Command::new(assert_cmd::cargo::cargo_bin("gh-workflow-parser"));
+    cmd.arg("watch");
+    for repo in repos {
+        cmd.arg(format!("--repo={repo}"));
+    }
+    cmd.arg("--label=bug")
+        .arg("--kind=yocto")
+        .arg(format!(
+            "--state-file={}",
+            dir.child("watch.json").path().display()
+        ))
+        .arg("--fake-github-cli")
+        .arg(format!("--fixtures={}", fixtures.display()))
+        .arg(format!(
+            "--fake-state={}",
+            dir.child("state.json").path().display()
+        ));
+    cmd
+}
+
+fn fake_issue_titles(dir: &TempDir) -> Result<Vec<String>, Box<dyn Error>> {
+    let Ok(state) = fs::read_to_string(dir.child("state.json").path()) else {
+        return Ok(vec![]);
+    };
+    let state: serde_json::Value = serde_json::from_str(&state)?;
+    Ok(state["issues"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|issue| issue["title"].as_str().unwrap_or_default().to_string())
+        .collect())
+}
+
+#[test]
+fn fake_github_cli_watch_starts_after_newest_run() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    Command::from_std(watch_command(&dir))
+        .arg("--once")
+        .assert()
+        .success();
+
+    // Runs that completed before the repository was watched are skipped
+    pretty_assert_eq!(fake_issue_titles(&dir)?, Vec::<String>::new());
+    let state: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(dir.child("watch.json").path())?)?;
+    pretty_assert_eq!(
+        state["repos"]["https://github.com/luftkode/distro-template"]["last_run_id"],
+        7865472546u64
+    );
+    Ok(())
+}
+
+#[test]
+fn fake_github_cli_watch_creates_issues_for_new_failures() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    dir.child("watch.json").write_str(
+        r#"{"repos": {"https://github.com/luftkode/distro-template": {"last_run_id": 7850254127}}}"#,
+    )?;
+    Command::from_std(watch_command(&dir))
+        .arg("--once")
+        .assert()
+        .success();
+
+    let titles = fake_issue_titles(&dir)?;
+    pretty_assert_eq!(titles.len(), 2, "{titles:?}");
+    let state: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(dir.child("watch.json").path())?)?;
+    pretty_assert_eq!(
+        state["repos"]["https://github.com/luftkode/distro-template"]["last_run_id"],
+        7865472546u64
+    );
+
+    // Nothing is processed twice
+    Command::from_std(watch_command(&dir))
+        .arg("--once")
+        .assert()
+        .success();
+    pretty_assert_eq!(fake_issue_titles(&dir)?, titles);
+    Ok(())
+}
+
+#[test]
+fn fake_github_cli_watch_continues_after_failing_repo() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let fixtures = TempDir::new()?;
+    fixtures.copy_from(
+        format!("{}/tests/fixtures", env!("CARGO_MANIFEST_DIR")),
+        &["**/*"],
+    )?;
+    fixtures
+        .child("github.com/luftkode/broken/runs.json")
+        .write_str("not json")?;
+    dir.child("watch.json").write_str(
+        r#"{"repos": {"https://github.com/luftkode/distro-template": {"last_run_id": 7850254127}}}"#,
+    )?;
+    Command::from_std(watch_command_with(
+        &dir,
+        &["luftkode/broken", "luftkode/distro-template"],
+        fixtures.path(),
+    ))
+    .arg("--once")
+    .assert()
+    .failure()
+    .stderr(predicates::str::contains(
+        "Failed to poll 1 of 2 repositories",
+    ));
+
+    // The repository after the failing one is still polled
+    let titles = fake_issue_titles(&dir)?;
+    pretty_assert_eq!(titles.len(), 2, "{titles:?}");
+    let state: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(dir.child("watch.json").path())?)?;
+    pretty_assert_eq!(
+        state["repos"]["https://github.com/luftkode/distro-template"]["last_run_id"],
+        7865472546u64
+    );
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn fake_github_cli_watch_shuts_down_on_sigterm() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let mut watcher = Server(
+        watch_command(&dir)
+            .arg("--interval=3600")
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn()?,
+    );
+    // The state file is written after the first poll, the signal handlers are registered by then
+    for _ in 0..100 {
+        if dir.child("watch.json").path().exists() {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    std::process::Command::new("kill")
+        .arg("-TERM")
+        .arg(watcher.0.id().to_string())
+        .status()?;
+    for _ in 0..100 {
+        if let Some(status) = watcher.0.try_wait()? {
+            assert!(status.success(), "Exited with {status}");
+            return Ok(());
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    Err("The watcher did not shut down".into())
+}
+
+#[test]
+fn watch_requires_a_repository() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let mut cmd = Command::cargo_bin("gh-workflow-parser")?;
+    cmd.arg("watch")
+        .arg("--label=bug")
+        .arg("--kind=yocto")
+        .arg(format!(
+            "--state-file={}",
+            dir.child("watch.json").path().display()
+        ))
+        .arg("--fake-github-cli");
+    cmd.assert()
+        .failure()
+        .stderr(predicates::str::contains("No repositories to watch"));
+    Ok(())
+}
+
+#[test]
+fn fake_github_cli_detects_flaky_job() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let state_file = dir.child("state.json");
+
+    // The xilinx job passed for the same commit the night before
+    create_issue_with_fake_state(
+        &state_file,
+        &[
+            &format!("--fixtures={}/tests/fixtures", env!("CARGO_MANIFEST_DIR")),
+            "--detect-flaky",
+        ],
+    )?;
+
+    let state: serde_json::Value = serde_json::from_str(&fs::read_to_string(state_file.path())?)?;
+    let issue = &state["issues"][0];
+    pretty_assert_eq!(
+        issue["labels"],
+        serde_json::json!(["bug", "do_fetch", "flaky"])
+    );
+    let body = issue["body"].as_str().unwrap();
+    assert!(
+        body.contains("- `Test template xilinx`: flake rate 33% (1 of 3 recent results)"),
+        "{body}"
+    );
+    Ok(())
+}
+
+#[test]
+fn fake_github_cli_suppresses_flaky_job() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let state_file = dir.child("state.json");
+
+    let mut cmd = Command::cargo_bin("gh-workflow-parser")?;
+    cmd.arg("create-issue-from-run")
+        .arg("--repo=luftkode/distro-template")
+        .arg("--run-id=7865472546")
+        .arg("--label=bug")
+        .arg("--kind=yocto")
+        .arg("--issue-per=job")
+        .arg("--detect-flaky")
+        .arg("--suppress-flaky-below=0.5")
+        .arg("--fake-github-cli")
+        .arg(format!(
+            "--fixtures={}/tests/fixtures",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .arg(format!("--fake-state={}", state_file.path().display()));
+    cmd.assert().success();
+
+    // Only the raspberry job failed consistently
+    let state: serde_json::Value = serde_json::from_str(&fs::read_to_string(state_file.path())?)?;
+    let issues = state["issues"].as_array().unwrap();
+    pretty_assert_eq!(issues.len(), 1);
+    pretty_assert_eq!(
+        issues[0]["labels"],
+        serde_json::json!(["bug", "do_compile"])
+    );
+    Ok(())
+}